@@ -86,8 +86,12 @@ int32_t polar_enable_roles(polar_Polar *polar_ptr);
 
 int32_t polar_validate_roles_config(polar_Polar *polar_ptr, const char *validation_query_results);
 
+const char *polar_roles_model(polar_Polar *polar_ptr, const char *format);
+
 const char *polar_build_filter_plan(polar_Polar *polar_ptr,
                                     const char *types,
                                     const char *results,
                                     const char *variable,
                                     const char *class_tag);
+
+const char *polar_explain_filter_plan(const char *filter_plan, const char *format);
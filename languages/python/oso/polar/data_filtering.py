@@ -1,16 +1,21 @@
 from typing import Any, Optional
 from dataclasses import dataclass
 
-VALID_KINDS = ["parent", "children"]
+VALID_KINDS = ["parent", "children", "many_to_many"]
 
 
 # Used so we know what fetchers to call and how to match up constraints.
+# A "many_to_many" relationship joins my_field to through_my_field of the
+# through_type objects, and their through_other_field to other_field.
 @dataclass
 class Relationship:
     kind: str
     other_type: str
     my_field: str
     other_field: str
+    through_type: Optional[str] = None
+    through_my_field: Optional[str] = None
+    through_other_field: Optional[str] = None
 
 
 # @NOTE(Steve): Some of this stuff is very inconsistent right now. Names for fields
@@ -24,7 +29,18 @@ def serialize_types(types, class_names):
     for tag, fields in types.items():
         field_types = {}
         for k, v in fields.items():
-            if isinstance(v, Relationship):
+            if isinstance(v, Relationship) and v.kind == "many_to_many":
+                field_types[k] = {
+                    "ManyToMany": {
+                        "other_class_tag": v.other_type,
+                        "my_field": v.my_field,
+                        "through_class_tag": v.through_type,
+                        "through_my_field": v.through_my_field,
+                        "through_other_field": v.through_other_field,
+                        "other_field": v.other_field,
+                    }
+                }
+            elif isinstance(v, Relationship):
                 field_types[k] = {
                     "Relationship": {
                         "kind": v.kind,
@@ -56,7 +72,7 @@ class Ref:
 
 @dataclass
 class Constraint:
-    kind: str  # ["Eq", "In", "Contains"]
+    kind: str  # ["Eq", "In", "Contains", "Neq", "Lt", "Leq", "Gt", "Geq", "Nin", "NotContains"]
     field: str
     value: Any
 
@@ -74,12 +90,28 @@ class Constraint:
             return lambda x: getattr(x, self.field) in get_value(x)
         if self.kind == "Contains":
             return lambda x: get_value(x) in getattr(x, self.field)
+        if self.kind == "Nin":
+            return lambda x: getattr(x, self.field) not in get_value(x)
+        if self.kind == "NotContains":
+            return lambda x: get_value(x) not in getattr(x, self.field)
+        if self.kind == "Neq":
+            return lambda x: getattr(x, self.field) != get_value(x)
+        if self.kind == "Lt":
+            return lambda x: getattr(x, self.field) < get_value(x)
+        if self.kind == "Leq":
+            return lambda x: getattr(x, self.field) <= get_value(x)
+        if self.kind == "Gt":
+            return lambda x: getattr(x, self.field) > get_value(x)
+        if self.kind == "Geq":
+            return lambda x: getattr(x, self.field) >= get_value(x)
         assert False, "unknown constraint kind"
 
 
 def parse_constraint(polar, constraint):
     kind = constraint["kind"]
-    assert kind in ["Eq", "In", "Contains"]
+    assert kind in [
+        "Eq", "In", "Contains", "Neq", "Lt", "Leq", "Gt", "Geq", "Nin", "NotContains"
+    ]
     field = constraint["field"]
     value = constraint["value"]
 
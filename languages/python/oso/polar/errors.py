@@ -20,6 +20,7 @@ from polar.exceptions import (
     PolarApiError,
     RolesValidationError,
     ValidationError,
+    DataFilteringError,
 )
 
 
@@ -59,6 +60,8 @@ def get_python_error(err_str, enrich_message=None):
         return _roles_validation_error(message, details)
     elif kind == "Validation":
         return _validation_error(message, details)
+    elif kind == "DataFiltering":
+        return _data_filtering_error(message, body)
 
 
 def _parse_error(subkind, message, details):
@@ -100,6 +103,10 @@ def _validation_error(message, details):
     return ValidationError(message, details)
 
 
+def _data_filtering_error(message, details):
+    return DataFilteringError(message, details)
+
+
 def _api_error(subkind, message, details):
     if subkind == "Parameter":
         return ParameterError(message, details)
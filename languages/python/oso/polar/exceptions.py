@@ -132,6 +132,12 @@ class InvalidConstructorError(PolarRuntimeError):
     pass
 
 
+class DataFilteringError(PolarRuntimeError):
+    """A policy construct that can't be used for data filtering."""
+
+    pass
+
+
 # =================
 # PARSER EXCEPTIONS
 # =================
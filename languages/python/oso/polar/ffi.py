@@ -36,6 +36,15 @@ class Polar:
         self.process_messages()
         self.check_result(result)
 
+    def roles_model(self, format):
+        """Export the validated roles configuration as JSON or DOT."""
+        model = lib.polar_roles_model(self.ptr, to_c_str(format))
+        self.process_messages()
+        model_p = check_result(model)
+        model_s = ffi.string(model_p).decode()
+        lib.string_free(model_p)
+        return model_s
+
     def build_filter_plan(self, types, partial_results, variable, class_tag):
         """Get a filterplan for data filtering."""
         # @TODO(Steve): Pass types.
@@ -58,7 +58,7 @@ class Host:
                 field_type = obj_type_info[field]
                 if field_type.kind == "parent":
                     return self.classes[field_type.other_type]
-                elif field_type.kind == "children":
+                elif field_type.kind in ["children", "many_to_many"]:
                     return list
             else:
                 raise AttributeError(f"no field {field} on {obj.__name__}")
@@ -129,6 +129,14 @@ class Polar:
 
             self.ffi_polar.validate_roles_config(validation_query_results)
 
+    def roles_model(self, format="json"):
+        """Return the resources, roles, permissions, implications and
+        parent/child relationships of the roles configuration.
+
+        :param format: ``"json"`` or ``"dot"`` (Graphviz).
+        """
+        return self.ffi_polar.roles_model(format)
+
     def load_file(self, policy_file):
         """Load Polar policy from a ".polar" file."""
         policy_file = Path(policy_file)
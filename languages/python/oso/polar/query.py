@@ -114,12 +114,33 @@ class Query:
                                 field=rel.other_field,
                                 value=getattr(instance, rel.my_field),
                             )
+                            if rel.kind == "many_to_many":
+                                # Go through the intermediate objects first.
+                                assert rel.through_type in self.host.fetchers
+                                through_fetcher = self.host.fetchers[rel.through_type]
+                                through = through_fetcher(
+                                    [
+                                        Constraint(
+                                            kind="Eq",
+                                            field=rel.through_my_field,
+                                            value=getattr(instance, rel.my_field),
+                                        )
+                                    ]
+                                )
+                                constraint = Constraint(
+                                    kind="In",
+                                    field=rel.other_field,
+                                    value=[
+                                        getattr(t, rel.through_other_field)
+                                        for t in through
+                                    ],
+                                )
                             constraints = [constraint]
                             results = fetcher(constraints)
                             if rel.kind == "parent":
                                 assert len(results) == 1
                                 attr = results[0]
-                            elif rel.kind == "children":
+                            elif rel.kind in ["children", "many_to_many"]:
                                 attr = results
             if attr is None:
                 attr = getattr(instance, attribute)
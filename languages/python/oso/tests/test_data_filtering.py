@@ -63,6 +63,16 @@ def t(oso):
         foo_id: str
         data: str
 
+    @dataclass
+    class Tag:
+        id: str
+        name: str
+
+    @dataclass
+    class FooTag:
+        foo_id: str
+        tag_id: str
+
     hello_bar = Bar(id="hello", is_cool=True, is_still_cool=True)
     goodbye_bar = Bar(id="goodbye", is_cool=False, is_still_cool=True)
     hershey_bar = Bar(id="hershey", is_cool=False, is_still_cool=False)
@@ -78,6 +88,13 @@ def t(oso):
     bars = [hello_bar, goodbye_bar, hershey_bar]
     foos = [something_foo, another_foo, third_foo, fourth_foo]
     foo_logs = [fourth_log_a, third_log_b, another_log_c]
+    tags = [Tag(id="t1", name="cool"), Tag(id="t2", name="boring")]
+    foo_tags = [
+        FooTag(foo_id="another", tag_id="t1"),
+        FooTag(foo_id="third", tag_id="t2"),
+        FooTag(foo_id="fourth", tag_id="t1"),
+        FooTag(foo_id="fourth", tag_id="t2"),
+    ]
 
     def get_bars(constraints):
         return filter_array(bars, constraints)
@@ -88,6 +105,12 @@ def t(oso):
     def get_foo_logs(constraints):
         return filter_array(foo_logs, constraints)
 
+    def get_tags(constraints):
+        return filter_array(tags, constraints)
+
+    def get_foo_tags(constraints):
+        return filter_array(foo_tags, constraints)
+
     oso.register_class(
         Bar, types={"id": str, "is_cool": bool, "is_still_cool": bool}, fetcher=get_bars
     )
@@ -107,6 +130,15 @@ def t(oso):
                 my_field="id",
                 other_field="foo_id",
             ),
+            "tags": Relationship(
+                kind="many_to_many",
+                other_type="Tag",
+                my_field="id",
+                other_field="id",
+                through_type="FooTag",
+                through_my_field="foo_id",
+                through_other_field="tag_id",
+            ),
         },
         fetcher=get_foos,
     )
@@ -122,6 +154,10 @@ def t(oso):
         },
         fetcher=get_foo_logs,
     )
+    oso.register_class(Tag, types={"id": str, "name": str}, fetcher=get_tags)
+    oso.register_class(
+        FooTag, types={"foo_id": str, "tag_id": str}, fetcher=get_foo_tags
+    )
     # Sorta hacky, just return anything you want to use in a test.
     return {
         "Foo": Foo,
@@ -269,6 +305,29 @@ def test_relationship(oso, t):
     assert len(results) == 2
 
 
+def test_specializer_fields(oso, t):
+    policy = """
+    allow("steve", "get", _: Foo{is_fooey: true, bar: Bar{is_cool: true}});
+    allow("steve", "list", _: Foo{bar: {is_cool: false}});
+    """
+    oso.load_str(policy)
+    check_authz(oso, "steve", "get", t["Foo"], [t["another_foo"], t["third_foo"]])
+    check_authz(oso, "steve", "list", t["Foo"], [t["fourth_foo"]])
+
+
+def test_many_to_many(oso, t):
+    policy = """
+    allow("steve", "get", resource: Foo) if
+        tag in resource.tags and
+        tag.name = "cool";
+    allow("steve", "list", resource: Foo) if
+        not _ in resource.tags;
+    """
+    oso.load_str(policy)
+    check_authz(oso, "steve", "get", t["Foo"], [t["another_foo"], t["fourth_foo"]])
+    check_authz(oso, "steve", "list", t["Foo"], [t["foos"][0]])
+
+
 def test_var_in_values(oso, t):
     policy = """
     allow("steve", "get", resource: Foo) if
@@ -327,21 +386,14 @@ def test_var_in_value(oso, t):
     assert unord_eq(results, [t["fourth_log_a"], t["third_log_b"]])
 
 
-@pytest.mark.skip(
-    """
-    `or` constraints come from `not` negations and should instead be expanded in the
-    simplifier"""
-)
 def test_or(oso, t):
     policy = """
     allow("steve", "get", r: Foo) if
         not (r.id = "something" and r.bar_id = "hello");
     """
     oso.load_str(policy)
-    # assert oso.is_allowed("steve", "get", t['fourth_log_a'])
-
-    results = list(oso.get_allowed_resources("steve", "get", t["Foo"]))
-    assert len(results) == 2
+    expected = [t["another_foo"], t["third_foo"], t["fourth_foo"]]
+    check_authz(oso, "steve", "get", t["Foo"], expected)
 
 
 def test_field_cmp_field(oso, t):
@@ -377,7 +429,6 @@ def test_const_in_coll(oso, t):
     check_authz(oso, "gwen", "eat", t["Foo"], expected)
 
 
-@pytest.mark.xfail(reason="negation unsupported")
 def test_const_not_in_coll(oso, t):
     magic = 1
     oso.register_constant(magic, "magic")
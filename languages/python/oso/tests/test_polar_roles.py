@@ -1,3 +1,4 @@
+import json
 import pytest
 import timeit
 import os
@@ -149,7 +150,6 @@ def test_empty_role(init_oso):
     assert e.match("Must define actions or implications for a role.")
 
 
-@pytest.mark.skip(reason="TODO: More validation")
 def test_bad_namespace_perm(init_oso):
     # - assigning permission with bad namespace throws an error
     oso, _ = init_oso
@@ -356,6 +356,51 @@ def test_relationship_without_resources(init_oso):
     )
 
 
+def test_roles_model(init_oso):
+    oso, _ = init_oso
+    policy = """
+    resource(_type: Org, "org", actions, roles) if
+        actions = ["invite"] and
+        roles = {
+            owner: {
+                permissions: ["invite"],
+                implies: ["member", "repo:reader"]
+            },
+            member: {
+                permissions: ["invite"]
+            }
+        };
+
+    resource(_type: Repo, "repo", ["pull"], {reader: {permissions: ["pull"]}});
+
+    parent_child(parent_org: Org, repo: Repo) if
+        repo.org = parent_org;
+
+    actor_has_role_for_resource(_, _, _);
+    """
+    oso.load_str(policy)
+    with pytest.raises(RolesValidationError):
+        oso.roles_model()
+    oso.enable_roles()
+
+    model = json.loads(oso.roles_model())
+    org, repo = model["resources"]
+    assert org["namespace"] == "org"
+    assert org["class_tag"] == "Org"
+    assert org["roles"][1] == {
+        "name": "owner",
+        "permissions": ["invite"],
+        "implies": ["member"],
+        "implies_cross_resource": ["repo:reader"],
+    }
+    assert repo["actions"] == ["pull"]
+    assert model["relationships"] == [{"parent": "org", "child": "repo"}]
+
+    dot = oso.roles_model("dot")
+    assert dot.startswith("digraph roles_model {")
+    assert '"org:owner" -> "repo:reader" [label="implies", style=dashed];' in dot
+
+
 def test_role_namespaces(init_oso, sample_data):
     oso, session = init_oso
     policy = """
@@ -458,7 +503,6 @@ def test_duplicate_action(init_oso):
     assert e.match("Duplicate action invite for Org.")
 
 
-@pytest.mark.skip(reason="TODO: More validation")
 def test_undeclared_permission(init_oso):
     # - assign permission that wasn't declared
     oso, _ = init_oso
@@ -481,7 +525,6 @@ def test_undeclared_permission(init_oso):
         oso.enable_roles()
 
 
-@pytest.mark.skip(reason="TODO: More validation")
 def test_undeclared_role(init_oso):
     # - imply role that wasn't declared
     oso, _ = init_oso
@@ -0,0 +1,326 @@
+//! Data filtering: fetch all the resources an actor is authorized for.
+//!
+//! Classes used in data filtering declare the types of their fields and a fetcher
+//! on their `ClassBuilder`. The filter plan built by Polar is then resolved by
+//! calling the fetchers with the constraints of each fetch request.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use polar_core::data_filtering::{self as core, FilterPlan, Type};
+
+pub use polar_core::data_filtering::ConstraintKind;
+
+use crate::host::Host;
+use crate::PolarValue;
+
+/// A relationship between a field of one class and a field of another class.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Relationship {
+    /// Either `"parent"` (the field is a single other object),
+    /// `"children"` (the field is a list of other objects), or
+    /// `"many_to_many"` (the field is a list of other objects joined through `through`).
+    pub kind: String,
+    /// The class name of the related objects.
+    pub other_type: String,
+    /// The field on this class to join on.
+    pub my_field: String,
+    /// The field on the related class to join on.
+    pub other_field: String,
+    /// The intermediate class of a many-to-many relationship.
+    pub through: Option<Through>,
+}
+
+/// The intermediate class of a many-to-many relationship, like a join table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Through {
+    /// The class name of the intermediate objects.
+    pub other_type: String,
+    /// The field joined with `my_field` of the relationship.
+    pub my_field: String,
+    /// The field joined with `other_field` of the relationship.
+    pub other_field: String,
+}
+
+impl Relationship {
+    /// A field holding the single `other_type` object where `other_field` equals `my_field`.
+    pub fn parent(other_type: &str, my_field: &str, other_field: &str) -> Self {
+        Self {
+            kind: "parent".to_owned(),
+            other_type: other_type.to_owned(),
+            my_field: my_field.to_owned(),
+            other_field: other_field.to_owned(),
+            through: None,
+        }
+    }
+
+    /// A field holding all the `other_type` objects where `other_field` equals `my_field`.
+    pub fn children(other_type: &str, my_field: &str, other_field: &str) -> Self {
+        Self {
+            kind: "children".to_owned(),
+            other_type: other_type.to_owned(),
+            my_field: my_field.to_owned(),
+            other_field: other_field.to_owned(),
+            through: None,
+        }
+    }
+
+    /// A field holding all the `other_type` objects related through `through_type` objects:
+    /// `through_my_field` equals `my_field` and `through_other_field` equals `other_field`.
+    pub fn many_to_many(
+        other_type: &str,
+        my_field: &str,
+        through_type: &str,
+        through_my_field: &str,
+        through_other_field: &str,
+        other_field: &str,
+    ) -> Self {
+        Self {
+            kind: "many_to_many".to_owned(),
+            other_type: other_type.to_owned(),
+            my_field: my_field.to_owned(),
+            other_field: other_field.to_owned(),
+            through: Some(Through {
+                other_type: through_type.to_owned(),
+                my_field: through_my_field.to_owned(),
+                other_field: through_other_field.to_owned(),
+            }),
+        }
+    }
+}
+
+impl From<Relationship> for Type {
+    fn from(relationship: Relationship) -> Self {
+        match relationship.through {
+            None => Type::Relationship {
+                kind: relationship.kind,
+                other_class_tag: relationship.other_type,
+                my_field: relationship.my_field,
+                other_field: relationship.other_field,
+            },
+            Some(through) => Type::ManyToMany {
+                other_class_tag: relationship.other_type,
+                my_field: relationship.my_field,
+                through_class_tag: through.other_type,
+                through_my_field: through.my_field,
+                through_other_field: through.other_field,
+                other_field: relationship.other_field,
+            },
+        }
+    }
+}
+
+/// The value a field is constrained by.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstraintValue {
+    /// A known value. For `In` constraints, this is a list.
+    Value(PolarValue),
+    /// Another field on the same object.
+    Field(String),
+}
+
+/// A constraint on the objects returned by a fetcher.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constraint {
+    pub kind: ConstraintKind,
+    pub field: String,
+    pub value: ConstraintValue,
+}
+
+impl Constraint {
+    /// Check whether an object satisfies the constraint.
+    /// `get_field` looks up the value of a field on the object.
+    pub fn check<F>(&self, get_field: F) -> bool
+    where
+        F: Fn(&str) -> Option<PolarValue>,
+    {
+        let field = match get_field(&self.field) {
+            Some(field) => field,
+            None => return false,
+        };
+        let value = match &self.value {
+            ConstraintValue::Value(value) => value.clone(),
+            ConstraintValue::Field(other) => match get_field(other) {
+                Some(value) => value,
+                None => return false,
+            },
+        };
+        match self.kind {
+            ConstraintKind::Eq => field == value,
+            ConstraintKind::In => {
+                matches!(value, PolarValue::List(values) if values.contains(&field))
+            }
+            ConstraintKind::Contains => {
+                matches!(field, PolarValue::List(values) if values.contains(&value))
+            }
+            ConstraintKind::Nin => {
+                matches!(value, PolarValue::List(values) if !values.contains(&field))
+            }
+            ConstraintKind::NotContains => {
+                matches!(field, PolarValue::List(values) if !values.contains(&value))
+            }
+            ConstraintKind::Neq => field != value,
+            ConstraintKind::Lt => compare(&field, &value) == Some(Ordering::Less),
+            ConstraintKind::Leq => matches!(
+                compare(&field, &value),
+                Some(Ordering::Less) | Some(Ordering::Equal)
+            ),
+            ConstraintKind::Gt => compare(&field, &value) == Some(Ordering::Greater),
+            ConstraintKind::Geq => matches!(
+                compare(&field, &value),
+                Some(Ordering::Greater) | Some(Ordering::Equal)
+            ),
+        }
+    }
+}
+
+/// Order numbers and strings the way Polar does. Other values aren't comparable.
+fn compare(left: &PolarValue, right: &PolarValue) -> Option<Ordering> {
+    match (left, right) {
+        (PolarValue::Integer(l), PolarValue::Integer(r)) => l.partial_cmp(r),
+        (PolarValue::Integer(l), PolarValue::Float(r)) => (*l as f64).partial_cmp(r),
+        (PolarValue::Float(l), PolarValue::Integer(r)) => l.partial_cmp(&(*r as f64)),
+        (PolarValue::Float(l), PolarValue::Float(r)) => l.partial_cmp(r),
+        (PolarValue::String(l), PolarValue::String(r)) => l.partial_cmp(r),
+        _ => None,
+    }
+}
+
+/// Substitute the results of previous fetches into a constraint.
+fn ground_constraint(
+    constraint: &core::Constraint,
+    results: &HashMap<String, Vec<PolarValue>>,
+    host: &mut Host,
+) -> crate::Result<Constraint> {
+    let value = match &constraint.value {
+        core::ConstraintValue::Term(term) => {
+            ConstraintValue::Value(PolarValue::from_term(term, host)?)
+        }
+        core::ConstraintValue::Field(field) => ConstraintValue::Field(field.clone()),
+        core::ConstraintValue::Ref(core::Ref { field, result_id }) => {
+            let values = results.get(result_id).cloned().unwrap_or_default();
+            let values = match field {
+                None => values,
+                Some(field) => values
+                    .into_iter()
+                    .map(|value| get_field(&value, field, host))
+                    .collect::<crate::Result<Vec<_>>>()?,
+            };
+            ConstraintValue::Value(PolarValue::List(values))
+        }
+    };
+    Ok(Constraint {
+        kind: constraint.kind.clone(),
+        field: constraint.field.clone(),
+        value,
+    })
+}
+
+fn contains(results: &[PolarValue], value: &PolarValue, host: &Host) -> bool {
+    results.iter().any(|result| match (result, value) {
+        (PolarValue::Instance(a), PolarValue::Instance(b)) => a.equals(b, host).unwrap_or(false),
+        (a, b) => a == b,
+    })
+}
+
+/// Resolve a filter plan, calling `fetch` with the class tag and constraints of each
+/// fetch request.
+///
+/// Results are deduplicated with the equality check of their class, if there is one.
+fn resolve_plan<F>(
+    host: &mut Host,
+    filter_plan: &FilterPlan,
+    mut fetch: F,
+) -> crate::Result<Vec<PolarValue>>
+where
+    F: FnMut(&mut Host, &str, &[Constraint]) -> crate::Result<Vec<PolarValue>>,
+{
+    let mut results: Vec<PolarValue> = vec![];
+    for result_set in &filter_plan.result_sets {
+        let mut set_results = HashMap::new();
+        for id in &result_set.resolve_order {
+            let request = result_set
+                .requests
+                .get(id)
+                .ok_or_else(|| crate::OsoError::Custom {
+                    message: format!("invalid filter plan: no fetch request {}", id),
+                })?;
+            let constraints = request
+                .constraints
+                .iter()
+                .map(|constraint| ground_constraint(constraint, &set_results, host))
+                .collect::<crate::Result<Vec<_>>>()?;
+            let fetched = fetch(host, &request.class_tag, &constraints)?;
+            set_results.insert(id.clone(), fetched);
+        }
+        for result in set_results
+            .remove(&result_set.result_id)
+            .unwrap_or_default()
+        {
+            if !contains(&results, &result, host) {
+                results.push(result);
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// Resolve a filter plan by calling the registered fetchers.
+pub(crate) fn filter_data(
+    host: &mut Host,
+    filter_plan: &FilterPlan,
+) -> crate::Result<Vec<PolarValue>> {
+    resolve_plan(host, filter_plan, |host, class_tag, constraints| {
+        host.get_class(class_tag)?.fetch(constraints)
+    })
+}
+
+/// Look up a field of an instance or a map.
+fn get_field(object: &PolarValue, field: &str, host: &mut Host) -> crate::Result<PolarValue> {
+    match object {
+        PolarValue::Instance(instance) => instance.get_attr(field, host),
+        PolarValue::Map(map) => match map.get(field) {
+            Some(value) => Ok(value.clone()),
+            None => lazy_error!("no field {} on map", field),
+        },
+        _ => lazy_error!("cannot look up field {} on a non-instance", field),
+    }
+}
+
+/// Resolve a filter plan by checking the constraints of each fetch request against every
+/// object of its class in `data`, a map from class tags to all the objects of that class.
+pub(crate) fn filter_data_in_memory(
+    host: &mut Host,
+    filter_plan: &FilterPlan,
+    data: &HashMap<String, Vec<PolarValue>>,
+) -> crate::Result<Vec<PolarValue>> {
+    resolve_plan(host, filter_plan, |host, class_tag, constraints| {
+        let objects = match data.get(class_tag) {
+            Some(objects) => objects,
+            None => return lazy_error!("no data for class {}", class_tag),
+        };
+        let mut fetched = vec![];
+        for object in objects {
+            // Constraints can only look fields up through the host, so look them up first.
+            let mut fields = HashMap::new();
+            for constraint in constraints {
+                let mut names = vec![&constraint.field];
+                if let ConstraintValue::Field(other) = &constraint.value {
+                    names.push(other);
+                }
+                for name in names {
+                    if !fields.contains_key(name) {
+                        fields.insert(name.clone(), get_field(object, name, host)?);
+                    }
+                }
+            }
+            if constraints
+                .iter()
+                .all(|constraint| constraint.check(|name| fields.get(name).cloned()))
+            {
+                fetched.push(object.clone());
+            }
+        }
+        Ok(fetched)
+    })
+}
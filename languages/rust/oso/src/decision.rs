@@ -0,0 +1,100 @@
+//! Authorization decisions combining `allow` and `deny` rules.
+
+use std::sync::Arc;
+
+use polar_core::explain::{FailedRule, ProofNode};
+use polar_core::rules::Rule;
+
+use crate::query::Query;
+
+/// How [`Oso::is_allowed`](crate::Oso::is_allowed) and [`Oso::decide`](crate::Oso::decide)
+/// combine the `allow(actor, action, resource)` and `deny(actor, action, resource)` rules
+/// of a policy.
+///
+/// Under every strategy a request that no rule applies to is denied.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DecisionStrategy {
+    /// Denied if any `deny` rule applies, otherwise allowed if any `allow` rule applies.
+    #[default]
+    DenyOverrides,
+    /// Allowed if any `allow` rule applies, otherwise denied.
+    AllowOverrides,
+    /// Decided by the applicable `allow` or `deny` rule that comes first in the policy.
+    /// Every result of both rules is evaluated to find it.
+    FirstApplicable,
+}
+
+/// Whether a rule allows or denies.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+impl Effect {
+    pub(crate) fn rule_name(self) -> &'static str {
+        match self {
+            Effect::Allow => "allow",
+            Effect::Deny => "deny",
+        }
+    }
+}
+
+/// The rule that decided an authorization request.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecidingRule {
+    pub effect: Effect,
+    /// The rule's Polar source, e.g. `deny(actor: User, _, _) if actor.suspended = true;`
+    pub source: String,
+    /// Where the rule was loaded, to order rules for `FirstApplicable`.
+    position: Option<(u64, usize)>,
+}
+
+impl DecidingRule {
+    /// The top-level `allow` or `deny` rule applied in the last result of `query`.
+    pub(crate) fn from_query(effect: Effect, query: &Query) -> Option<Self> {
+        let rule = query.applied_rule()?;
+        Some(Self::new(effect, &rule, query))
+    }
+
+    fn new(effect: Effect, rule: &Arc<Rule>, query: &Query) -> Self {
+        let position = rule.params.first().and_then(|param| {
+            let term = &param.parameter;
+            Some((term.get_source_id()?, term.offset()))
+        });
+        Self {
+            effect,
+            source: query.rule_source(rule),
+            position,
+        }
+    }
+
+    /// Whether this rule was loaded before `other`. Rules without a source come last.
+    pub(crate) fn precedes(&self, other: &Self) -> bool {
+        match (self.position, other.position) {
+            (Some(a), Some(b)) => a < b,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+/// The outcome of an authorization request.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    /// The rule that decided the request, if one applied.
+    pub rule: Option<DecidingRule>,
+}
+
+/// Why an authorization request was decided the way it was, from
+/// [`Oso::explain`](crate::Oso::explain).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Explanation {
+    pub decision: Decision,
+    /// The deciding rule and the goals that satisfied it.
+    pub proof: Option<ProofNode>,
+    /// If no rule decided the request, the `allow` rules and how far each got, closest
+    /// matches first.
+    pub failed_rules: Vec<FailedRule>,
+}
@@ -5,10 +5,13 @@ use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 
+use polar_core::data_filtering::Type;
+
+use crate::data_filtering::{Constraint, Relationship};
 use crate::errors::{InvalidCallError, OsoError};
 
 use super::class_method::{
-    AttributeGetter, ClassMethod, Constructor, InstanceMethod, RegisterHook,
+    AttributeGetter, ClassMethod, Constructor, Fetcher, InstanceMethod, RegisterHook,
 };
 use super::from_polar::FromPolarList;
 use super::method::{Function, Method};
@@ -20,6 +23,7 @@ type Attributes = HashMap<&'static str, AttributeGetter>;
 type RegisterHooks = Vec<RegisterHook>;
 type ClassMethods = HashMap<&'static str, ClassMethod>;
 type InstanceMethods = HashMap<&'static str, InstanceMethod>;
+type FieldTypes = HashMap<String, Type>;
 
 fn equality_not_supported(
 ) -> Box<dyn Fn(&Host, &Instance, &Instance) -> crate::Result<bool> + Send + Sync> {
@@ -73,6 +77,11 @@ pub struct Class {
 
     // Hooks to be called on the class once it's been registered with host.
     pub register_hooks: RegisterHooks,
+
+    /// Types of the fields of `T`, used to build data filtering plans.
+    pub(crate) types: FieldTypes,
+    /// A function that fetches instances of `T` matching a list of data filtering constraints.
+    fetcher: Option<Fetcher>,
 }
 
 impl Class {
@@ -105,6 +114,15 @@ impl Class {
         attr.clone().invoke(args)
     }
 
+    /// Fetch the instances of this class matching `constraints` using the
+    /// registered fetcher.
+    pub fn fetch(&self, constraints: &[Constraint]) -> crate::Result<Vec<PolarValue>> {
+        let fetcher = self.fetcher.as_ref().ok_or_else(|| OsoError::Custom {
+            message: format!("MissingFetcherError: {} has no fetcher", self.name),
+        })?;
+        fetcher.invoke(constraints)
+    }
+
     fn get_method(&self, name: &str) -> Option<InstanceMethod> {
         tracing::trace!({class=%self.name, name}, "get_method");
         if self.type_id == TypeId::of::<Class>() {
@@ -153,6 +171,8 @@ where
                 into_iter: Arc::from(iterator_not_supported()),
                 type_id: TypeId::of::<T>(),
                 register_hooks: RegisterHooks::new(),
+                types: FieldTypes::new(),
+                fetcher: None,
             },
             ty: std::marker::PhantomData,
         }
@@ -318,6 +338,38 @@ where
         self
     }
 
+    /// Declare the Polar class of field `name` for data filtering.
+    /// `class.add_field_type("id", "Integer")`
+    pub fn add_field_type(mut self, name: &str, class_tag: &str) -> Self {
+        self.class.types.insert(
+            name.to_string(),
+            Type::Base {
+                class_tag: class_tag.to_string(),
+            },
+        );
+        self
+    }
+
+    /// Declare a relationship field to another class for data filtering.
+    /// `class.add_relationship("bar", Relationship::parent("Bar", "bar_id", "id"))`
+    pub fn add_relationship(mut self, name: &str, relationship: Relationship) -> Self {
+        self.class
+            .types
+            .insert(name.to_string(), relationship.into());
+        self
+    }
+
+    /// Set the function used by data filtering to fetch instances of the class
+    /// that match a list of constraints.
+    pub fn set_fetcher<F, R>(mut self, f: F) -> Self
+    where
+        F: Fn(&[Constraint]) -> R + Send + Sync + 'static,
+        R: ToPolarResult,
+    {
+        self.class.fetcher = Some(Fetcher::new(f));
+        self
+    }
+
     /// Finish building a build the class
     pub fn build(self) -> Class {
         self.class
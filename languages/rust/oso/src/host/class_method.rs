@@ -1,6 +1,8 @@
 //! Wrapper structs for the generic `Function` and `Method` traits
 use std::sync::Arc;
 
+use crate::data_filtering::Constraint;
+use crate::errors::TypeError;
 use crate::host::from_polar::FromPolarList;
 use crate::host::to_polar::{PolarIterator, ToPolar, ToPolarResult};
 
@@ -15,6 +17,7 @@ fn join<A, B>(left: crate::Result<A>, right: crate::Result<B>) -> crate::Result<
 type TypeErasedFunction<R> = Arc<dyn Fn(Vec<PolarValue>) -> crate::Result<R> + Send + Sync>;
 type TypeErasedMethod<R> =
     Arc<dyn Fn(&Instance, Vec<PolarValue>, &mut Host) -> crate::Result<R> + Send + Sync>;
+type TypeErasedFetcher = Arc<dyn Fn(&[Constraint]) -> crate::Result<PolarValue> + Send + Sync>;
 
 #[derive(Clone)]
 pub struct RegisterHook(Arc<dyn Fn(&mut crate::Oso) -> crate::Result<()> + Send + Sync + 'static>);
@@ -172,3 +175,28 @@ impl ClassMethod {
         self.0(args)
     }
 }
+
+#[derive(Clone)]
+pub struct Fetcher(TypeErasedFetcher);
+
+impl Fetcher {
+    pub fn new<F, R>(f: F) -> Self
+    where
+        F: Fn(&[Constraint]) -> R + Send + Sync + 'static,
+        R: ToPolarResult,
+    {
+        Self(Arc::new(move |constraints: &[Constraint]| {
+            f(constraints).to_polar_result()
+        }))
+    }
+
+    /// Fetch the objects matching `constraints`.
+    ///
+    /// Returns: The fetched objects, or an error if the fetcher didn't return a list.
+    pub fn invoke(&self, constraints: &[Constraint]) -> crate::Result<Vec<PolarValue>> {
+        match self.0(constraints)? {
+            PolarValue::List(results) => Ok(results),
+            _ => Err(TypeError::expected("List").user()),
+        }
+    }
+}
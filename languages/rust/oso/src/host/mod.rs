@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
+use polar_core::data_filtering::{Type, Types};
+
 use crate::errors::OsoError;
 use crate::Polar;
 
@@ -174,6 +176,52 @@ impl Host {
         Ok(res)
     }
 
+    /// Starting from the class `base_tag`, follow the declared field types along `path` and
+    /// check whether the resulting class is `class_tag`.
+    pub fn isa_with_path(
+        &self,
+        base_tag: &str,
+        path: &[PolarValue],
+        class_tag: &str,
+    ) -> crate::Result<bool> {
+        let mut tag = base_tag.to_owned();
+        for field in path {
+            let field = match field {
+                PolarValue::String(field) => field,
+                _ => return Err(crate::errors::TypeError::expected("String").user()),
+            };
+            tag = match self.get_class(&tag)?.types.get(field) {
+                Some(Type::Base { class_tag }) => class_tag.clone(),
+                Some(Type::Relationship {
+                    kind,
+                    other_class_tag,
+                    ..
+                }) => match kind.as_str() {
+                    "parent" => other_class_tag.clone(),
+                    "children" => "List".to_owned(),
+                    _ => return lazy_error!("unknown relationship kind {}", kind),
+                },
+                Some(Type::ManyToMany { .. }) => "List".to_owned(),
+                None => return lazy_error!("no type information for field {} on {}", field, tag),
+            };
+        }
+        Ok(tag == class_tag)
+    }
+
+    pub fn is_subclass(&self, left_tag: &str, right_tag: &str) -> bool {
+        // Rust has no notion of inheritance, so a class is only a subclass of itself.
+        left_tag == right_tag
+    }
+
+    /// Collect the declared field types of all registered classes for data filtering.
+    pub fn types(&self) -> Types {
+        self.classes
+            .iter()
+            .filter(|(_, class)| !class.types.is_empty())
+            .map(|(name, class)| (name.clone(), class.types.clone()))
+            .collect()
+    }
+
     pub fn is_subspecializer(&self, _id: u64, _left_tag: &str, _right_tag: &str) -> bool {
         // Rust has no notion of inheritance, so there are no subspecializers.
         false
@@ -75,6 +75,8 @@
 pub mod macros;
 
 pub(crate) mod builtins;
+mod data_filtering;
+mod decision;
 pub mod errors;
 mod extras;
 mod host;
@@ -82,8 +84,14 @@ mod oso;
 mod query;
 
 pub use crate::oso::{Action, Oso};
+pub use data_filtering::{Constraint, ConstraintKind, ConstraintValue, Relationship, Through};
+pub use decision::{DecidingRule, Decision, DecisionStrategy, Effect, Explanation};
 pub use errors::{OsoError, Result};
 pub use host::{Class, ClassBuilder, FromPolar, FromPolarList, PolarValue, ToPolar, ToPolarList};
+pub use polar_core::explain::{FailedGoal, FailedRule, ProofNode, SourceLocation};
+pub use polar_core::roles_model::{
+    ParentChild, ResourceModel, RoleModel, RolesModel, RolesModelFormat,
+};
 pub use query::{Query, ResultSet};
 
 use polar_core::polar::Polar;
@@ -1,13 +1,21 @@
 //! Communicate with the Polar virtual machine: load rules, make queries, etc/
+use polar_core::data_filtering::FilterPlan;
 use polar_core::events::ResultEvent;
-use polar_core::terms::{Call, Symbol, Term, Value};
-
-use std::collections::HashSet;
+use polar_core::explain::{FailedRule, ProofNode};
+use polar_core::roles_model::RolesModel;
+use polar_core::terms::{
+    Call, Dictionary, InstanceLiteral, Operation, Operator, Pattern, Symbol, Term, Value,
+};
+
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::hash::Hash;
 use std::io::Read;
 use std::sync::Arc;
 
+use crate::data_filtering::{filter_data, filter_data_in_memory};
+use crate::decision::{DecidingRule, Decision, DecisionStrategy, Effect, Explanation};
 use crate::host::Host;
 use crate::query::Query;
 use crate::{FromPolar, OsoError, PolarValue, ToPolar, ToPolarList};
@@ -19,6 +27,8 @@ pub struct Oso {
     inner: Arc<polar_core::polar::Polar>,
     host: Host,
     polar_roles_enabled: bool,
+    decision_strategy: DecisionStrategy,
+    trace_queries: bool,
 }
 
 impl Default for Oso {
@@ -57,6 +67,8 @@ impl Oso {
             inner,
             host,
             polar_roles_enabled: false,
+            decision_strategy: DecisionStrategy::default(),
+            trace_queries: false,
         };
 
         for class in crate::builtins::classes() {
@@ -68,7 +80,9 @@ impl Oso {
         oso
     }
 
-    /// High level interface for authorization decisions. Makes an allow query with the given actor, action and resource and returns true or false.
+    /// High level interface for authorization decisions. Makes `allow` and `deny` queries with
+    /// the given actor, action and resource and combines them with the [`DecisionStrategy`]
+    /// set by [`Oso::set_decision_strategy`] to return true or false.
     pub fn is_allowed<Actor, Action, Resource>(
         &self,
         actor: Actor,
@@ -80,12 +94,180 @@ impl Oso {
         Action: ToPolar,
         Resource: ToPolar,
     {
-        let mut query = self.query_rule("allow", (actor, action, resource)).unwrap();
-        match query.next() {
-            Some(Ok(_)) => Ok(true),
-            Some(Err(e)) => Err(e),
-            None => Ok(false),
+        Ok(self.decide(actor, action, resource)?.allowed)
+    }
+
+    /// Like [`Oso::is_allowed`], but also return the `allow` or `deny` rule that decided.
+    /// # Examples
+    /// ```ignore
+    /// let decision = oso.decide(user, "delete", repo)?;
+    /// if let Some(rule) = decision.rule {
+    ///     println!("{:?} by {}", rule.effect, rule.source);
+    /// }
+    /// ```
+    pub fn decide<Actor, Action, Resource>(
+        &self,
+        actor: Actor,
+        action: Action,
+        resource: Resource,
+    ) -> crate::Result<Decision>
+    where
+        Actor: ToPolar,
+        Action: ToPolar,
+        Resource: ToPolar,
+    {
+        let args = (actor.to_polar(), action.to_polar(), resource.to_polar());
+        Ok(self.decision(args, false)?.0)
+    }
+
+    /// Explain an authorization decision.
+    ///
+    /// If a rule decided the request, the explanation holds the proof that the rule applies.
+    /// If none did, it holds the `allow` rules with the first goal of each that failed,
+    /// closest matches first.
+    /// # Examples
+    /// ```ignore
+    /// let explanation = oso.explain(user, "delete", repo)?;
+    /// for failed in explanation.failed_rules {
+    ///     if let Some(goal) = failed.failed_goal {
+    ///         println!("{} failed at {}", failed.rule, goal.source);
+    ///     }
+    /// }
+    /// ```
+    pub fn explain<Actor, Action, Resource>(
+        &self,
+        actor: Actor,
+        action: Action,
+        resource: Resource,
+    ) -> crate::Result<Explanation>
+    where
+        Actor: ToPolar,
+        Action: ToPolar,
+        Resource: ToPolar,
+    {
+        let args = (actor.to_polar(), action.to_polar(), resource.to_polar());
+        let (decision, proof) = self.decision(args.clone(), true)?;
+        let failed_rules = if decision.rule.is_none() {
+            self.failed_rules(Effect::Allow.rule_name(), args)?
+        } else {
+            vec![]
+        };
+        Ok(Explanation {
+            decision,
+            proof,
+            failed_rules,
+        })
+    }
+
+    /// How far each rule named `name` gets with `args`, closest matches first.
+    fn failed_rules(&self, name: &str, args: impl ToPolarList) -> crate::Result<Vec<FailedRule>> {
+        let mut host = self.host.clone();
+        let args = args
+            .to_polar_list()
+            .iter()
+            .map(|value| value.to_term(&mut host))
+            .collect::<Vec<_>>();
+        let mut failed_rules = vec![];
+        for rule_queries in self.inner.rule_goal_queries(name, &args) {
+            let mut succeeded = 0;
+            for term in &rule_queries.queries {
+                let query = self.inner.new_query_from_term(term.clone(), false);
+                check_messages!(self.inner);
+                match Query::new(query, host.clone()).next() {
+                    Some(result) => {
+                        result?;
+                        succeeded += 1;
+                    }
+                    None => break,
+                }
+            }
+            failed_rules.push(self.inner.failed_rule(&rule_queries, succeeded));
+        }
+        // Stable, so rules that got equally far stay in policy order.
+        failed_rules.sort_by_key(|failed| {
+            std::cmp::Reverse((failed.failed_goal.is_some(), failed.satisfied_goals))
+        });
+        Ok(failed_rules)
+    }
+
+    /// Set how `allow` and `deny` rules are combined. Defaults to
+    /// [`DecisionStrategy::DenyOverrides`].
+    pub fn set_decision_strategy(&mut self, strategy: DecisionStrategy) {
+        self.decision_strategy = strategy;
+    }
+
+    /// Decide a request, and if `prove` is set, also return the proof that the deciding
+    /// rule applies.
+    fn decision(
+        &self,
+        args: (PolarValue, PolarValue, PolarValue),
+        prove: bool,
+    ) -> crate::Result<(Decision, Option<ProofNode>)> {
+        let proof = |query: &Query| if prove { query.proof() } else { None };
+        // Policies without rules of an effect, most often `deny`, need no query for it.
+        let has_rules = |effect: Effect| self.inner.has_rule(effect.rule_name(), 3);
+        // Find the first result of a rule with `effect`, if any.
+        let first_applicable =
+            |effect: Effect| -> crate::Result<Option<(Option<DecidingRule>, Option<ProofNode>)>> {
+                if !has_rules(effect) {
+                    return Ok(None);
+                }
+                let mut query = self.query_rule(effect.rule_name(), args.clone())?;
+                match query.next_result() {
+                    Some(Ok(_)) => Ok(Some((
+                        DecidingRule::from_query(effect, &query),
+                        proof(&query),
+                    ))),
+                    Some(Err(e)) => Err(e),
+                    None => Ok(None),
+                }
+            };
+        let denied = Decision {
+            allowed: false,
+            rule: None,
+        };
+
+        let order = match self.decision_strategy {
+            DecisionStrategy::DenyOverrides => [Effect::Deny, Effect::Allow],
+            // Deny rules can't change the decision, but may be the rule that decided it.
+            DecisionStrategy::AllowOverrides => [Effect::Allow, Effect::Deny],
+            DecisionStrategy::FirstApplicable => {
+                // Results come in rule specificity order, so look at every result of both
+                // rules for the one loaded first.
+                let mut first: Option<(DecidingRule, Option<ProofNode>)> = None;
+                for effect in [Effect::Allow, Effect::Deny] {
+                    if !has_rules(effect) {
+                        continue;
+                    }
+                    let mut query = self.query_rule(effect.rule_name(), args.clone())?;
+                    while let Some(result) = query.next_result() {
+                        result?;
+                        if let Some(rule) = DecidingRule::from_query(effect, &query) {
+                            if first.as_ref().is_none_or(|(first, _)| rule.precedes(first)) {
+                                first = Some((rule, proof(&query)));
+                            }
+                        }
+                    }
+                }
+                return Ok(first.map_or((denied, None), |(rule, proof)| {
+                    let decision = Decision {
+                        allowed: rule.effect == Effect::Allow,
+                        rule: Some(rule),
+                    };
+                    (decision, proof)
+                }));
+            }
+        };
+        for effect in order {
+            if let Some((rule, proof)) = first_applicable(effect)? {
+                let decision = Decision {
+                    allowed: effect == Effect::Allow,
+                    rule,
+                };
+                return Ok((decision, proof));
+            }
         }
+        Ok((denied, None))
     }
 
     /// Get the actions actor is allowed to take on resource.
@@ -134,6 +316,246 @@ impl Oso {
         Ok(set)
     }
 
+    /// Build the filter plan for the resources of type `Resource` that `actor` is allowed to
+    /// perform `action` on, together with the host the plan's values were created on.
+    fn filter_plan<Actor, Resource>(
+        &self,
+        actor: Actor,
+        action: impl ToPolar,
+    ) -> crate::Result<(FilterPlan, Host)>
+    where
+        Actor: ToPolar,
+        Resource: 'static,
+    {
+        self.partial_filter_plan::<Resource, _>("resource", |host, resource| {
+            vec![
+                actor.to_polar().to_term(host),
+                action.to_polar().to_term(host),
+                resource,
+            ]
+        })
+    }
+
+    /// Build the filter plan for the values of type `T` that the variable `name` can take in
+    /// an `allow` query with the arguments returned by `args`, which receives the variable.
+    fn partial_filter_plan<T, F>(&self, name: &str, args: F) -> crate::Result<(FilterPlan, Host)>
+    where
+        T: 'static,
+        F: FnOnce(&mut Host, Term) -> Vec<Term>,
+    {
+        let class_name = self
+            .host
+            .get_class_by_type_id(TypeId::of::<T>())?
+            .name
+            .clone();
+
+        let mut host = self.host.clone();
+        host.accept_expression = true;
+
+        let variable = Symbol::new(name);
+        let args = args(
+            &mut host,
+            Term::new_temporary(Value::Variable(variable.clone())),
+        );
+        let query_term = Term::new_from_ffi(Value::Call(Call {
+            name: Symbol::new("allow"),
+            args,
+            kwargs: None,
+        }));
+        let mut query = self.inner.new_query_from_term(query_term, false);
+        check_messages!(self.inner);
+
+        // variable matches T{}
+        let isa = Term::new_temporary(Value::Expression(Operation {
+            operator: Operator::Isa,
+            args: vec![
+                Term::new_temporary(Value::Variable(variable.clone())),
+                Term::new_temporary(Value::Pattern(Pattern::Instance(InstanceLiteral {
+                    tag: Symbol::new(&class_name),
+                    fields: Dictionary::new(),
+                }))),
+            ],
+        }));
+        let constraint = Term::new_temporary(Value::Expression(Operation {
+            operator: Operator::And,
+            args: vec![isa],
+        }));
+        query.bind(variable.clone(), constraint)?;
+
+        let results = Query::new(query, host.clone())
+            .map(|result| result.map(|result| result.into_event()))
+            .collect::<crate::Result<Vec<ResultEvent>>>()?;
+
+        let plan =
+            self.inner
+                .build_filter_plan(self.host.types(), results, &variable.0, &class_name)?;
+        Ok((plan, host))
+    }
+
+    /// Get the filter plan for the resources of type `Resource` that `actor` is allowed to
+    /// perform `action` on.
+    ///
+    /// The plan can be inspected or executed against your own data store. Use
+    /// [`Oso::authorized_resources`] to execute it with the fetchers registered on each class.
+    pub fn authorized_query<Actor, Resource>(
+        &self,
+        actor: Actor,
+        action: impl ToPolar,
+    ) -> crate::Result<FilterPlan>
+    where
+        Actor: ToPolar,
+        Resource: 'static,
+    {
+        self.filter_plan::<Actor, Resource>(actor, action)
+            .map(|(plan, _)| plan)
+    }
+
+    /// Get all the resources of type `Resource` that `actor` is allowed to perform `action` on.
+    ///
+    /// The class of `Resource`, and of every class related to it, must be registered with
+    /// its field types and a fetcher.
+    /// # Examples
+    /// ```ignore
+    /// oso.register_class(
+    ///     Post::get_polar_class_builder()
+    ///         .add_field_type("published", "Boolean")
+    ///         .set_fetcher(|constraints: &[Constraint]| fetch_posts(constraints))
+    ///         .build(),
+    /// )?;
+    /// oso.load_str(r#"allow(_, "read", post: Post) if post.published = true;"#)?;
+    ///
+    /// let posts: Vec<Post> = oso.authorized_resources::<_, Post>(actor, "read")?;
+    /// ```
+    pub fn authorized_resources<Actor, Resource>(
+        &self,
+        actor: Actor,
+        action: impl ToPolar,
+    ) -> crate::Result<Vec<Resource>>
+    where
+        Actor: ToPolar,
+        Resource: FromPolar + 'static,
+    {
+        let (plan, mut host) = self.filter_plan::<Actor, Resource>(actor, action)?;
+        filter_data(&mut host, &plan)?
+            .into_iter()
+            .map(Resource::from_polar)
+            .collect()
+    }
+
+    /// Get all the resources of type `Resource` that `actor` is allowed to perform `action` on,
+    /// out of the objects in `data`.
+    ///
+    /// `data` maps the name of every class the policy filters on to all the objects of that
+    /// class, so no fetchers are needed: each object is checked against the constraints of the
+    /// filter plan. This is meant for small data sets and for testing policies.
+    /// # Examples
+    /// ```ignore
+    /// let data = hashmap! {
+    ///     "Post".to_owned() => posts.into_iter().map(ToPolar::to_polar).collect(),
+    /// };
+    /// let posts: Vec<Post> = oso.authorized_resources_in::<_, Post>(actor, "read", &data)?;
+    /// ```
+    pub fn authorized_resources_in<Actor, Resource>(
+        &self,
+        actor: Actor,
+        action: impl ToPolar,
+        data: &HashMap<String, Vec<PolarValue>>,
+    ) -> crate::Result<Vec<Resource>>
+    where
+        Actor: ToPolar,
+        Resource: FromPolar + 'static,
+    {
+        let (plan, mut host) = self.filter_plan::<Actor, Resource>(actor, action)?;
+        filter_data_in_memory(&mut host, &plan, data)?
+            .into_iter()
+            .map(Resource::from_polar)
+            .collect()
+    }
+
+    /// Get all the actors of type `Actor` that are allowed to perform `action` on `resource`.
+    ///
+    /// Like [`Oso::authorized_resources`], the policy is partially evaluated with the actor
+    /// unknown, so the class of `Actor`, and of every class related to it (e.g. the role
+    /// assignments `actor_has_role_for_resource` looks up), must be registered with its field
+    /// types and a fetcher.
+    /// # Examples
+    /// ```ignore
+    /// // Who can share this document?
+    /// let users: Vec<User> = oso.authorized_actors::<User, _>("share", document)?;
+    /// ```
+    pub fn authorized_actors<Actor, Resource>(
+        &self,
+        action: impl ToPolar,
+        resource: Resource,
+    ) -> crate::Result<Vec<Actor>>
+    where
+        Actor: FromPolar + 'static,
+        Resource: ToPolar,
+    {
+        let (plan, mut host) = self.partial_filter_plan::<Actor, _>("actor", |host, actor| {
+            vec![
+                actor,
+                action.to_polar().to_term(host),
+                resource.to_polar().to_term(host),
+            ]
+        })?;
+        filter_data(&mut host, &plan)?
+            .into_iter()
+            .map(Actor::from_polar)
+            .collect()
+    }
+
+    /// Get the roles that grant `action` on `resource`, as pairs of a role name and the
+    /// resource the role is on: either `resource` or one of its ancestors, or the string
+    /// `"global"` for global roles. An actor with any of these roles is allowed to perform
+    /// `action` by `role_allows`, unless another of their roles denies it. Roles that deny
+    /// `action` themselves, through a role they imply, are left out. Resources are compared
+    /// with the equality check of their class, if it has one.
+    ///
+    /// Roles must be enabled with [`Oso::enable_roles`].
+    pub fn roles_granting<Resource>(
+        &self,
+        action: impl ToPolar,
+        resource: Resource,
+    ) -> crate::Result<Vec<(String, PolarValue)>>
+    where
+        Resource: ToPolar,
+    {
+        if !self.polar_roles_enabled {
+            return lazy_error!("Roles are not enabled, call `Oso::enable_roles` first.");
+        }
+        let query = self.query_rule(
+            "__oso_internal__roles_granting",
+            (
+                action,
+                resource,
+                PolarValue::Variable("role".to_owned()),
+                PolarValue::Variable("role_resource".to_owned()),
+            ),
+        )?;
+        // A role can grant the action through more than one chain of implications, so drop
+        // repeated roles, comparing instances with their class's equality check.
+        let same_resource = |a: &PolarValue, b: &PolarValue| match (a, b) {
+            (PolarValue::Instance(a), PolarValue::Instance(b)) => {
+                a.equals(b, &self.host).unwrap_or(false)
+            }
+            _ => a == b,
+        };
+        let mut roles: Vec<(String, PolarValue)> = vec![];
+        for result in query {
+            let result = result?;
+            let role: String = result.get_typed("role")?;
+            let role_resource: PolarValue = result.get_typed("role_resource")?;
+            if !roles
+                .iter()
+                .any(|(r, rr)| *r == role && same_resource(rr, &role_resource))
+            {
+                roles.push((role, role_resource));
+            }
+        }
+        Ok(roles)
+    }
+
     /// Clear out all files and rules that have been loaded.
     pub fn clear_rules(&mut self) -> crate::Result<()> {
         self.inner.clear_rules();
@@ -199,7 +621,7 @@ impl Oso {
     /// oso.query("x = 1 or x = 2");
     /// ```
     pub fn query(&self, s: &str) -> crate::Result<Query> {
-        let query = self.inner.new_query(s, false)?;
+        let query = self.inner.new_query(s, self.trace_queries)?;
         check_messages!(self.inner);
         let query = Query::new(query, self.host.clone());
         Ok(query)
@@ -225,12 +647,27 @@ impl Oso {
             kwargs: None,
         });
         let query_term = Term::new_from_ffi(query_value);
-        let query = self.inner.new_query_from_term(query_term, false);
+        let query = self
+            .inner
+            .new_query_from_term(query_term, self.trace_queries);
         check_messages!(self.inner);
         let query = Query::new(query, query_host);
         Ok(query)
     }
 
+    /// Trace the queries made with [`Oso::query`] and [`Oso::query_rule`], so each
+    /// [`ResultSet`](crate::ResultSet) holds the rules and goals that produced it.
+    /// Tracing is off by default, since it slows queries down.
+    /// # Examples
+    /// ```ignore
+    /// oso.set_query_tracing(true);
+    /// let result = oso.query_rule("allow", (user, "read", repo))?.next().unwrap()?;
+    /// println!("{}", result.formatted_trace().unwrap());
+    /// ```
+    pub fn set_query_tracing(&mut self, enabled: bool) {
+        self.trace_queries = enabled;
+    }
+
     /// Register a rust type as a Polar class.
     /// See [`oso::Class`] docs.
     pub fn register_class(&mut self, class: crate::host::Class) -> crate::Result<()> {
@@ -285,6 +722,13 @@ impl Oso {
         self.polar_roles_enabled = true;
         Ok(())
     }
+
+    /// The resources, roles, permissions, implications and parent/child
+    /// relationships of the roles configuration, available after `enable_roles`.
+    /// Render it with `RolesModel::to_json` or `RolesModel::to_dot`.
+    pub fn roles_model(&self) -> crate::Result<RolesModel> {
+        Ok(self.inner.roles_model()?)
+    }
 }
 
 // Make sure the `Oso` object is threadsafe
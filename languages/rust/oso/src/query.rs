@@ -1,12 +1,15 @@
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use crate::errors::OsoError;
 use crate::host::{Host, Instance, PolarIterator};
 use crate::{FromPolar, PolarValue};
 
 use polar_core::events::*;
+use polar_core::explain::ProofNode;
 use polar_core::terms::*;
+use polar_core::traces::{Trace, TraceResult};
 
 impl Iterator for Query {
     type Item = crate::Result<ResultSet>;
@@ -47,8 +50,11 @@ impl Query {
             let result = match event {
                 QueryEvent::None => Ok(()),
                 QueryEvent::Done { .. } => return None,
-                QueryEvent::Result { bindings, .. } => {
-                    return Some(ResultSet::from_bindings(bindings, self.host.clone()));
+                QueryEvent::Result { bindings, trace } => {
+                    return Some(
+                        ResultSet::from_bindings(bindings, self.host.clone())
+                            .map(|result| ResultSet { trace, ..result }),
+                    );
                 }
                 QueryEvent::MakeExternal {
                     instance_id,
@@ -74,6 +80,17 @@ impl Query {
                     instance,
                     class_tag,
                 } => self.handle_external_isa(call_id, instance, class_tag),
+                QueryEvent::ExternalIsaWithPath {
+                    call_id,
+                    base_tag,
+                    path,
+                    class_tag,
+                } => self.handle_external_isa_with_path(call_id, base_tag, path, class_tag),
+                QueryEvent::ExternalIsSubclass {
+                    call_id,
+                    left_class_tag,
+                    right_class_tag,
+                } => self.handle_external_is_subclass(call_id, left_class_tag, right_class_tag),
                 QueryEvent::ExternalIsSubSpecializer {
                     call_id,
                     instance_id,
@@ -105,6 +122,23 @@ impl Query {
         }
     }
 
+    /// The source of `rule` as it was loaded.
+    pub(crate) fn rule_source(&self, rule: &polar_core::rules::Rule) -> String {
+        self.inner.rule_source(rule)
+    }
+
+    /// The top-level rule applied to produce the last result.
+    pub(crate) fn applied_rule(&self) -> Option<Arc<polar_core::rules::Rule>> {
+        self.inner.applied_rule()
+    }
+
+    /// The proof that the top-level rule applies in the last result.
+    pub(crate) fn proof(&self) -> Option<ProofNode> {
+        // The root of the proof is the queried call, applied by the rule.
+        let call = self.inner.proof().into_iter().next()?;
+        call.children.into_iter().next()
+    }
+
     fn question_result(&mut self, call_id: u64, result: bool) -> crate::Result<()> {
         Ok(self.inner.question_result(call_id, result)?)
     }
@@ -231,6 +265,34 @@ impl Query {
         Ok(())
     }
 
+    fn handle_external_isa_with_path(
+        &mut self,
+        call_id: u64,
+        base_tag: Symbol,
+        path: TermList,
+        class_tag: Symbol,
+    ) -> crate::Result<()> {
+        tracing::debug!(base = %base_tag, path = ?path, class = %class_tag, "isa_with_path");
+        let path = path
+            .iter()
+            .map(|term| PolarValue::from_term(term, &self.host))
+            .collect::<crate::Result<Vec<PolarValue>>>()?;
+        let res = self.host.isa_with_path(&base_tag.0, &path, &class_tag.0)?;
+        self.question_result(call_id, res)?;
+        Ok(())
+    }
+
+    fn handle_external_is_subclass(
+        &mut self,
+        call_id: u64,
+        left_class_tag: Symbol,
+        right_class_tag: Symbol,
+    ) -> crate::Result<()> {
+        let res = self.host.is_subclass(&left_class_tag.0, &right_class_tag.0);
+        self.question_result(call_id, res)?;
+        Ok(())
+    }
+
     fn handle_external_is_subspecializer(
         &mut self,
         call_id: u64,
@@ -257,6 +319,7 @@ impl Query {
 pub struct ResultSet {
     bindings: polar_core::kb::Bindings,
     host: crate::host::Host,
+    pub(crate) trace: Option<TraceResult>,
 }
 
 impl ResultSet {
@@ -278,7 +341,11 @@ This may mean you performed an operation in your policy over an unbound variable
             }
         }
 
-        Ok(Self { bindings, host })
+        Ok(Self {
+            bindings,
+            host,
+            trace: None,
+        })
     }
 
     /// Return the keys in bindings.
@@ -306,6 +373,18 @@ This may mean you performed an operation in your policy over an unbound variable
             .and_then(T::from_polar)
     }
 
+    /// The tree of rules and goals that produced this result, if the query was traced.
+    /// See [`Oso::set_query_tracing`](crate::Oso::set_query_tracing).
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref().map(|trace| trace.trace.as_ref())
+    }
+
+    /// The trace drawn as an indented list of the goals and rules that produced this
+    /// result, if the query was traced.
+    pub fn formatted_trace(&self) -> Option<&str> {
+        self.trace.as_ref().map(|trace| trace.formatted.as_str())
+    }
+
     pub fn into_event(self) -> ResultEvent {
         ResultEvent::new(self.bindings)
     }
@@ -332,3 +411,7 @@ impl<S: AsRef<str>, T: crate::host::FromPolar + PartialEq<T>> PartialEq<HashMap<
 // Make sure the `Query` object is _not_ threadsafe
 #[cfg(test)]
 static_assertions::assert_not_impl_any!(Query: Send, Sync);
+
+// Results, and their traces, can be sent to other threads
+#[cfg(test)]
+static_assertions::assert_impl_all!(ResultSet: Send, Sync);
@@ -0,0 +1,650 @@
+use std::collections::HashMap;
+
+use oso::errors::polar::{ErrorKind, PolarError};
+use oso::errors::OsoError;
+use oso::{Constraint, Oso, PolarClass, PolarValue, Relationship, ToPolar};
+
+mod common;
+
+#[derive(PolarClass, Debug, Clone, PartialEq)]
+struct Bar {
+    #[polar(attribute)]
+    id: String,
+    #[polar(attribute)]
+    is_cool: bool,
+    #[polar(attribute)]
+    is_still_cool: bool,
+}
+
+#[derive(PolarClass, Debug, Clone, PartialEq)]
+struct Foo {
+    #[polar(attribute)]
+    id: String,
+    #[polar(attribute)]
+    bar_id: String,
+    #[polar(attribute)]
+    is_fooey: bool,
+    #[polar(attribute)]
+    numbers: Vec<i64>,
+}
+
+#[derive(PolarClass, Debug, Clone, PartialEq)]
+struct FooLogRecord {
+    #[polar(attribute)]
+    id: String,
+    #[polar(attribute)]
+    foo_id: String,
+    #[polar(attribute)]
+    data: String,
+}
+
+#[derive(PolarClass, Debug, Clone, PartialEq)]
+struct Tag {
+    #[polar(attribute)]
+    id: String,
+    #[polar(attribute)]
+    name: String,
+}
+
+#[derive(PolarClass, Debug, Clone, PartialEq)]
+struct FooTag {
+    #[polar(attribute)]
+    foo_id: String,
+    #[polar(attribute)]
+    tag_id: String,
+}
+
+impl Bar {
+    fn new(id: &str, is_cool: bool, is_still_cool: bool) -> Self {
+        Self {
+            id: id.to_owned(),
+            is_cool,
+            is_still_cool,
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<PolarValue> {
+        match name {
+            "id" => Some(self.id.clone().to_polar()),
+            "is_cool" => Some(self.is_cool.to_polar()),
+            "is_still_cool" => Some(self.is_still_cool.to_polar()),
+            _ => None,
+        }
+    }
+}
+
+impl Foo {
+    fn new(id: &str, bar_id: &str, is_fooey: bool, numbers: Vec<i64>) -> Self {
+        Self {
+            id: id.to_owned(),
+            bar_id: bar_id.to_owned(),
+            is_fooey,
+            numbers,
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<PolarValue> {
+        match name {
+            "id" => Some(self.id.clone().to_polar()),
+            "bar_id" => Some(self.bar_id.clone().to_polar()),
+            "is_fooey" => Some(self.is_fooey.to_polar()),
+            "numbers" => Some(self.numbers.clone().to_polar()),
+            _ => None,
+        }
+    }
+}
+
+impl FooLogRecord {
+    fn new(id: &str, foo_id: &str, data: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            foo_id: foo_id.to_owned(),
+            data: data.to_owned(),
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<PolarValue> {
+        match name {
+            "id" => Some(self.id.clone().to_polar()),
+            "foo_id" => Some(self.foo_id.clone().to_polar()),
+            "data" => Some(self.data.clone().to_polar()),
+            _ => None,
+        }
+    }
+}
+
+impl Tag {
+    fn field(&self, name: &str) -> Option<PolarValue> {
+        match name {
+            "id" => Some(self.id.clone().to_polar()),
+            "name" => Some(self.name.clone().to_polar()),
+            _ => None,
+        }
+    }
+}
+
+impl FooTag {
+    fn field(&self, name: &str) -> Option<PolarValue> {
+        match name {
+            "foo_id" => Some(self.foo_id.clone().to_polar()),
+            "tag_id" => Some(self.tag_id.clone().to_polar()),
+            _ => None,
+        }
+    }
+}
+
+fn bars() -> Vec<Bar> {
+    vec![
+        Bar::new("hello", true, true),
+        Bar::new("goodbye", false, true),
+        Bar::new("hershey", false, false),
+    ]
+}
+
+fn foos() -> Vec<Foo> {
+    vec![
+        Foo::new("something", "hello", false, vec![]),
+        Foo::new("another", "hello", true, vec![1]),
+        Foo::new("third", "hello", true, vec![2]),
+        Foo::new("fourth", "goodbye", true, vec![2, 1]),
+    ]
+}
+
+fn foo_logs() -> Vec<FooLogRecord> {
+    vec![
+        FooLogRecord::new("a", "fourth", "hello"),
+        FooLogRecord::new("b", "third", "world"),
+        FooLogRecord::new("c", "another", "steve"),
+    ]
+}
+
+fn tags() -> Vec<Tag> {
+    vec![
+        Tag {
+            id: "t1".to_owned(),
+            name: "cool".to_owned(),
+        },
+        Tag {
+            id: "t2".to_owned(),
+            name: "boring".to_owned(),
+        },
+    ]
+}
+
+fn foo_tags() -> Vec<FooTag> {
+    [
+        ("another", "t1"),
+        ("third", "t2"),
+        ("fourth", "t1"),
+        ("fourth", "t2"),
+    ]
+    .iter()
+    .map(|(foo_id, tag_id)| FooTag {
+        foo_id: foo_id.to_string(),
+        tag_id: tag_id.to_string(),
+    })
+    .collect()
+}
+
+fn test_oso() -> Oso {
+    common::setup();
+    let mut oso = Oso::new();
+    oso.register_class(
+        Bar::get_polar_class_builder()
+            .with_equality_check()
+            .add_field_type("id", "String")
+            .add_field_type("is_cool", "Boolean")
+            .add_field_type("is_still_cool", "Boolean")
+            .set_fetcher(|constraints: &[Constraint]| {
+                bars()
+                    .into_iter()
+                    .filter(|bar| constraints.iter().all(|c| c.check(|f| bar.field(f))))
+                    .collect::<Vec<_>>()
+            })
+            .build(),
+    )
+    .unwrap();
+    oso.register_class(
+        Foo::get_polar_class_builder()
+            .with_equality_check()
+            .add_field_type("id", "String")
+            .add_field_type("bar_id", "String")
+            .add_field_type("is_fooey", "Boolean")
+            .add_field_type("numbers", "List")
+            .add_relationship("bar", Relationship::parent("Bar", "bar_id", "id"))
+            .add_relationship(
+                "logs",
+                Relationship::children("FooLogRecord", "id", "foo_id"),
+            )
+            .add_relationship(
+                "tags",
+                Relationship::many_to_many("Tag", "id", "FooTag", "foo_id", "tag_id", "id"),
+            )
+            .set_fetcher(|constraints: &[Constraint]| {
+                foos()
+                    .into_iter()
+                    .filter(|foo| constraints.iter().all(|c| c.check(|f| foo.field(f))))
+                    .collect::<Vec<_>>()
+            })
+            .build(),
+    )
+    .unwrap();
+    oso.register_class(
+        FooLogRecord::get_polar_class_builder()
+            .with_equality_check()
+            .add_field_type("id", "String")
+            .add_field_type("foo_id", "String")
+            .add_field_type("data", "String")
+            .set_fetcher(|constraints: &[Constraint]| {
+                foo_logs()
+                    .into_iter()
+                    .filter(|log| constraints.iter().all(|c| c.check(|f| log.field(f))))
+                    .collect::<Vec<_>>()
+            })
+            .build(),
+    )
+    .unwrap();
+    oso.register_class(
+        Tag::get_polar_class_builder()
+            .add_field_type("id", "String")
+            .add_field_type("name", "String")
+            .set_fetcher(|constraints: &[Constraint]| {
+                tags()
+                    .into_iter()
+                    .filter(|tag| constraints.iter().all(|c| c.check(|f| tag.field(f))))
+                    .collect::<Vec<_>>()
+            })
+            .build(),
+    )
+    .unwrap();
+    oso.register_class(
+        FooTag::get_polar_class_builder()
+            .add_field_type("foo_id", "String")
+            .add_field_type("tag_id", "String")
+            .set_fetcher(|constraints: &[Constraint]| {
+                foo_tags()
+                    .into_iter()
+                    .filter(|foo_tag| constraints.iter().all(|c| c.check(|f| foo_tag.field(f))))
+                    .collect::<Vec<_>>()
+            })
+            .build(),
+    )
+    .unwrap();
+    oso
+}
+
+fn ids(foos: Vec<Foo>) -> Vec<String> {
+    let mut ids: Vec<String> = foos.into_iter().map(|foo| foo.id).collect();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn test_no_relationships() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            resource.is_fooey = true;"#,
+    )?;
+    let results = oso.authorized_resources::<_, Foo>("steve", "get")?;
+    assert_eq!(ids(results), vec!["another", "fourth", "third"]);
+    Ok(())
+}
+
+#[test]
+fn test_relationship() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            resource.bar = bar and
+            bar.is_cool = true and
+            resource.is_fooey = true;"#,
+    )?;
+    let results = oso.authorized_resources::<_, Foo>("steve", "get")?;
+    assert_eq!(ids(results), vec!["another", "third"]);
+    Ok(())
+}
+
+#[test]
+fn test_specializer_fields() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", _resource: Foo{is_fooey: true, bar: Bar{is_cool: true}});
+          allow("steve", "list", _resource: Foo{bar: {is_cool: false}});"#,
+    )?;
+    let results = oso.authorized_resources::<_, Foo>("steve", "get")?;
+    assert_eq!(ids(results), vec!["another", "third"]);
+    let results = oso.authorized_resources::<_, Foo>("steve", "list")?;
+    assert_eq!(ids(results), vec!["fourth"]);
+    Ok(())
+}
+
+#[test]
+fn test_var_in_var() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            log in resource.logs and
+            log.data = "hello";"#,
+    )?;
+    let results = oso.authorized_resources::<_, Foo>("steve", "get")?;
+    assert_eq!(ids(results), vec!["fourth"]);
+    Ok(())
+}
+
+#[test]
+fn test_many_to_many() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            tag in resource.tags and
+            tag.name = "cool";
+          allow("steve", "list", resource: Foo) if
+            not _ in resource.tags;"#,
+    )?;
+    let results = oso.authorized_resources::<_, Foo>("steve", "get")?;
+    assert_eq!(ids(results), vec!["another", "fourth"]);
+    let results = oso.authorized_resources::<_, Foo>("steve", "list")?;
+    assert_eq!(ids(results), vec!["something"]);
+    Ok(())
+}
+
+#[test]
+fn test_val_in_var() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            1 in resource.numbers and 2 in resource.numbers;"#,
+    )?;
+    let results = oso.authorized_resources::<_, Foo>("steve", "get")?;
+    assert_eq!(ids(results), vec!["fourth"]);
+    Ok(())
+}
+
+#[test]
+fn test_field_cmp_field() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow(_, _, bar: Bar) if
+            bar.is_cool = bar.is_still_cool;"#,
+    )?;
+    let results = oso.authorized_resources::<_, Bar>("gwen", "eat")?;
+    let expected: Vec<Bar> = bars()
+        .into_iter()
+        .filter(|bar| bar.is_cool == bar.is_still_cool)
+        .collect();
+    assert_eq!(results, expected);
+    Ok(())
+}
+
+#[test]
+fn test_const_in_coll() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.register_constant(1, "magic")?;
+    oso.load_str(
+        r#"allow(_, _, foo: Foo) if
+            magic in foo.numbers;"#,
+    )?;
+    let results = oso.authorized_resources::<_, Foo>("gwen", "eat")?;
+    assert_eq!(ids(results), vec!["another", "fourth"]);
+    Ok(())
+}
+
+#[test]
+fn test_actor_field() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow(actor: Bar, "read", log: FooLogRecord) if
+            log.data = actor.id;"#,
+    )?;
+    let actor = Bar::new("hello", true, true);
+    let results = oso.authorized_resources::<_, FooLogRecord>(actor, "read")?;
+    assert_eq!(results, vec![FooLogRecord::new("a", "fourth", "hello")]);
+    Ok(())
+}
+
+#[test]
+fn test_or() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            not (resource.id != "something" and resource.bar_id != "goodbye");"#,
+    )?;
+    let results = oso.authorized_resources::<_, Foo>("steve", "get")?;
+    assert_eq!(ids(results), vec!["fourth", "something"]);
+    Ok(())
+}
+
+#[test]
+fn test_comparisons() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            resource.id > "f" and resource.bar_id != "goodbye";
+          allow("steve", "list", resource: Foo) if
+            resource.id <= resource.bar_id;"#,
+    )?;
+    let results = oso.authorized_resources::<_, Foo>("steve", "get")?;
+    assert_eq!(ids(results), vec!["something", "third"]);
+    let results = oso.authorized_resources::<_, Foo>("steve", "list")?;
+    assert_eq!(ids(results), vec!["another", "fourth"]);
+    Ok(())
+}
+
+#[test]
+fn test_compare_actor_field() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow(actor: Bar, "read", log: FooLogRecord) if
+            log.data != actor.id and log.foo_id < actor.id;"#,
+    )?;
+    let actor = Bar::new("hello", true, true);
+    let results = oso.authorized_resources::<_, FooLogRecord>(actor, "read")?;
+    assert_eq!(results, vec![FooLogRecord::new("c", "another", "steve")]);
+    Ok(())
+}
+
+#[test]
+fn test_not() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            not resource.is_fooey;
+          allow("steve", "list", resource: Foo) if
+            not 1 in resource.numbers;
+          allow("steve", "read", resource: Foo) if
+            not _ in resource.logs;"#,
+    )?;
+    let results = oso.authorized_resources::<_, Foo>("steve", "get")?;
+    assert_eq!(ids(results), vec!["something"]);
+    let results = oso.authorized_resources::<_, Foo>("steve", "list")?;
+    assert_eq!(ids(results), vec!["something", "third"]);
+    let results = oso.authorized_resources::<_, Foo>("steve", "read")?;
+    assert_eq!(ids(results), vec!["something"]);
+    Ok(())
+}
+
+#[test]
+fn test_authorized_query() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            resource.bar = bar and
+            bar.is_cool = true;"#,
+    )?;
+    let plan = oso.authorized_query::<_, Foo>("steve", "get")?;
+    assert_eq!(plan.result_sets.len(), 1);
+    let result_set = &plan.result_sets[0];
+    assert_eq!(result_set.resolve_order.len(), 2);
+    let result = &result_set.requests[&result_set.result_id];
+    assert_eq!(result.class_tag, "Foo");
+    Ok(())
+}
+
+/// An `Oso` with the test classes registered without fetchers, and all their objects.
+fn in_memory_oso() -> (Oso, HashMap<String, Vec<PolarValue>>) {
+    common::setup();
+    let mut oso = Oso::new();
+    oso.register_class(
+        Bar::get_polar_class_builder()
+            .with_equality_check()
+            .add_field_type("id", "String")
+            .add_field_type("is_cool", "Boolean")
+            .add_field_type("is_still_cool", "Boolean")
+            .build(),
+    )
+    .unwrap();
+    oso.register_class(
+        Foo::get_polar_class_builder()
+            .with_equality_check()
+            .add_field_type("id", "String")
+            .add_field_type("bar_id", "String")
+            .add_field_type("is_fooey", "Boolean")
+            .add_field_type("numbers", "List")
+            .add_relationship("bar", Relationship::parent("Bar", "bar_id", "id"))
+            .add_relationship(
+                "logs",
+                Relationship::children("FooLogRecord", "id", "foo_id"),
+            )
+            .build(),
+    )
+    .unwrap();
+    oso.register_class(
+        FooLogRecord::get_polar_class_builder()
+            .with_equality_check()
+            .add_field_type("id", "String")
+            .add_field_type("foo_id", "String")
+            .add_field_type("data", "String")
+            .build(),
+    )
+    .unwrap();
+
+    let mut data = HashMap::new();
+    data.insert(
+        "Bar".to_owned(),
+        bars().into_iter().map(ToPolar::to_polar).collect(),
+    );
+    data.insert(
+        "Foo".to_owned(),
+        foos().into_iter().map(ToPolar::to_polar).collect(),
+    );
+    data.insert(
+        "FooLogRecord".to_owned(),
+        foo_logs().into_iter().map(ToPolar::to_polar).collect(),
+    );
+    (oso, data)
+}
+
+#[test]
+fn test_in_memory() -> oso::Result<()> {
+    let (mut oso, data) = in_memory_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            resource.bar = bar and
+            bar.is_cool = true and
+            resource.is_fooey = true;
+          allow("steve", "list", resource: Foo) if
+            log in resource.logs and
+            log.data = "hello";
+          allow("steve", "read", resource: Foo) if
+            not _ in resource.logs;
+          allow("steve", "edit", _resource: Foo{bar: {is_cool: false}});
+          allow(_, _, bar: Bar) if
+            bar.is_cool = bar.is_still_cool;"#,
+    )?;
+    let results = oso.authorized_resources_in::<_, Foo>("steve", "get", &data)?;
+    assert_eq!(ids(results), vec!["another", "third"]);
+    let results = oso.authorized_resources_in::<_, Foo>("steve", "list", &data)?;
+    assert_eq!(ids(results), vec!["fourth"]);
+    let results = oso.authorized_resources_in::<_, Foo>("steve", "read", &data)?;
+    assert_eq!(ids(results), vec!["something"]);
+    let results = oso.authorized_resources_in::<_, Foo>("steve", "edit", &data)?;
+    assert_eq!(ids(results), vec!["fourth"]);
+
+    let results = oso.authorized_resources_in::<_, Bar>("gwen", "eat", &data)?;
+    let expected: Vec<Bar> = bars()
+        .into_iter()
+        .filter(|bar| bar.is_cool == bar.is_still_cool)
+        .collect();
+    assert_eq!(results, expected);
+    Ok(())
+}
+
+#[test]
+fn test_in_memory_missing_data() -> oso::Result<()> {
+    let (mut oso, mut data) = in_memory_oso();
+    data.remove("Bar");
+    oso.load_str(r#"allow(_, _, foo: Foo) if foo.bar.is_cool = true;"#)?;
+    let err = oso
+        .authorized_resources_in::<_, Foo>("gwen", "eat", &data)
+        .unwrap_err();
+    assert!(err.to_string().contains("no data for class Bar"));
+    Ok(())
+}
+
+#[test]
+fn test_in_memory_maps() -> oso::Result<()> {
+    let (mut oso, mut data) = in_memory_oso();
+    let fields = ["id", "is_cool", "is_still_cool"];
+    data.insert(
+        "Bar".to_owned(),
+        bars()
+            .into_iter()
+            .map(|bar| {
+                PolarValue::Map(
+                    fields
+                        .iter()
+                        .map(|&name| (name.to_owned(), bar.field(name).unwrap()))
+                        .collect(),
+                )
+            })
+            .collect(),
+    );
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            resource.bar = bar and
+            bar.is_cool = true and
+            resource.is_fooey = true;"#,
+    )?;
+    let results = oso.authorized_resources_in::<_, Foo>("steve", "get", &data)?;
+    assert_eq!(ids(results), vec!["another", "third"]);
+    Ok(())
+}
+
+#[test]
+fn test_missing_fetcher() {
+    let mut oso = Oso::new();
+    oso.register_class(
+        Bar::get_polar_class_builder()
+            .add_field_type("is_cool", "Boolean")
+            .build(),
+    )
+    .unwrap();
+    oso.load_str(r#"allow(_, _, bar: Bar) if bar.is_cool = true;"#)
+        .unwrap();
+    let err = oso
+        .authorized_resources::<_, Bar>("gwen", "eat")
+        .unwrap_err();
+    assert!(err.to_string().contains("Bar has no fetcher"));
+}
+
+#[test]
+fn test_unsupported_policy() -> oso::Result<()> {
+    let mut oso = test_oso();
+    oso.load_str(
+        r#"allow("steve", "get", resource: Foo) if
+            resource.id + 1 = 2;"#,
+    )?;
+    let err = oso
+        .authorized_resources::<_, Foo>("steve", "get")
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        OsoError::Polar(PolarError {
+            kind: ErrorKind::DataFiltering(_),
+            ..
+        })
+    ));
+    assert!(err.to_string().contains("addition is not supported"));
+    Ok(())
+}
@@ -1,4 +1,5 @@
-use oso::{Action, Oso, PolarClass};
+use oso::{Action, DecisionStrategy, Effect, Oso, PolarClass};
+use polar_core::traces::Node;
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
@@ -220,3 +221,138 @@ fn test_get_allowed_actions() -> oso::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_deny_rules() -> oso::Result<()> {
+    common::setup();
+    let mut oso = Oso::new();
+    oso.register_class(Actor::get_polar_class()).unwrap();
+    oso.register_class(Widget::get_polar_class()).unwrap();
+    oso.load_str(
+        r#"deny(actor: Actor, "delete", _: Widget) if actor.name = "intern";
+           allow(_: Actor, _, _: Widget);
+           deny(_: Actor, "delete", widget: Widget) if widget.id = 1;"#,
+    )?;
+    let intern = || Actor::new("intern".to_owned());
+    let admin = || Actor::new("admin".to_owned());
+
+    // Deny rules override allow rules by default.
+    assert!(oso.is_allowed(admin(), "read", Widget::new(1))?);
+    assert!(!oso.is_allowed(intern(), "delete", Widget::new(2))?);
+    assert!(oso.is_allowed(admin(), "delete", Widget::new(2))?);
+    let decision = oso.decide(intern(), "delete", Widget::new(2))?;
+    assert!(!decision.allowed);
+    let rule = decision.rule.unwrap();
+    assert_eq!(rule.effect, Effect::Deny);
+    assert_eq!(
+        rule.source,
+        r#"deny(actor: Actor, "delete", _: Widget) if actor.name = "intern";"#
+    );
+    let decision = oso.decide(admin(), "read", Widget::new(2))?;
+    assert!(decision.allowed);
+    assert_eq!(decision.rule.unwrap().effect, Effect::Allow);
+
+    oso.set_decision_strategy(DecisionStrategy::AllowOverrides);
+    assert!(oso.is_allowed(intern(), "delete", Widget::new(2))?);
+    assert_eq!(
+        oso.decide(intern(), "delete", Widget::new(2))?
+            .rule
+            .unwrap()
+            .effect,
+        Effect::Allow
+    );
+
+    // The first rule in the policy decides: the intern's deny comes before the allow, the
+    // widget's deny after it.
+    oso.set_decision_strategy(DecisionStrategy::FirstApplicable);
+    assert!(!oso.is_allowed(intern(), "delete", Widget::new(1))?);
+    assert!(oso.is_allowed(admin(), "delete", Widget::new(1))?);
+    let decision = oso.decide(admin(), "delete", Widget::new(1))?;
+    assert!(decision.allowed);
+    assert_eq!(
+        decision.rule.unwrap().source,
+        "allow(_: Actor, _, _: Widget);"
+    );
+
+    // Nothing applies.
+    let decision = oso.decide(admin(), "read", Company::new(1))?;
+    assert!(!decision.allowed);
+    assert!(decision.rule.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_query_tracing() -> oso::Result<()> {
+    common::setup();
+    let mut oso = Oso::new();
+    oso.register_class(Actor::get_polar_class()).unwrap();
+    oso.load_str(r#"allow(actor: Actor, "read", _) if actor.name = "admin";"#)?;
+    let args = || (Actor::new("admin".to_owned()), "read", 1);
+
+    let result = oso.query_rule("allow", args())?.next().unwrap()?;
+    assert!(result.trace().is_none());
+    assert!(result.formatted_trace().is_none());
+
+    oso.set_query_tracing(true);
+    let result = oso.query_rule("allow", args())?.next().unwrap()?;
+    let trace = result.trace().unwrap();
+    assert!(matches!(&trace.children[0].node, Node::Rule(_)));
+    assert!(result
+        .formatted_trace()
+        .unwrap()
+        .contains(r#"allow(actor: Actor, "read", _) if actor.name = "admin";"#));
+
+    let result = oso.query("x = 1")?.next().unwrap()?;
+    assert!(result.formatted_trace().is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_explain() -> oso::Result<()> {
+    common::setup();
+    let mut oso = Oso::new();
+    oso.register_class(Actor::get_polar_class()).unwrap();
+    oso.register_class(Widget::get_polar_class()).unwrap();
+    oso.load_str(
+        r#"allow(_: Actor, "write", _: Widget);
+           allow(actor: Actor, "read", widget: Widget) if
+               actor.name = "admin" and
+               widget.id = 1;"#,
+    )?;
+
+    let explanation = oso.explain(Actor::new("admin".to_owned()), "read", Widget::new(1))?;
+    assert!(explanation.decision.allowed);
+    assert!(explanation.failed_rules.is_empty());
+    let rule = explanation.proof.unwrap();
+    assert!(rule
+        .source
+        .starts_with(r#"allow(actor: Actor, "read", widget: Widget) if"#));
+    assert_eq!(rule.location.as_ref().unwrap().row, 2);
+    let goals = rule
+        .children
+        .iter()
+        .map(|goal| goal.source.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(goals, vec![r#"actor.name = "admin""#, "widget.id = 1"]);
+
+    // The rule that got furthest comes first, and the rule whose parameters didn't match last.
+    let explanation = oso.explain(Actor::new("admin".to_owned()), "read", Widget::new(2))?;
+    assert!(!explanation.decision.allowed);
+    assert!(explanation.proof.is_none());
+    let failed = &explanation.failed_rules;
+    assert_eq!(failed.len(), 2);
+    assert_eq!(failed[0].satisfied_goals, 1);
+    let goal = failed[0].failed_goal.as_ref().unwrap();
+    assert_eq!(goal.source, "widget.id = 1");
+    assert_eq!(goal.location.as_ref().unwrap().row, 4);
+    assert_eq!(failed[1].rule, r#"allow(_: Actor, "write", _: Widget);"#);
+    assert!(failed[1].failed_goal.is_none());
+
+    let explanation = oso.explain(Actor::new("guest".to_owned()), "read", Widget::new(1))?;
+    let goal = explanation.failed_rules[0].failed_goal.as_ref().unwrap();
+    assert_eq!(goal.source, r#"actor.name = "admin""#);
+
+    Ok(())
+}
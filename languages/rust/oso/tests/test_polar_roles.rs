@@ -1,4 +1,4 @@
-use oso::{PolarClass, PolarValue, Query, ResultSet, ToPolar};
+use oso::{Constraint, FromPolar, PolarClass, PolarValue, Query, Relationship, ResultSet, ToPolar};
 mod common;
 use common::OsoTest;
 use oso::errors::polar::{ErrorKind, PolarError, RolesValidationError};
@@ -54,6 +54,41 @@ struct User {
     pub roles: Vec<Role>,
 }
 
+#[derive(Clone, Debug, PolarClass, PartialEq)]
+struct Member {
+    #[polar(attribute)]
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PolarClass, PartialEq)]
+struct Assignment {
+    #[polar(attribute)]
+    pub member: String,
+    #[polar(attribute)]
+    pub role: String,
+    #[polar(attribute)]
+    pub resource: String,
+}
+
+impl Assignment {
+    fn new(member: &str, role: &str, resource: &str) -> Self {
+        Self {
+            member: member.to_owned(),
+            role: role.to_owned(),
+            resource: resource.to_owned(),
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<PolarValue> {
+        match name {
+            "member" => Some(self.member.clone().to_polar()),
+            "role" => Some(self.role.clone().to_polar()),
+            "resource" => Some(self.resource.clone().to_polar()),
+            _ => None,
+        }
+    }
+}
+
 fn roles_test_oso() -> OsoTest {
     let mut test = OsoTest::new();
     test.oso
@@ -283,3 +318,705 @@ fn test_roles_revalidation_file() {
 
     check_empty_roles_error(test.load_file(file!(), "invalid_roles.polar").unwrap_err());
 }
+
+fn roles_config_error(policy: &str) -> PolarError {
+    let mut test = roles_test_oso();
+    test.load_str(policy);
+    match test.oso.enable_roles().unwrap_err() {
+        OsoError::Polar(err) => err,
+        err => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn test_roles_config_validation() {
+    common::setup();
+    let cases = [
+        (
+            r#"resource(_: Org, "org", ["invite"], {member: {permissions: ["push"]}});
+               actor_has_role_for_resource(_, _, _);"#,
+            "Role member on org has permission push, which is not an action on org.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {member: {permissions: ["repo:push"]}});
+               actor_has_role_for_resource(_, _, _);"#,
+            "Role member on org has permission repo:push, but there is no resource named repo.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {member: {implies: ["fake"]}});
+               actor_has_role_for_resource(_, _, _);"#,
+            "Role member on org implies fake, which is not a role on org.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {member: {implies: ["repo:reader"]}});
+               resource(_: Repo, "repo", ["pull"], {writer: {permissions: ["pull"]}});
+               actor_has_role_for_resource(_, _, _);"#,
+            "Role member on org implies repo:reader, which is not a role on repo.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {
+                   member: {implies: ["owner"]},
+                   owner: {permissions: ["invite"], implies: ["repo:reader"]}
+               });
+               resource(_: Repo, "repo", ["pull"], {reader: {implies: ["org:member"]}});
+               actor_has_role_for_resource(_, _, _);"#,
+            "Role implications form a cycle: org:member -> org:owner -> repo:reader -> org:member.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {});
+               resource(_: Repo, "org", ["pull"], {});
+               actor_has_role_for_resource(_, _, _);"#,
+            "Duplicate resource name org.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {member: {implies: ["global:admin"]}});
+               resource(_, "global", [], {admin: {permissions: ["org:invite"]}});
+               actor_has_role_for_resource(_, _, _);
+               actor_has_global_role(_, _);"#,
+            "Role member on org implies global:admin, but global roles can only be implied by global roles.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {});
+               resource(_, "global", [], {admin: {permissions: ["invite"]}});
+               actor_has_role_for_resource(_, _, _);
+               actor_has_global_role(_, _);"#,
+            "Role admin on global has permission invite, but permissions of global roles must name a resource, like repo:invite.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {});
+               resource(_, "global", [], {admin: {permissions: ["org:invite"]}});
+               actor_has_role_for_resource(_, _, _);"#,
+            "Need to define `actor_has_global_role(actor, role_name)` predicate to use global roles.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {guest: {deny: ["delete"]}});
+               actor_has_role_for_resource(_, _, _);"#,
+            "Role guest on org denies delete, which is not an action on org.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {guest: {deny: ["repo:delete"]}});
+               actor_has_role_for_resource(_, _, _);"#,
+            "Role guest on org denies repo:delete, but there is no resource named repo.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {guest: {permissions: ["invite"], deny: ["invite"]}});
+               actor_has_role_for_resource(_, _, _);"#,
+            "Role guest on org both has permission invite and denies it.",
+        ),
+        (
+            r#"resource(_: Org, "org", ["invite"], {});
+               resource(_, "global", [], {banned: {deny: ["org:invite"]}});
+               actor_has_role_for_resource(_, _, _);
+               actor_has_global_role(_, _);"#,
+            "Role banned on global denies org:invite, but global roles can't deny actions.",
+        ),
+    ];
+    for (policy, msg) in cases.iter() {
+        let err = roles_config_error(policy);
+        assert!(
+            matches!(&err.kind, ErrorKind::RolesValidation(RolesValidationError(m)) if m == msg),
+            "{}",
+            err
+        );
+        assert!(err.context.is_some(), "{}", err);
+    }
+
+    // The error points at the offending `resource` rule.
+    let err = roles_config_error(
+        r#"resource(_: Org, "org", ["invite"], {});
+           resource(_: Repo, "repo", ["pull"], {reader: {permissions: ["push"]}});
+           actor_has_role_for_resource(_, _, _);"#,
+    );
+    let context = err.context.unwrap();
+    assert_eq!((context.row, context.column), (1, 29));
+
+    let err = roles_config_error(
+        r#"resource(_: Org, "global", [], {admin: {permissions: ["org:invite"]}});
+           actor_has_role_for_resource(_, _, _);"#,
+    );
+    assert!(err.to_string().contains(
+        "The global resource can't have a type specializer, declare it with `resource(_, \"global\", [], roles)`."
+    ));
+}
+
+#[test]
+fn test_roles_model() {
+    common::setup();
+    let mut test = roles_test_oso();
+    test.load_str(
+        r#"resource(_: Org, "org", ["invite"], {owner: {permissions: ["invite"], implies: ["repo:reader"]}});
+           resource(_: Repo, "repo", ["pull"], {reader: {permissions: ["pull"]}});
+           parent_child(parent_org: Org, repo: Repo) if repo.org = parent_org;
+           actor_has_role_for_resource(_, _, _);"#,
+    );
+    assert!(matches!(
+        test.oso.roles_model().unwrap_err(),
+        OsoError::Polar(PolarError {
+            kind: ErrorKind::RolesValidation(_),
+            ..
+        })
+    ));
+    test.enable_roles();
+
+    let model = test.oso.roles_model().unwrap();
+    assert_eq!(
+        model,
+        oso::RolesModel {
+            resources: vec![
+                oso::ResourceModel {
+                    namespace: "org".to_owned(),
+                    class_tag: "Org".to_owned(),
+                    actions: vec!["invite".to_owned()],
+                    roles: vec![oso::RoleModel {
+                        name: "owner".to_owned(),
+                        permissions: vec!["invite".to_owned()],
+                        deny: vec![],
+                        implies: vec![],
+                        implies_cross_resource: vec!["repo:reader".to_owned()],
+                    }],
+                },
+                oso::ResourceModel {
+                    namespace: "repo".to_owned(),
+                    class_tag: "Repo".to_owned(),
+                    actions: vec!["pull".to_owned()],
+                    roles: vec![oso::RoleModel {
+                        name: "reader".to_owned(),
+                        permissions: vec!["pull".to_owned()],
+                        deny: vec![],
+                        implies: vec![],
+                        implies_cross_resource: vec![],
+                    }],
+                },
+            ],
+            global_roles: vec![],
+            relationships: vec![oso::ParentChild {
+                parent: "org".to_owned(),
+                child: "repo".to_owned(),
+            }],
+        }
+    );
+    let dot = model.render(oso::RolesModelFormat::Dot);
+    assert!(dot.contains(r#""org" -> "repo" [label="parent_child", style=bold];"#));
+}
+
+/// Roles on orgs and repos assigned to members by name, for partial evaluation.
+fn assignments_test_oso() -> OsoTest {
+    let mut test = roles_test_oso();
+    let members = || {
+        ["alice", "bob", "carol", "dave", "erin"]
+            .iter()
+            .map(|name| Member {
+                name: name.to_string(),
+            })
+            .collect::<Vec<_>>()
+    };
+    let assignments = || {
+        vec![
+            Assignment::new("alice", "owner", "osohq"),
+            Assignment::new("bob", "reader", "oso"),
+            Assignment::new("carol", "writer", "oso"),
+            Assignment::new("dave", "reader", "ios"),
+            Assignment::new("erin", "writer", "oso"),
+            Assignment::new("erin", "suspended", "oso"),
+        ]
+    };
+    test.oso
+        .register_class(
+            Member::get_polar_class_builder()
+                .add_field_type("name", "String")
+                .add_relationship(
+                    "assignments",
+                    Relationship::children("Assignment", "name", "member"),
+                )
+                .set_fetcher(move |constraints: &[Constraint]| {
+                    members()
+                        .into_iter()
+                        .filter(|m| {
+                            constraints.iter().all(|c| {
+                                c.check(|f| (f == "name").then(|| m.name.clone().to_polar()))
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .build(),
+        )
+        .unwrap();
+    test.oso
+        .register_class(
+            Assignment::get_polar_class_builder()
+                .add_field_type("member", "String")
+                .add_field_type("role", "String")
+                .add_field_type("resource", "String")
+                .set_fetcher(move |constraints: &[Constraint]| {
+                    assignments()
+                        .into_iter()
+                        .filter(|a| constraints.iter().all(|c| c.check(|f| a.field(f))))
+                        .collect::<Vec<_>>()
+                })
+                .build(),
+        )
+        .unwrap();
+    test.load_str(
+        r#"resource(_type: Org, "org", ["invite"], roles) if
+               roles = {
+                   member: {implies: ["repo:reader"]},
+                   owner: {permissions: ["invite"], implies: ["member", "repo:writer"]}
+               };
+
+           resource(_type: Repo, "repo", ["push", "pull"], roles) if
+               roles = {
+                   writer: {permissions: ["push"], implies: ["reader"]},
+                   reader: {permissions: ["pull"]},
+                   suspended: {deny: ["push"]}
+               };
+
+           parent_child(parent_org: Org, repo: Repo) if
+               repo.org = parent_org;
+
+           actor_has_role_for_resource(actor: Member, role_name, org: Org) if
+               assignment in actor.assignments and
+               assignment.role = role_name and
+               assignment.resource = org.name;
+
+           actor_has_role_for_resource(actor: Member, role_name, repo: Repo) if
+               assignment in actor.assignments and
+               assignment.role = role_name and
+               assignment.resource = repo.name;
+
+           allow(actor, action, resource) if
+               role_allows(actor, action, resource);"#,
+    );
+    test
+}
+
+fn oso_repo() -> Repo {
+    Repo {
+        name: "oso".to_string(),
+        org: Org {
+            name: "osohq".to_string(),
+        },
+    }
+}
+
+#[test]
+fn test_authorized_actors() {
+    common::setup();
+    let mut test = assignments_test_oso();
+    test.enable_roles();
+
+    let names = |members: Vec<Member>| {
+        let mut names = members.into_iter().map(|m| m.name).collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    // Erin is a writer, but is suspended from pushing. Denials through a relationship of
+    // the actor can't be filtered, so this is an error rather than including Erin.
+    let err = test
+        .oso
+        .authorized_actors::<Member, _>("push", oso_repo())
+        .err()
+        .unwrap();
+    assert!(
+        matches!(
+            &err,
+            OsoError::Polar(PolarError {
+                kind: ErrorKind::DataFiltering(_),
+                ..
+            })
+        ),
+        "{}",
+        err
+    );
+    let pullers = test
+        .oso
+        .authorized_actors::<Member, _>("pull", oso_repo())
+        .unwrap();
+    assert_eq!(names(pullers), vec!["alice", "bob", "carol", "erin"]);
+    let inviters = test
+        .oso
+        .authorized_actors::<Member, _>("invite", oso_repo().org)
+        .unwrap();
+    assert_eq!(names(inviters), vec!["alice"]);
+}
+
+#[test]
+fn test_authorized_resources_with_denials() {
+    common::setup();
+    let mut test = OsoTest::new();
+    test.oso
+        .register_class(Org::get_polar_class_builder().with_equality_check().build())
+        .unwrap();
+    test.oso.register_class(User::get_polar_class()).unwrap();
+    let repos = || {
+        ["oso", "ios", "docs"]
+            .iter()
+            .map(|name| Repo {
+                name: name.to_string(),
+                org: oso_repo().org,
+            })
+            .collect::<Vec<_>>()
+    };
+    test.oso
+        .register_class(
+            Repo::get_polar_class_builder()
+                .with_equality_check()
+                .add_field_type("name", "String")
+                .set_fetcher(move |constraints: &[Constraint]| {
+                    repos()
+                        .into_iter()
+                        .filter(|r| {
+                            constraints.iter().all(|c| {
+                                c.check(|f| (f == "name").then(|| r.name.clone().to_polar()))
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .build(),
+        )
+        .unwrap();
+    test.load_str(
+        r#"resource(_type: Repo, "repo", ["push", "pull"], roles) if
+               roles = {
+                   writer: {permissions: ["push"], implies: ["reader"]},
+                   reader: {permissions: ["pull"]},
+                   suspended: {deny: ["push"]}
+               };
+
+           actor_has_role_for_resource(actor: User, role_name, repo: Repo) if
+               role in actor.roles and
+               role.name = role_name and
+               role.resource = repo.name;
+
+           allow(actor, action, resource) if
+               role_allows(actor, action, resource);"#,
+    );
+    test.enable_roles();
+
+    let role = |name: &str, repo: &str| Role {
+        name: name.to_owned(),
+        resource: repo.to_owned().to_polar(),
+    };
+    let user = User {
+        name: "user".to_owned(),
+        roles: vec![
+            role("writer", "oso"),
+            role("writer", "ios"),
+            role("suspended", "ios"),
+        ],
+    };
+    let names = |repos: Vec<Repo>| {
+        let mut names = repos.into_iter().map(|r| r.name).collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+    let pushable = test
+        .oso
+        .authorized_resources::<_, Repo>(user.clone(), "push")
+        .unwrap();
+    assert_eq!(names(pushable), vec!["oso"]);
+    let pullable = test
+        .oso
+        .authorized_resources::<_, Repo>(user, "pull")
+        .unwrap();
+    assert_eq!(names(pullable), vec!["ios", "oso"]);
+}
+
+#[test]
+fn test_roles_granting() {
+    common::setup();
+    let mut test = assignments_test_oso();
+    assert!(test.oso.roles_granting("push", oso_repo()).is_err());
+    test.enable_roles();
+
+    let describe = |roles: Vec<(String, PolarValue)>| {
+        let mut roles = roles
+            .into_iter()
+            .map(|(role, resource)| match Org::from_polar(resource.clone()) {
+                Ok(org) => format!("{} on org {}", role, org.name),
+                Err(_) => format!(
+                    "{} on repo {}",
+                    role,
+                    Repo::from_polar(resource).unwrap().name
+                ),
+            })
+            .collect::<Vec<_>>();
+        roles.sort();
+        roles
+    };
+    let roles = test.oso.roles_granting("push", oso_repo()).unwrap();
+    assert_eq!(
+        describe(roles),
+        vec!["owner on org osohq", "writer on repo oso"]
+    );
+    let roles = test.oso.roles_granting("pull", oso_repo()).unwrap();
+    assert_eq!(
+        describe(roles),
+        vec![
+            "member on org osohq",
+            "owner on org osohq",
+            "reader on repo oso",
+            "writer on repo oso"
+        ]
+    );
+    assert!(test
+        .oso
+        .roles_granting("delete", oso_repo())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_roles_granting_global_and_denying_roles() {
+    common::setup();
+    let mut test = roles_test_oso();
+    test.load_str(
+        r#"resource(_type: Org, "org", ["invite"], roles) if
+               roles = {
+                   owner: {permissions: ["invite"], implies: ["repo:writer"]},
+                   intern: {implies: ["repo:writer", "repo:suspended"]}
+               };
+
+           resource(_type: Repo, "repo", ["push", "pull"], roles) if
+               roles = {
+                   writer: {permissions: ["push"], implies: ["reader"]},
+                   reader: {permissions: ["pull"]},
+                   suspended: {deny: ["push"]}
+               };
+
+           resource(_, "global", [], roles) if
+               roles = {
+                   superadmin: {implies: ["org:owner"]},
+                   temp: {implies: ["repo:writer", "repo:suspended"]},
+                   support: {permissions: ["repo:pull"]}
+               };
+
+           parent_child(parent_org: Org, repo: Repo) if
+               repo.org = parent_org;
+
+           actor_has_role_for_resource(actor: User, role_name, role_resource) if
+               role in actor.roles and
+               role matches {name: role_name, resource: role_resource};
+
+           actor_has_global_role(actor: User, "superadmin") if actor.name = "root";
+
+           allow(actor, action, resource) if
+               role_allows(actor, action, resource);"#,
+    );
+    test.enable_roles();
+
+    let describe = |roles: Vec<(String, PolarValue)>| {
+        let mut roles = roles
+            .into_iter()
+            .map(|(role, resource)| match resource {
+                PolarValue::String(global) => format!("{} {}", role, global),
+                resource => match Org::from_polar(resource.clone()) {
+                    Ok(org) => format!("{} on org {}", role, org.name),
+                    Err(_) => format!(
+                        "{} on repo {}",
+                        role,
+                        Repo::from_polar(resource).unwrap().name
+                    ),
+                },
+            })
+            .collect::<Vec<_>>();
+        roles.sort();
+        roles
+    };
+    // Interns and temps are writers, but are also suspended from pushing.
+    let roles = test.oso.roles_granting("push", oso_repo()).unwrap();
+    assert_eq!(
+        describe(roles),
+        vec![
+            "owner on org osohq",
+            "superadmin global",
+            "writer on repo oso"
+        ]
+    );
+    let roles = test.oso.roles_granting("pull", oso_repo()).unwrap();
+    assert_eq!(
+        describe(roles),
+        vec![
+            "intern on org osohq",
+            "owner on org osohq",
+            "reader on repo oso",
+            "superadmin global",
+            "support global",
+            "temp global",
+            "writer on repo oso"
+        ]
+    );
+}
+
+#[test]
+fn test_global_roles() {
+    common::setup();
+    let mut test = roles_test_oso();
+    test.load_str(
+        r#"resource(_type: Org, "org", ["invite"], roles) if
+               roles = {
+                   member: {implies: ["repo:reader"]},
+                   owner: {permissions: ["invite"], implies: ["member", "repo:writer"]}
+               };
+
+           resource(_type: Repo, "repo", ["push", "pull"], roles) if
+               roles = {
+                   writer: {permissions: ["push"], implies: ["reader"]},
+                   reader: {permissions: ["pull"]}
+               };
+
+           resource(_, "global", [], roles) if
+               roles = {
+                   superadmin: {implies: ["support", "org:owner"]},
+                   support: {permissions: ["repo:pull"]}
+               };
+
+           parent_child(parent_org: Org, repo: Repo) if
+               repo.org = parent_org;
+
+           actor_has_role_for_resource(actor: User, role_name, role_resource) if
+               role in actor.roles and
+               role matches {name: role_name, resource: role_resource};
+
+           actor_has_global_role(actor: User, "superadmin") if actor.name = "root";
+           actor_has_global_role(actor: User, "support") if actor.name = "helpdesk";
+
+           allow(actor, action, resource) if
+               role_allows(actor, action, resource);"#,
+    );
+    test.enable_roles();
+
+    let user = |name: &str| User {
+        name: name.to_owned(),
+        roles: vec![],
+    };
+    let allowed = |user: User, action: &str, resource: PolarValue| {
+        test.oso.is_allowed(user, action, resource).unwrap()
+    };
+    let osohq = oso_repo().org;
+
+    assert!(allowed(user("root"), "invite", osohq.clone().to_polar()));
+    assert!(allowed(user("root"), "push", oso_repo().to_polar()));
+    assert!(allowed(user("root"), "pull", oso_repo().to_polar()));
+    assert!(allowed(user("helpdesk"), "pull", oso_repo().to_polar()));
+    assert!(!allowed(user("helpdesk"), "push", oso_repo().to_polar()));
+    assert!(!allowed(user("helpdesk"), "invite", osohq.to_polar()));
+    assert!(!allowed(user("gwen"), "pull", oso_repo().to_polar()));
+
+    let model = test.oso.roles_model().unwrap();
+    assert_eq!(
+        model
+            .global_roles
+            .iter()
+            .map(|role| role.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["superadmin", "support"]
+    );
+    assert!(model.resources.iter().all(|r| r.namespace != "global"));
+}
+
+#[test]
+fn test_role_denials() {
+    common::setup();
+    let mut test = roles_test_oso();
+    test.load_str(
+        r#"resource(_type: Org, "org", ["invite"], roles) if
+               roles = {
+                   owner: {permissions: ["invite"], implies: ["repo:admin"]},
+                   contractor: {implies: ["repo:suspended"]},
+                   auditor: {deny: ["repo:push"], implies: ["repo:reader"]}
+               };
+
+           resource(_type: Repo, "repo", ["push", "pull", "delete"], roles) if
+               roles = {
+                   admin: {permissions: ["delete"], implies: ["writer"]},
+                   writer: {permissions: ["push"], implies: ["reader"]},
+                   reader: {permissions: ["pull"]},
+                   suspended: {deny: ["delete", "push"]}
+               };
+
+           resource(_, "global", [], roles) if
+               roles = {
+                   temp: {implies: ["repo:writer", "repo:suspended"]}
+               };
+
+           parent_child(parent_org: Org, repo: Repo) if
+               repo.org = parent_org;
+
+           actor_has_role_for_resource(actor: User, role_name, role_resource) if
+               role in actor.roles and
+               role matches {name: role_name, resource: role_resource};
+
+           actor_has_global_role(actor: User, "temp") if actor.name = "temp";
+
+           allow(actor, action, resource) if
+               role_allows(actor, action, resource);"#,
+    );
+    test.enable_roles();
+
+    let repo = oso_repo();
+    let org = repo.org.clone();
+    let user = |roles: &[(&str, PolarValue)]| User {
+        name: "user".to_owned(),
+        roles: roles
+            .iter()
+            .map(|(name, resource)| Role {
+                name: name.to_string(),
+                resource: resource.clone(),
+            })
+            .collect(),
+    };
+    let allowed = |user: &User, action: &str| {
+        test.oso
+            .is_allowed(user.clone(), action, repo.clone())
+            .unwrap()
+    };
+
+    let admin = user(&[("admin", repo.clone().to_polar())]);
+    assert!(allowed(&admin, "delete"));
+    assert!(allowed(&admin, "push"));
+
+    // A denying role overrides the permissions of the actor's other roles.
+    let suspended_admin = user(&[
+        ("admin", repo.clone().to_polar()),
+        ("suspended", repo.clone().to_polar()),
+    ]);
+    assert!(!allowed(&suspended_admin, "delete"));
+    assert!(!allowed(&suspended_admin, "push"));
+    assert!(allowed(&suspended_admin, "pull"));
+
+    // Denials are implied like permissions, including across resources.
+    let contractor = user(&[
+        ("owner", org.clone().to_polar()),
+        ("contractor", org.clone().to_polar()),
+    ]);
+    assert!(!allowed(&contractor, "delete"));
+    assert!(allowed(&contractor, "pull"));
+    assert!(test
+        .oso
+        .is_allowed(contractor, "invite", org.clone())
+        .unwrap());
+
+    // A role on an ancestor can deny actions on its descendants.
+    let auditor = user(&[
+        ("writer", repo.clone().to_polar()),
+        ("auditor", org.to_polar()),
+    ]);
+    assert!(!allowed(&auditor, "push"));
+    assert!(allowed(&auditor, "pull"));
+
+    // A global role's denials apply along with its permissions.
+    let temp = User {
+        name: "temp".to_owned(),
+        roles: vec![],
+    };
+    assert!(!allowed(&temp, "push"));
+    assert!(allowed(&temp, "pull"));
+
+    let model = test.oso.roles_model().unwrap();
+    let suspended = model.resources[1]
+        .roles
+        .iter()
+        .find(|role| role.name == "suspended")
+        .unwrap();
+    assert_eq!(suspended.deny, vec!["delete", "push"]);
+}
@@ -1,4 +1,6 @@
+use polar_core::data_filtering::{ExplainFormat, FilterPlan};
 pub use polar_core::polar::{Polar, Query};
+use polar_core::roles_model::RolesModelFormat;
 use polar_core::{error, terms};
 
 use std::cell::RefCell;
@@ -454,6 +456,26 @@ pub extern "C" fn polar_validate_roles_config(
     })
 }
 
+#[no_mangle]
+pub extern "C" fn polar_roles_model(polar_ptr: *mut Polar, format: *const c_char) -> *const c_char {
+    ffi_try!({
+        let polar = unsafe { ffi_ref!(polar_ptr) };
+        let format = unsafe { ffi_string!(format) };
+        let model = format
+            .parse::<RolesModelFormat>()
+            .and_then(|format| polar.roles_model().map(|model| model.render(format)));
+        match model {
+            Ok(model) => CString::new(model)
+                .expect("Roles model should not contain any 0 bytes")
+                .into_raw(),
+            Err(e) => {
+                set_error(e);
+                null()
+            }
+        }
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn polar_build_filter_plan(
     polar_ptr: *mut Polar,
@@ -504,3 +526,30 @@ pub extern "C" fn polar_build_filter_plan(
         }
     })
 }
+
+#[no_mangle]
+pub extern "C" fn polar_explain_filter_plan(
+    filter_plan: *const c_char,
+    format: *const c_char,
+) -> *const c_char {
+    ffi_try!({
+        let filter_plan_str = unsafe { ffi_string!(filter_plan) };
+        let format = unsafe { ffi_string!(format) };
+        let explanation = serde_json::from_str::<FilterPlan>(&filter_plan_str)
+            .map_err(|e| error::RuntimeError::Serialization { msg: e.to_string() }.into())
+            .and_then(|filter_plan| {
+                format
+                    .parse::<ExplainFormat>()
+                    .map(|format| filter_plan.explain().render(format))
+            });
+        match explanation {
+            Ok(explanation) => CString::new(explanation)
+                .expect("Explanation should not contain any 0 bytes")
+                .into_raw(),
+            Err(e) => {
+                set_error(e);
+                null()
+            }
+        }
+    })
+}
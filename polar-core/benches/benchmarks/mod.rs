@@ -1,3 +1,4 @@
 pub mod partial;
 pub mod queries;
+pub mod roles;
 mod runner;
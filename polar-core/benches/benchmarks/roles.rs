@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use criterion::{criterion_group, BenchmarkId, Criterion};
+
+use polar_core::events::{QueryEvent, ResultEvent};
+use polar_core::polar::{Polar, Query};
+use polar_core::terms::*;
+
+/// Benchmarks comparing `role_allows` answered from the compiled role graph
+/// against the same check interpreted entirely in Polar.
+///
+/// Resources, users and their roles are external instances, so each query
+/// runs against a minimal host that answers class checks, field lookups and
+/// equality by instance id.
+
+const POLICY: &str = r#"
+resource(_type: Org, "org", actions, roles) if
+    actions = ["invite", "create_repo"] and
+    roles = {
+        member: {
+            permissions: ["create_repo"],
+            implies: ["repo:reader"]
+        },
+        owner: {
+            permissions: ["invite"],
+            implies: ["member", "repo:admin"]
+        }
+    };
+
+resource(_type: Repo, "repo", actions, roles) if
+    actions = ["push", "pull", "delete"] and
+    roles = {
+        admin: {
+            permissions: ["delete"],
+            implies: ["maintainer"]
+        },
+        maintainer: {
+            permissions: ["issue:close"],
+            implies: ["writer"]
+        },
+        writer: {
+            permissions: ["push", "issue:edit"],
+            implies: ["reader"]
+        },
+        reader: {
+            permissions: ["pull"]
+        }
+    };
+
+resource(_type: Issue, "issue", actions, {}) if
+    actions = ["edit", "close"];
+
+parent_child(parent_org: Org, repo: Repo) if
+    repo.org = parent_org;
+
+parent_child(parent_repo: Repo, issue: Issue) if
+    issue.repo = parent_repo;
+
+actor_has_role_for_resource(actor, role_name, role_resource) if
+    role in actor.roles and
+    role matches {name: role_name, resource: role_resource};
+
+allow(actor, action, resource) if
+    role_allows(actor, action, resource);
+"#;
+
+fn instance(instance_id: u64, class: &str) -> Term {
+    Term::new_temporary(Value::ExternalInstance(ExternalInstance {
+        instance_id,
+        constructor: None,
+        repr: Some(class.to_owned()),
+    }))
+}
+
+fn role(name: &str, resource: &Term) -> Term {
+    let mut fields = std::collections::BTreeMap::new();
+    fields.insert(
+        Symbol::new("name"),
+        Term::new_temporary(Value::String(name.to_owned())),
+    );
+    fields.insert(Symbol::new("resource"), resource.clone());
+    Term::new_temporary(Value::Dictionary(Dictionary { fields }))
+}
+
+fn class_of(term: &Term) -> Option<&str> {
+    match term.value() {
+        Value::ExternalInstance(ExternalInstance {
+            repr: Some(class), ..
+        }) => Some(class),
+        _ => None,
+    }
+}
+
+fn instance_id(term: &Term) -> Option<u64> {
+    match term.value() {
+        Value::ExternalInstance(ExternalInstance { instance_id, .. }) => Some(*instance_id),
+        _ => None,
+    }
+}
+
+/// An org with a repo with an issue, and a user with one role on each.
+struct Host {
+    fields: HashMap<(u64, String), Term>,
+    org_owner: Term,
+    repo_reader: Term,
+    issue_writer: Term,
+    nobody: Term,
+    issue: Term,
+    repo: Term,
+}
+
+impl Host {
+    fn new() -> Self {
+        let org = instance(1, "Org");
+        let repo = instance(2, "Repo");
+        let issue = instance(3, "Issue");
+        let mut fields = HashMap::new();
+        fields.insert((2, "org".to_owned()), org.clone());
+        fields.insert((3, "repo".to_owned()), repo.clone());
+
+        let mut user = |id, roles: Vec<Term>| {
+            fields.insert(
+                (id, "roles".to_owned()),
+                Term::new_temporary(Value::List(roles)),
+            );
+            instance(id, "User")
+        };
+        let org_owner = user(10, vec![role("owner", &org)]);
+        let repo_reader = user(11, vec![role("reader", &repo)]);
+        let issue_writer = user(12, vec![role("member", &org), role("writer", &repo)]);
+        let nobody = user(13, vec![]);
+
+        Self {
+            fields,
+            org_owner,
+            repo_reader,
+            issue_writer,
+            nobody,
+            issue,
+            repo,
+        }
+    }
+
+    /// Run a query to completion, answering its external events.
+    fn run(&self, query: &mut Query, first_only: bool) -> Vec<ResultEvent> {
+        let mut results = vec![];
+        loop {
+            match query.next_event().expect("query errored") {
+                QueryEvent::Done { .. } => return results,
+                QueryEvent::Result { bindings, .. } => {
+                    results.push(ResultEvent::new(bindings));
+                    if first_only {
+                        return results;
+                    }
+                }
+                QueryEvent::ExternalIsa {
+                    call_id,
+                    instance,
+                    class_tag,
+                } => query
+                    .question_result(call_id, class_of(&instance) == Some(&class_tag.0))
+                    .unwrap(),
+                QueryEvent::ExternalIsSubSpecializer { call_id, .. } => {
+                    query.question_result(call_id, false).unwrap()
+                }
+                QueryEvent::ExternalOp { call_id, args, .. } => query
+                    .question_result(call_id, instance_id(&args[0]) == instance_id(&args[1]))
+                    .unwrap(),
+                QueryEvent::ExternalCall {
+                    call_id,
+                    instance,
+                    attribute,
+                    args,
+                    ..
+                } => {
+                    let result = if attribute.0 == "join" {
+                        // `__oso_internal_roles_helpers__.join(separator, ...)`
+                        let args = args.unwrap();
+                        let strings = args
+                            .iter()
+                            .map(|a| a.value().as_string().unwrap().to_owned())
+                            .collect::<Vec<_>>();
+                        Some(Term::new_temporary(Value::String(
+                            strings[1..].join(&strings[0]),
+                        )))
+                    } else {
+                        instance_id(&instance)
+                            .and_then(|id| self.fields.get(&(id, attribute.0.clone())))
+                            .cloned()
+                    };
+                    query.call_result(call_id, result).unwrap()
+                }
+                event => todo!("{:?}", event),
+            }
+        }
+    }
+
+    fn check(&self, actor: &Term, action: &str, resource: &Term) -> Term {
+        Term::new_temporary(Value::Call(Call {
+            name: Symbol::new("allow"),
+            args: vec![
+                actor.clone(),
+                Term::new_temporary(Value::String(action.to_owned())),
+                resource.clone(),
+            ],
+            kwargs: None,
+        }))
+    }
+}
+
+/// Load the policy with the built-in roles policy and compile the role graph.
+fn native_polar(host: &Host) -> Polar {
+    let polar = Polar::new();
+    polar.load_str(POLICY).unwrap();
+    polar.enable_roles().unwrap();
+    let mut results = vec![];
+    while let Some(mut query) = polar.next_inline_query(false) {
+        results.push(host.run(&mut query, false));
+    }
+    polar.validate_roles_config(results).unwrap();
+    polar
+}
+
+/// Load the policy with the roles policy that looks up roles in Polar.
+fn interpreted_polar() -> Polar {
+    let polar = Polar::new();
+    polar.register_constant(
+        Symbol::new("__oso_internal_roles_helpers__"),
+        instance(0, "RolesHelpers"),
+    );
+    polar.load_str(POLICY).unwrap();
+    polar
+        .load_str(include_str!("roles_interpreted.polar"))
+        .unwrap();
+    polar
+}
+
+pub fn role_allows(c: &mut Criterion) {
+    let host = Host::new();
+    let cases = [
+        // Permission granted by a role on the resource itself.
+        (
+            "local",
+            host.check(&host.repo_reader, "pull", &host.repo),
+            true,
+        ),
+        // Permission on an issue granted by a repo role implied by an org role.
+        (
+            "nested",
+            host.check(&host.org_owner, "close", &host.issue),
+            true,
+        ),
+        (
+            "nested_implied",
+            host.check(&host.issue_writer, "edit", &host.issue),
+            true,
+        ),
+        // No role grants the permission, so every path is explored.
+        (
+            "denied",
+            host.check(&host.nobody, "close", &host.issue),
+            false,
+        ),
+    ];
+    let implementations = [
+        ("polar", interpreted_polar()),
+        ("native", native_polar(&host)),
+    ];
+
+    let mut group = c.benchmark_group("role_allows");
+    for (case, check, allowed) in &cases {
+        for (implementation, polar) in &implementations {
+            let mut query = polar.new_query_from_term(check.clone(), false);
+            assert_eq!(
+                !host.run(&mut query, true).is_empty(),
+                *allowed,
+                "{} {}",
+                implementation,
+                case
+            );
+            group.bench_function(BenchmarkId::new(*implementation, case), |b| {
+                b.iter_batched_ref(
+                    || polar.new_query_from_term(check.clone(), false),
+                    |query| host.run(query, true),
+                    criterion::BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, role_allows);
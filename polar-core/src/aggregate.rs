@@ -0,0 +1,70 @@
+//! Aggregation operators: `findall`, `count`, `sum`, `min` and `max`.
+//!
+//! An aggregate runs its goal in a sub-VM until the goal has no more results.
+//! A `Collector` records the value of the aggregate's template for each
+//! result, and the outer VM then combines the values and binds the result.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::counter::Counter;
+use crate::error::{PolarError, PolarResult};
+use crate::events::QueryEvent;
+use crate::runnable::Runnable;
+use crate::terms::{Term, TermList};
+use crate::vm::PolarVirtualMachine;
+
+/// A `Runnable` that runs a goal in `vm` to exhaustion, collecting the value
+/// of `template` for each result.
+#[derive(Clone)]
+pub struct Collector {
+    vm: PolarVirtualMachine,
+    template: Term,
+    values: Rc<RefCell<TermList>>,
+}
+
+impl Collector {
+    pub fn new(vm: PolarVirtualMachine, template: Term, values: Rc<RefCell<TermList>>) -> Self {
+        Self {
+            vm,
+            template,
+            values,
+        }
+    }
+}
+
+impl Runnable for Collector {
+    fn run(&mut self, _: Option<&mut Counter>) -> PolarResult<QueryEvent> {
+        loop {
+            // Pass most events through, but collect results.
+            match self.vm.run(None)? {
+                QueryEvent::Result { .. } => {
+                    let value = self.vm.deep_deref(&self.template);
+                    self.values.borrow_mut().push(value);
+                }
+                QueryEvent::Done { .. } => return Ok(QueryEvent::Done { result: true }),
+                event => return Ok(event),
+            }
+        }
+    }
+
+    fn external_question_result(&mut self, call_id: u64, answer: bool) -> PolarResult<()> {
+        self.vm.external_question_result(call_id, answer)
+    }
+
+    fn external_call_result(&mut self, call_id: u64, term: Option<Term>) -> PolarResult<()> {
+        self.vm.external_call_result(call_id, term)
+    }
+
+    fn debug_command(&mut self, command: &str) -> PolarResult<()> {
+        self.vm.debug_command(command)
+    }
+
+    fn clone_runnable(&self) -> Box<dyn Runnable> {
+        Box::new(self.clone())
+    }
+
+    fn handle_error(&mut self, error: PolarError) -> PolarResult<QueryEvent> {
+        self.vm.handle_error(error)
+    }
+}
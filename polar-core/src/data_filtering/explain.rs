@@ -0,0 +1,262 @@
+//! Describe a `FilterPlan` for debugging.
+//!
+//! `FilterPlan::explain` returns an `Explanation` of the fetches in each result set, which
+//! renders as an indented text tree, as JSON, or as a Graphviz DOT graph with an edge for
+//! every `Ref` from one fetch to the results of another.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Constraint, ConstraintKind, ConstraintValue, FilterPlan};
+use crate::error::{ParameterError, PolarError};
+use crate::formatting::ToPolarString;
+
+/// How to render an `Explanation`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ExplainFormat {
+    Text,
+    Json,
+    Dot,
+}
+
+impl FromStr for ExplainFormat {
+    type Err = PolarError;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "dot" => Ok(Self::Dot),
+            _ => Err(ParameterError(format!(
+                "unknown explain format {}, expected text, json or dot",
+                format
+            ))
+            .into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Explanation {
+    pub result_sets: Vec<ResultSetExplanation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ResultSetExplanation {
+    pub result_id: String,
+    /// The fetches in resolve order.
+    pub fetches: Vec<FetchExplanation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct FetchExplanation {
+    pub id: String,
+    pub class_tag: String,
+    pub constraints: Vec<String>,
+    /// The ids of the fetches whose results this fetch uses.
+    pub depends_on: Vec<String>,
+}
+
+fn describe(constraint: &Constraint) -> String {
+    let op = match constraint.kind {
+        ConstraintKind::Eq => "=",
+        ConstraintKind::In => "in",
+        ConstraintKind::Contains => "contains",
+        ConstraintKind::Neq => "!=",
+        ConstraintKind::Lt => "<",
+        ConstraintKind::Leq => "<=",
+        ConstraintKind::Gt => ">",
+        ConstraintKind::Geq => ">=",
+        ConstraintKind::Nin => "not in",
+        ConstraintKind::NotContains => "not contains",
+    };
+    let value = match &constraint.value {
+        ConstraintValue::Term(t) => t.to_polar(),
+        ConstraintValue::Field(f) => format!("FIELD({})", f),
+        ConstraintValue::Ref(r) => match &r.field {
+            Some(field) => format!("REF(field {} of result {})", field, r.result_id),
+            None => format!("REF(result {})", r.result_id),
+        },
+    };
+    format!("{} {} {}", constraint.field, op, value)
+}
+
+/// Quote a string for a DOT file.
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl FilterPlan {
+    pub fn explain(&self) -> Explanation {
+        let result_sets = self
+            .result_sets
+            .iter()
+            .map(|result_set| ResultSetExplanation {
+                result_id: result_set.result_id.clone(),
+                fetches: result_set
+                    .resolve_order
+                    .iter()
+                    .filter_map(|id| result_set.requests.get(id).map(|request| (id, request)))
+                    .map(|(id, request)| {
+                        let mut depends_on = vec![];
+                        for constraint in &request.constraints {
+                            if let ConstraintValue::Ref(r) = &constraint.value {
+                                if !depends_on.contains(&r.result_id) {
+                                    depends_on.push(r.result_id.clone());
+                                }
+                            }
+                        }
+                        FetchExplanation {
+                            id: id.clone(),
+                            class_tag: request.class_tag.clone(),
+                            constraints: request.constraints.iter().map(describe).collect(),
+                            depends_on,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+        Explanation { result_sets }
+    }
+}
+
+impl Explanation {
+    pub fn render(&self, format: ExplainFormat) -> String {
+        match format {
+            ExplainFormat::Text => self.to_text(),
+            ExplainFormat::Json => self.to_json(),
+            ExplainFormat::Dot => self.to_dot(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = "UNION\n".to_owned();
+        for (i, result_set) in self.result_sets.iter().enumerate() {
+            text.push_str(&format!(
+                "  Result Set {} (result {})\n",
+                i, result_set.result_id
+            ));
+            for fetch in &result_set.fetches {
+                text.push_str(&format!("    {}: Fetch {}\n", fetch.id, fetch.class_tag));
+                for constraint in &fetch.constraints {
+                    text.push_str(&format!("      {}\n", constraint));
+                }
+            }
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Each result set is a cluster of fetches, with the result fetch drawn with a
+    /// double border and an edge from every fetch to the fetches that use its results.
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph filter_plan {\n  node [shape=box];\n".to_owned();
+        for (i, result_set) in self.result_sets.iter().enumerate() {
+            let node = |id: &str| dot_quote(&format!("{}.{}", i, id));
+            dot.push_str(&format!("  subgraph cluster_{} {{\n", i));
+            dot.push_str(&format!("    label=\"Result Set {}\";\n", i));
+            for fetch in &result_set.fetches {
+                let mut label = format!("{}: Fetch {}", fetch.id, fetch.class_tag);
+                for constraint in &fetch.constraints {
+                    label.push('\n');
+                    label.push_str(constraint);
+                }
+                let label = dot_quote(&label).replace('\n', "\\n");
+                let border = if fetch.id == result_set.result_id {
+                    ", peripheries=2"
+                } else {
+                    ""
+                };
+                dot.push_str(&format!(
+                    "    {} [label={}{}];\n",
+                    node(&fetch.id),
+                    label,
+                    border
+                ));
+            }
+            for fetch in &result_set.fetches {
+                for dependency in &fetch.depends_on {
+                    dot.push_str(&format!(
+                        "    {} -> {};\n",
+                        node(dependency),
+                        node(&fetch.id)
+                    ));
+                }
+            }
+            dot.push_str("  }\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_text())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::filter_plan;
+    use super::*;
+    use crate::error::PolarResult;
+
+    const POLICY: &str = r#"allow("steve", "get", resource: Foo) if
+                                resource.bar.is_cool = true and
+                                resource.is_fooey = true;"#;
+
+    #[test]
+    fn test_explain_text() -> PolarResult<()> {
+        let explanation = filter_plan(POLICY, "Foo")?.explain();
+        assert_eq!(
+            explanation.to_text(),
+            "UNION
+  Result Set 0 (result 1)
+    0: Fetch Bar
+      is_cool = true
+    1: Fetch Foo
+      bar_id in REF(field id of result 0)
+      is_fooey = true
+"
+        );
+        assert_eq!(explanation.to_string(), explanation.to_text());
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_json() -> PolarResult<()> {
+        let explanation = filter_plan(POLICY, "Foo")?.explain();
+        let json = explanation.render("json".parse()?);
+        let parsed: Explanation = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, explanation);
+        let fetches = &parsed.result_sets[0].fetches;
+        assert_eq!(fetches[0].depends_on, Vec::<String>::new());
+        assert_eq!(fetches[1].depends_on, vec!["0"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_dot() -> PolarResult<()> {
+        let dot = filter_plan(POLICY, "Foo")?
+            .explain()
+            .render(ExplainFormat::Dot);
+        assert!(dot.starts_with("digraph filter_plan {\n"));
+        assert!(dot.contains(r#""0.0" [label="0: Fetch Bar\nis_cool = true"];"#));
+        assert!(dot.contains(r#""0.1" [label="1: Fetch Foo\nbar_id in REF(field id of result 0)\nis_fooey = true", peripheries=2];"#));
+        assert!(dot.contains(r#""0.0" -> "0.1";"#));
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_format() {
+        assert_eq!("dot".parse::<ExplainFormat>().unwrap(), ExplainFormat::Dot);
+        let err = "yaml".parse::<ExplainFormat>().unwrap_err();
+        assert!(err.to_string().contains("unknown explain format yaml"));
+    }
+}
@@ -0,0 +1,1822 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{DataFilteringError, OperationalError, ParameterError, PolarResult};
+use crate::events::ResultEvent;
+
+use crate::terms::*;
+
+mod explain;
+mod optimizer;
+pub mod sql;
+
+pub use explain::{ExplainFormat, Explanation, FetchExplanation, ResultSetExplanation};
+pub use optimizer::{opt_pass, optimize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum Type {
+    Base {
+        class_tag: String,
+    },
+    Relationship {
+        kind: String,
+        other_class_tag: String,
+        my_field: String,
+        other_field: String,
+    },
+    // A relationship through an intermediate class, like posts and tags related by
+    // post_tags: my_field joins through_my_field and through_other_field joins other_field.
+    ManyToMany {
+        other_class_tag: String,
+        my_field: String,
+        through_class_tag: String,
+        through_my_field: String,
+        through_other_field: String,
+        other_field: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Ref {
+    pub field: Option<String>, // An optional field to map over the result objects with.
+    pub result_id: String,     // Id of the FetchResult that should be an input.
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum ConstraintValue {
+    Term(Term),    // An actual value
+    Ref(Ref),      // A reference to a different result.
+    Field(String), // Another field on the same result
+}
+
+// @TODO(steve): These are all constraints on a field. If we need to add constraints
+// on the value itself. eg `value in [Foo{id: "blah}]` then we should probably call
+// these FieldEq, FieldIn, FieldContains
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub enum ConstraintKind {
+    Eq,          // The field is equal to a value.
+    In,          // The field is equal to one of the values.
+    Contains,    // The field is a collection that contains the value.
+    Neq,         // The field is not equal to a value.
+    Lt,          // The field is less than a value.
+    Leq,         // The field is less than or equal to a value.
+    Gt,          // The field is greater than a value.
+    Geq,         // The field is greater than or equal to a value.
+    Nin,         // The field is not equal to any of the values.
+    NotContains, // The field is a collection that doesn't contain the value.
+}
+
+impl ConstraintKind {
+    /// The constraint kind for a comparison operator, if it has one.
+    fn from_comparison(operator: Operator) -> Option<Self> {
+        match operator {
+            Operator::Unify | Operator::Eq | Operator::Assign => Some(Self::Eq),
+            Operator::Neq => Some(Self::Neq),
+            Operator::Lt => Some(Self::Lt),
+            Operator::Leq => Some(Self::Leq),
+            Operator::Gt => Some(Self::Gt),
+            Operator::Geq => Some(Self::Geq),
+            _ => None,
+        }
+    }
+
+    /// The kind of the same comparison with its arguments swapped: `a < b` is `b > a`.
+    fn flip(&self) -> Self {
+        match self {
+            Self::Lt => Self::Gt,
+            Self::Leq => Self::Geq,
+            Self::Gt => Self::Lt,
+            Self::Geq => Self::Leq,
+            kind => kind.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Constraint {
+    pub kind: ConstraintKind,
+    pub field: String,
+    pub value: ConstraintValue,
+}
+
+// The list of constraints passed to a fetching function for a particular type.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct FetchRequest {
+    pub class_tag: String,
+    pub constraints: Vec<Constraint>,
+}
+
+// A Set of fetch requests that may depend on the results of other fetches.
+// resolve_order is the order to resolve the fetches in.
+// result_id says which result to return.
+// @Q(steve): Is it always the last one in the resolve_order?
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ResultSet {
+    pub requests: HashMap<String, FetchRequest>,
+    pub resolve_order: Vec<String>,
+    pub result_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct FilterPlan {
+    pub result_sets: Vec<ResultSet>,
+}
+
+pub type Types = HashMap<String, HashMap<String, Type>>;
+pub type PartialResults = Vec<ResultEvent>;
+
+#[derive(Debug)]
+struct VarInfo {
+    cycles: Vec<(Symbol, Symbol)>,                                // x = y
+    types: Vec<(Symbol, String)>,                                 // x matches XClass
+    eq_values: Vec<(Symbol, Term)>,                               // x = 1;
+    contained_values: Vec<(Term, Symbol)>,                        // 1 in x
+    field_relationships: Vec<(Symbol, String, Symbol)>,           // x.a = y
+    in_relationships: Vec<(Symbol, Symbol)>,                      // x in y
+    comparisons: Vec<(Symbol, ConstraintKind, Term)>,             // x > 1
+    var_comparisons: Vec<(Symbol, ConstraintKind, Symbol, Term)>, // x > y
+    not_types: Vec<(Symbol, String)>,                             // not x matches XClass
+    not_contained_values: Vec<(Term, Symbol)>,                    // not 1 in x
+    not_in_relationships: Vec<(Symbol, Symbol, Term)>,            // not x in y
+    field_terms: HashMap<Symbol, Term>,                           // x.a => a
+}
+
+/// Expand the disjunctions in `term` into a list of conjunctions, one for each
+/// way of satisfying it. `(a or b) and c` becomes `[[a, c], [b, c]]`.
+fn expand_disjunctions(term: &Term) -> Vec<Vec<Term>> {
+    match term.value() {
+        Value::Expression(Operation {
+            operator: Operator::And,
+            args,
+        }) => args.iter().fold(vec![vec![]], |conjunctions, arg| {
+            let arg_conjunctions = expand_disjunctions(arg);
+            conjunctions
+                .iter()
+                .flat_map(|conjunction| {
+                    arg_conjunctions.iter().map(move |arg_conjunction| {
+                        let mut conjunction = conjunction.clone();
+                        conjunction.extend(arg_conjunction.iter().cloned());
+                        conjunction
+                    })
+                })
+                .collect()
+        }),
+        Value::Expression(Operation {
+            operator: Operator::Or,
+            args,
+        }) => args.iter().flat_map(expand_disjunctions).collect(),
+        _ => vec![vec![term.clone()]],
+    }
+}
+
+fn unsupported<T>(msg: &str, term: &Term) -> PolarResult<T> {
+    Err(DataFilteringError::new(msg, Some(term)).into())
+}
+
+fn invalid_state<T>(msg: String) -> PolarResult<T> {
+    Err(OperationalError::InvalidState(msg).into())
+}
+
+fn process_result(conjunction: &[Term]) -> PolarResult<VarInfo> {
+    let mut var_info = VarInfo {
+        cycles: vec![],
+        types: vec![],
+        eq_values: vec![],
+        contained_values: vec![],
+        field_relationships: vec![],
+        in_relationships: vec![],
+        comparisons: vec![],
+        var_comparisons: vec![],
+        not_types: vec![],
+        not_contained_values: vec![],
+        not_in_relationships: vec![],
+        field_terms: HashMap::new(),
+    };
+    for term in conjunction {
+        process_exp(&mut var_info, term)?;
+    }
+    Ok(var_info)
+}
+
+fn dot_var(var_info: &mut VarInfo, var: Term, field: &Term) -> PolarResult<Symbol> {
+    let mut var = var;
+    while let Ok(Operation {
+        operator: Operator::Dot,
+        args,
+    }) = var.value().as_expression()
+    {
+        var = var.clone_with_value(Value::Variable(dot_var(
+            var_info,
+            args[0].clone(),
+            &args[1],
+        )?))
+    }
+    // TODO(steve): There's a potential name clash here which would be bad. Works for now.
+    // but should probably generate this var better.
+    let sym = match var.value().as_symbol() {
+        Ok(sym) => sym,
+        Err(_) => return unsupported("only fields of variables can be looked up", &var),
+    };
+    let field_str = match field.value().as_string() {
+        Ok(field_str) => field_str,
+        Err(_) => return unsupported("only fields can be looked up, not methods", field),
+    };
+    let new_var = Symbol::new(&format!("{}_dot_{}", sym.0, field_str));
+
+    // Record the relationship between the vars.
+    var_info
+        .field_relationships
+        .push((sym.clone(), field_str.to_string(), new_var.clone()));
+    var_info
+        .field_terms
+        .entry(new_var.clone())
+        .or_insert_with(|| field.clone());
+    Ok(new_var)
+}
+
+/// Process an argument of an operation, replacing any
+/// nested expression with the var that represents its result.
+fn process_arg(var_info: &mut VarInfo, arg: &Term) -> PolarResult<Term> {
+    if let Value::Expression(_) = arg.value() {
+        match process_exp(var_info, arg)? {
+            Some(var) => Ok(var),
+            None => unsupported("this expression can't be used as a value", arg),
+        }
+    } else {
+        Ok(arg.clone())
+    }
+}
+
+fn binary_args(term: &Term) -> PolarResult<(&Term, &Term)> {
+    match &term.value().as_expression()?.args[..] {
+        [lhs, rhs] => Ok((lhs, rhs)),
+        _ => invalid_state(format!("expected two arguments: {}", term.to_polar())),
+    }
+}
+
+/// The var and class tag of a `matches` operation.
+fn process_isa(var_info: &mut VarInfo, term: &Term) -> PolarResult<(Symbol, String)> {
+    let (lhs, rhs) = binary_args(term)?;
+    if let Value::Pattern(Pattern::Instance(InstanceLiteral { tag, fields })) = rhs.value() {
+        let var = match lhs.value() {
+            Value::Variable(var) | Value::RestVariable(var) => var.clone(),
+            Value::Expression(op) if op.operator == Operator::Dot => {
+                dot_var(var_info, op.args[0].clone(), &op.args[1])?
+            }
+            _ => return unsupported("only variables and fields can be matched", lhs),
+        };
+        process_fields(var_info, &var, fields)?;
+        Ok((var, tag.clone().0))
+    } else {
+        unsupported("non pattern specializers are not yet supported", rhs)
+    }
+}
+
+/// Lower the fields of a specializer or dictionary pattern into constraints on
+/// the fields of `var`, e.g. `x matches Foo{bar: {id: 1}}` becomes `x.bar.id = 1`.
+fn process_fields(var_info: &mut VarInfo, var: &Symbol, fields: &Dictionary) -> PolarResult<()> {
+    for (field, value) in &fields.fields {
+        let field = value.clone_with_value(Value::String(field.0.clone()));
+        let field_var = dot_var(var_info, term!(var.clone()), &field)?;
+        process_unify(var_info, &field_var, value)?;
+    }
+    Ok(())
+}
+
+/// Unify a var with a value. Patterns are lowered to constraints on the var's fields.
+fn process_unify(var_info: &mut VarInfo, var: &Symbol, value: &Term) -> PolarResult<()> {
+    match value.value() {
+        Value::Variable(other) => var_info.cycles.push((var.clone(), other.clone())),
+        Value::Pattern(Pattern::Instance(InstanceLiteral { tag, fields })) => {
+            var_info.types.push((var.clone(), tag.0.clone()));
+            process_fields(var_info, var, fields)?;
+        }
+        Value::Pattern(Pattern::Dictionary(fields)) => process_fields(var_info, var, fields)?,
+        Value::Expression(_) => {
+            let value = process_arg(var_info, value)?;
+            process_unify(var_info, var, &value)?;
+        }
+        val => var_info
+            .eq_values
+            .push((var.clone(), Term::new_temporary(val.clone()))),
+    }
+    Ok(())
+}
+
+/// Process a negated operation. Only some negations can be expressed as constraints.
+fn process_not(var_info: &mut VarInfo, term: &Term) -> PolarResult<()> {
+    let negated = match &term.value().as_expression()?.args[..] {
+        [negated] => negated,
+        _ => return invalid_state(format!("expected one argument: {}", term.to_polar())),
+    };
+    let operator = match negated.value().as_expression() {
+        Ok(exp) => exp.operator,
+        Err(_) => return unsupported("expected a negated expression", negated),
+    };
+    match operator {
+        Operator::Isa => {
+            let (_, rhs) = binary_args(negated)?;
+            if let Value::Pattern(Pattern::Instance(InstanceLiteral { fields, .. })) = rhs.value() {
+                if !fields.fields.is_empty() {
+                    return unsupported("negated specializer fields are not yet supported", rhs);
+                }
+            }
+            let (var, tag) = process_isa(var_info, negated)?;
+            var_info.not_types.push((var, tag));
+        }
+        Operator::In => {
+            let (lhs, rhs) = binary_args(negated)?;
+            let lhs = process_arg(var_info, lhs)?;
+            let rhs = process_arg(var_info, rhs)?;
+
+            match (lhs.value(), rhs.value()) {
+                // not l in r
+                (Value::Variable(l), Value::Variable(r)) => {
+                    var_info
+                        .not_in_relationships
+                        .push((l.clone(), r.clone(), negated.clone()));
+                }
+                // not 123 in var
+                (_, Value::Variable(var)) => {
+                    var_info
+                        .not_contained_values
+                        .push((lhs.clone(), var.clone()));
+                }
+                (_a, _b) => return unsupported("unknown negated `in` constraint", term),
+            }
+        }
+        _ => {
+            return unsupported(
+                "only negated `matches` and `in` operations are supported",
+                term,
+            )
+        }
+    }
+    Ok(())
+}
+
+fn process_exp(var_info: &mut VarInfo, term: &Term) -> PolarResult<Option<Term>> {
+    let exp = match term.value().as_expression() {
+        Ok(exp) => exp,
+        Err(_) => return unsupported("expected an expression", term),
+    };
+    match exp.operator {
+        Operator::And => {
+            // Disjunctions have already been expanded, so these are all conjunctions.
+            for arg in &exp.args {
+                process_exp(var_info, arg)?;
+            }
+        }
+        Operator::Dot => {
+            // Dot operations return a var that can be unified with.
+            // We create a new var to represent the result of the operation.
+            let (var, field) = binary_args(term)?;
+            if let Ok(inner_exp) = var.value().as_expression() {
+                if inner_exp.operator != Operator::Dot {
+                    return unsupported(
+                        "operations other than dot nested within a dot are not yet supported",
+                        var,
+                    );
+                }
+            }
+            let new_var = dot_var(var_info, var.clone(), field)?;
+            // Return the var so we can unify with it.
+            return Ok(Some(term.clone_with_value(Value::Variable(new_var))));
+        }
+        Operator::Isa => {
+            let (var, tag) = process_isa(var_info, term)?;
+            var_info.types.push((var, tag))
+        }
+        Operator::Unify | Operator::Eq | Operator::Assign => {
+            let (lhs, rhs) = binary_args(term)?;
+            let lhs = process_arg(var_info, lhs)?;
+            let rhs = process_arg(var_info, rhs)?;
+
+            match (lhs.value(), rhs.value()) {
+                // Unifying a variable with another variable, a value or a pattern
+                (Value::Variable(var), _) => process_unify(var_info, var, &rhs)?,
+                (_, Value::Variable(var)) => process_unify(var_info, var, &lhs)?,
+                // Unifying something else.
+                // 1 = 1 is irrelevant for data filtering, other stuff seems like an error.
+                // @NOTE(steve): Going with the same not yet supported message but if this is
+                // coming through it's probably a bug in the simplifier.
+                (_a, _b) => {
+                    return unsupported("unification of values is not yet supported", term);
+                }
+            };
+        }
+        Operator::In => {
+            let (lhs, rhs) = binary_args(term)?;
+            let lhs = process_arg(var_info, lhs)?;
+            let rhs = process_arg(var_info, rhs)?;
+
+            match (lhs.value(), rhs.value()) {
+                // l in r
+                (Value::Variable(l), Value::Variable(r)) => {
+                    var_info.in_relationships.push((l.clone(), r.clone()));
+                }
+                // var in [1, 2, 3]
+                (Value::Variable(_var), _val) => {
+                    // @Q(steve): Does this ever actually come through the simplifier?
+                    // @Note(steve): MikeD wishes this came through as an in instead of or-expanded.
+                    // That way we could turn it into an `in` in sql.
+                    return unsupported(
+                        "var in list of values constraints are not yet supported",
+                        term,
+                    );
+                    // var_info.in_values.push((var.clone(), Term::new_temporary(val.clone())));
+                }
+                // 123 in var
+                (val, Value::Variable(var)) => {
+                    var_info
+                        .contained_values
+                        .push((Term::new_temporary(val.clone()), var.clone()));
+                }
+                (_a, _b) => {
+                    // @NOTE: This is probably just a bug if we hit it. Shouldn't get any other `in` cases.
+                    return unsupported("unknown `in` constraint", term);
+                }
+            };
+        }
+        Operator::Debug => return unsupported("`debug()` is not supported", term),
+        Operator::Print => (),
+        Operator::Cut => return unsupported("`cut` is not supported", term),
+        Operator::New => return unsupported("`new` is not supported", term),
+        Operator::Not => process_not(var_info, term)?,
+        Operator::Mul => return unsupported("multiplication is not supported", term),
+        Operator::Div => return unsupported("division is not supported", term),
+        Operator::Mod => return unsupported("`mod` is not supported", term),
+        Operator::Rem => return unsupported("`rem` is not supported", term),
+        Operator::Add => return unsupported("addition is not supported", term),
+        Operator::Sub => return unsupported("subtraction is not supported", term),
+        Operator::Neq | Operator::Lt | Operator::Leq | Operator::Gt | Operator::Geq => {
+            let (lhs, rhs) = binary_args(term)?;
+            let kind = match ConstraintKind::from_comparison(exp.operator) {
+                Some(kind) => kind,
+                None => return unsupported("unknown comparison", term),
+            };
+            let lhs = process_arg(var_info, lhs)?;
+            let rhs = process_arg(var_info, rhs)?;
+
+            match (lhs.value(), rhs.value()) {
+                // x > y
+                (Value::Variable(l), Value::Variable(r)) => {
+                    var_info
+                        .var_comparisons
+                        .push((l.clone(), kind, r.clone(), term.clone()));
+                }
+                // x > 1
+                (Value::Variable(var), val) => {
+                    var_info
+                        .comparisons
+                        .push((var.clone(), kind, Term::new_temporary(val.clone())))
+                }
+                // 1 < x
+                (val, Value::Variable(var)) => var_info.comparisons.push((
+                    var.clone(),
+                    kind.flip(),
+                    Term::new_temporary(val.clone()),
+                )),
+                // 1 < 2 is irrelevant for data filtering, so this is probably a bug.
+                (_a, _b) => {
+                    return unsupported("comparison of values is not yet supported", term);
+                }
+            };
+        }
+        // Expanded into multiple conjunctions before processing.
+        Operator::Or => return invalid_state(format!("unexpanded `or`: {}", term.to_polar())),
+        Operator::ForAll => return unsupported("`forall` is not supported", term),
+        Operator::IfThenElse => return unsupported("if-then-else is not supported", term),
+        Operator::FindAll | Operator::Count | Operator::Sum | Operator::Min | Operator::Max => {
+            return unsupported("aggregation is not supported", term)
+        }
+    }
+    Ok(None)
+}
+
+#[derive(Debug)]
+struct Vars {
+    field_relationships: HashSet<(String, String, String)>,
+    in_relationships: HashSet<(String, String)>,
+    eq_values: HashMap<String, Term>,
+    contained_values: HashMap<String, HashSet<Term>>,
+    comparisons: HashMap<String, Vec<(ConstraintKind, Term)>>,
+    var_comparisons: HashSet<(String, ConstraintKind, String, Term)>,
+    not_types: HashMap<String, HashSet<String>>,
+    not_contained_values: HashMap<String, HashSet<Term>>,
+    not_in_relationships: HashSet<(String, String, Term)>,
+    /// The lookups of the fields of `field_relationships`, by child id.
+    field_terms: HashMap<String, Term>,
+    this_id: String,
+}
+
+/// The id of the set of variables containing `var`, creating a new set if there isn't one.
+fn var_id(
+    variables: &mut HashMap<String, HashSet<Symbol>>,
+    var: &Symbol,
+    get_id: &mut impl FnMut() -> String,
+) -> String {
+    if let Some((id, _)) = variables.iter().find(|(_, set)| set.contains(var)) {
+        return id.clone();
+    }
+    let new_id = get_id();
+    let mut new_set = HashSet::new();
+    new_set.insert(var.clone());
+    variables.insert(new_id.clone(), new_set);
+    new_id
+}
+
+/// Collapses the var info that we obtained from walking the expressions.
+/// Track equivalence classes of variables and assign each one an id.
+fn collapse_vars(var_info: VarInfo) -> PolarResult<Vars> {
+    // Merge variable cycles.
+    let mut joined_cycles: Vec<HashSet<Symbol>> = vec![];
+    'cycles: for (l, r) in var_info.cycles {
+        // See if we can add to an existing cycle.
+        for joined_cycle in &mut joined_cycles {
+            if joined_cycle.contains(&l) || joined_cycle.contains(&r) {
+                joined_cycle.insert(l);
+                joined_cycle.insert(r);
+                continue 'cycles;
+            }
+        }
+        // Create new cycle if we couldn't
+        let mut new_cycle = HashSet::new();
+        new_cycle.insert(l);
+        new_cycle.insert(r);
+        joined_cycles.push(new_cycle);
+    }
+
+    let mut next_id = 0;
+    let mut get_id = move || {
+        let id = next_id;
+        next_id += 1;
+        format!("{}", id)
+    };
+
+    // Give each cycle an id
+    let mut variables: HashMap<String, HashSet<Symbol>> = HashMap::new();
+    for joined_cycle in joined_cycles {
+        let id = get_id();
+        variables.insert(id, joined_cycle);
+    }
+
+    // Substitute in relationships
+    let mut parent_ids = vec![];
+    'parent_relationships: for (parent, _, _) in &var_info.field_relationships {
+        for (id, set) in &mut variables {
+            if set.contains(parent) {
+                parent_ids.push(id.clone());
+                continue 'parent_relationships;
+            }
+        }
+        // Create a new set if we didn't find one.
+        let new_id = get_id();
+        let mut new_set = HashSet::new();
+        new_set.insert(parent.clone());
+        variables.insert(new_id.clone(), new_set);
+        parent_ids.push(new_id);
+    }
+    let mut child_ids = vec![];
+    'child_relationships: for (_, _, child) in &var_info.field_relationships {
+        for (id, set) in &mut variables {
+            if set.contains(child) {
+                child_ids.push(id.clone());
+                continue 'child_relationships;
+            }
+        }
+        // Create a new set if we didn't find one.
+        let new_id = get_id();
+        let mut new_set = HashSet::new();
+        new_set.insert(child.clone());
+        variables.insert(new_id.clone(), new_set);
+        child_ids.push(new_id);
+    }
+
+    // If a.b = c and a.b = d, that means c = d.
+    // @Sorry(steve): Wow, what a loop. Maybe just loop over indexes instead.
+    let mut new_unifies: Vec<(String, String)> = vec![];
+    for (i, ((parent_id1, child_id1), (_, field1, _))) in parent_ids
+        .iter()
+        .zip(child_ids.iter())
+        .zip(var_info.field_relationships.iter())
+        .enumerate()
+    {
+        for (j, ((parent_id2, child_id2), (_, field2, _))) in parent_ids
+            .iter()
+            .zip(child_ids.iter())
+            .zip(var_info.field_relationships.iter())
+            .enumerate()
+        {
+            if i != j && parent_id1 == parent_id2 && field1 == field2 && child_id1 != child_id2 {
+                // Unify children
+                new_unifies.push((child_id1.clone(), child_id2.clone()));
+            }
+        }
+    }
+
+    // @TODO(steve): There are absolutely bugs in here.
+    // If we're turning 0 into 1 and then 0 into 2 it'll just blow up
+    // not correctly turn 0 and 1 into 2. Needs some tests.
+    for (x, y) in &new_unifies {
+        if x != y {
+            match (variables.remove(x), variables.remove(y)) {
+                (Some(mut xs), Some(ys)) => {
+                    xs.extend(ys);
+                    variables.insert(x.clone(), xs);
+                }
+                _ => return invalid_state(format!("can't unify variables {} and {}", x, y)),
+            }
+        }
+    }
+
+    // Substitute in relationship ids.
+    // @Sorry(steve): This is a real mess too.
+    let mut field_relationships = HashSet::new();
+    let mut field_terms = HashMap::new();
+    for (parent, field, child) in &var_info.field_relationships {
+        let mut parent_id = String::new();
+        let mut child_id = String::new();
+        for (id, set) in &mut variables {
+            if set.contains(parent) {
+                parent_id = id.clone();
+            }
+            if set.contains(child) {
+                child_id = id.clone();
+            }
+        }
+        if parent_id.is_empty() || child_id.is_empty() {
+            return invalid_state(format!("no id for {}.{} = {}", parent, field, child));
+        }
+        if let Some(term) = var_info.field_terms.get(child) {
+            field_terms
+                .entry(child_id.clone())
+                .or_insert_with(|| term.clone());
+        }
+        field_relationships.insert((parent_id, field.clone(), child_id));
+    }
+
+    // In relationships
+    let mut in_relationships = HashSet::new();
+    for (lhs, rhs) in &var_info.in_relationships {
+        let mut lhs_id = String::new();
+        let mut rhs_id = String::new();
+        for (id, set) in &mut variables {
+            if set.contains(lhs) {
+                lhs_id = id.clone();
+            }
+            if set.contains(rhs) {
+                rhs_id = id.clone();
+            }
+        }
+        if lhs_id == String::new() {
+            let new_id = get_id();
+            let mut new_set = HashSet::new();
+            new_set.insert(lhs.clone());
+            variables.insert(new_id.clone(), new_set);
+        }
+        if rhs_id == String::new() {
+            let new_id = get_id();
+            let mut new_set = HashSet::new();
+            new_set.insert(rhs.clone());
+            variables.insert(new_id.clone(), new_set);
+        }
+        in_relationships.insert((lhs_id, rhs_id));
+    }
+
+    // I think a var can only have one value since we make sure there's a var for the dot lookup,
+    // and if they had aliases they'd be collapsed by now, so it should be an error
+    // if foo.name = "steve" and foo.name = "gabe".
+    let mut eq_values = HashMap::new();
+    'values: for (var, value) in var_info.eq_values {
+        for (id, set) in &mut variables {
+            if set.contains(&var) {
+                // @TODO(steve): If we already have a value for it make sure they match don't just
+                // overwrite it.
+                eq_values.insert(id.clone(), value);
+                continue 'values;
+            }
+        }
+        // Create new variable if we didn't find one.
+        let new_id = get_id();
+        let mut new_set = HashSet::new();
+        new_set.insert(var.clone());
+        variables.insert(new_id.clone(), new_set);
+        eq_values.insert(new_id, value);
+    }
+
+    let mut contained_values = HashMap::new();
+    'contained_values: for (value, var) in var_info.contained_values {
+        for (id, set) in &mut variables {
+            if set.contains(&var) {
+                contained_values
+                    .entry(id.clone())
+                    .or_insert_with(HashSet::new)
+                    .insert(value);
+                continue 'contained_values;
+            }
+        }
+        // Create new variable if we didn't find one.
+        let new_id = get_id();
+        let mut new_set = HashSet::new();
+        new_set.insert(var.clone());
+        variables.insert(new_id.clone(), new_set);
+        let mut new_val_set = HashSet::new();
+        new_val_set.insert(value);
+        contained_values.insert(new_id, new_val_set);
+    }
+
+    let mut comparisons = HashMap::new();
+    'comparisons: for (var, kind, value) in var_info.comparisons {
+        for (id, set) in &mut variables {
+            if set.contains(&var) {
+                comparisons
+                    .entry(id.clone())
+                    .or_insert_with(Vec::new)
+                    .push((kind, value));
+                continue 'comparisons;
+            }
+        }
+        // Create new variable if we didn't find one.
+        let new_id = get_id();
+        let mut new_set = HashSet::new();
+        new_set.insert(var.clone());
+        variables.insert(new_id.clone(), new_set);
+        comparisons.insert(new_id, vec![(kind, value)]);
+    }
+
+    // Comparisons between two variables. Both are fields of some object, so
+    // they've already been given ids by the field relationships.
+    let mut var_comparisons = HashSet::new();
+    for (lhs, kind, rhs, term) in var_info.var_comparisons {
+        let find_id = |var: &Symbol| {
+            variables
+                .iter()
+                .find(|(_, set)| set.contains(var))
+                .map(|(id, _)| id.clone())
+        };
+        match (find_id(&lhs), find_id(&rhs)) {
+            (Some(lhs_id), Some(rhs_id)) => {
+                var_comparisons.insert((lhs_id, kind, rhs_id, term));
+            }
+            _ => {
+                return Err(DataFilteringError::new(
+                    &format!(
+                        "comparing variables that aren't fields is not yet supported: {} and {}",
+                        lhs, rhs
+                    ),
+                    Some(&term),
+                )
+                .into())
+            }
+        }
+    }
+
+    // Negations
+    let mut not_contained_values = HashMap::new();
+    for (value, var) in var_info.not_contained_values {
+        not_contained_values
+            .entry(var_id(&mut variables, &var, &mut get_id))
+            .or_insert_with(HashSet::new)
+            .insert(value);
+    }
+    let mut not_in_relationships = HashSet::new();
+    for (lhs, rhs, term) in &var_info.not_in_relationships {
+        let lhs_id = var_id(&mut variables, lhs, &mut get_id);
+        let rhs_id = var_id(&mut variables, rhs, &mut get_id);
+        not_in_relationships.insert((lhs_id, rhs_id, term.clone()));
+    }
+    let mut not_types = HashMap::new();
+    for (var, typ) in var_info.not_types {
+        not_types
+            .entry(var_id(&mut variables, &var, &mut get_id))
+            .or_insert_with(HashSet::new)
+            .insert(typ);
+    }
+
+    // Matched variables get an id even if nothing else constrains them.
+    for (var, _) in var_info.types {
+        var_id(&mut variables, &var, &mut get_id);
+    }
+
+    let mut this_id = String::new();
+    for (id, set) in &variables {
+        if set.contains(&Symbol::new("_this")) {
+            this_id = id.clone()
+        }
+    }
+
+    Ok(Vars {
+        field_relationships,
+        in_relationships,
+        eq_values,
+        contained_values,
+        comparisons,
+        var_comparisons,
+        not_types,
+        not_contained_values,
+        not_in_relationships,
+        field_terms,
+        this_id,
+    })
+}
+
+/// How the objects of a relationship field join the object with the field.
+struct Join<'a> {
+    other_class_tag: &'a str,
+    my_field: &'a str,
+    other_field: &'a str,
+    /// The class and fields of the intermediate objects of a many-to-many relationship.
+    through: Option<(&'a str, &'a str, &'a str)>,
+}
+
+impl Join<'_> {
+    /// A constraint that `my_field` is (`In`) or isn't (`Nin`) joined to the results of
+    /// `other_id`. For a many-to-many relationship this adds a fetch of the intermediate
+    /// objects joined to those results, and joins to them instead.
+    fn constraint(
+        &self,
+        result_set: &mut ResultSet,
+        kind: ConstraintKind,
+        other_id: &str,
+    ) -> Constraint {
+        let mut value = Ref {
+            field: Some(self.other_field.to_owned()),
+            result_id: other_id.to_owned(),
+        };
+        if let Some((class_tag, through_my_field, through_other_field)) = self.through {
+            let through_id = format!("{}_through", other_id);
+            let through_request = FetchRequest {
+                class_tag: class_tag.to_owned(),
+                constraints: vec![Constraint {
+                    kind: ConstraintKind::In,
+                    field: through_other_field.to_owned(),
+                    value: ConstraintValue::Ref(value),
+                }],
+            };
+            result_set
+                .requests
+                .insert(through_id.clone(), through_request);
+            result_set.resolve_order.push(through_id.clone());
+            value = Ref {
+                field: Some(through_my_field.to_owned()),
+                result_id: through_id,
+            };
+        }
+        Constraint {
+            kind,
+            field: self.my_field.to_owned(),
+            value: ConstraintValue::Ref(value),
+        }
+    }
+}
+
+/// Build the result set for the constrained vars, or `None` if nothing can satisfy them.
+fn constrain_vars(types: &Types, vars: &Vars, this_type: &str) -> PolarResult<Option<ResultSet>> {
+    let mut result_set = ResultSet {
+        requests: HashMap::new(),
+        resolve_order: vec![],
+        result_id: vars.this_id.clone(),
+    };
+    if constrain_var(&mut result_set, types, vars, &vars.this_id, this_type)? {
+        Ok(Some(result_set))
+    } else {
+        Ok(None)
+    }
+}
+
+fn constrain_var(
+    result_set: &mut ResultSet,
+    types: &Types,
+    vars: &Vars,
+    var_id: &str,
+    var_type: &str,
+) -> PolarResult<bool> {
+    // The var can't both be and not be a `var_type`.
+    if matches!(vars.not_types.get(var_id), Some(tags) if tags.contains(var_type)) {
+        return Ok(false);
+    }
+
+    // @TODO(steve): Probably should check the type against the var types. I think???
+    let type_def = types
+        .iter()
+        .find(|r| r.0 == var_type)
+        .map(|r| r.1.clone())
+        .unwrap_or_else(HashMap::new);
+
+    let mut request = result_set
+        .requests
+        .remove(var_id)
+        .unwrap_or_else(|| FetchRequest {
+            class_tag: var_type.to_string(),
+            constraints: vec![],
+        });
+
+    for (parent, field, child) in &vars.field_relationships {
+        if parent == var_id {
+            let typ = match type_def.get(field) {
+                None => {
+                    return Err(DataFilteringError::new(
+                        &format!("unknown field {} on {}", field, var_type),
+                        vars.field_terms.get(child),
+                    )
+                    .into())
+                }
+                Some(t) => t,
+            };
+
+            let join = match typ {
+                Type::Relationship {
+                    other_class_tag,
+                    my_field,
+                    other_field,
+                    ..
+                } => Some(Join {
+                    other_class_tag,
+                    my_field,
+                    other_field,
+                    through: None,
+                }),
+                Type::ManyToMany {
+                    other_class_tag,
+                    my_field,
+                    through_class_tag,
+                    through_my_field,
+                    through_other_field,
+                    other_field,
+                } => Some(Join {
+                    other_class_tag,
+                    my_field,
+                    other_field,
+                    through: Some((through_class_tag, through_my_field, through_other_field)),
+                }),
+                Type::Base { .. } => None,
+            };
+            if let Some(join) = join {
+                if !constrain_var(result_set, types, vars, child, join.other_class_tag)? {
+                    return Ok(false);
+                }
+
+                // If the constrained child var doesn't have any constraints on it, we don't need to
+                // constrain this var. Otherwise we're just saying field foo in all Foos which
+                // would fetch all Foos and not be good.
+                if let Some(child_result) = result_set.requests.remove(child) {
+                    if !child_result.constraints.is_empty() {
+                        result_set.requests.insert(child.to_owned(), child_result);
+                        let constraint = join.constraint(result_set, ConstraintKind::In, child);
+                        request.constraints.push(constraint);
+                    } else {
+                        // Remove the id from the resolve_order too.
+                        result_set.resolve_order.pop();
+                    }
+                }
+
+                if let Some(values) = vars.not_contained_values.get(child) {
+                    return Err(DataFilteringError::new(
+                        &format!(
+                            "checking that a value is not in relationship {} is not yet supported",
+                            field
+                        ),
+                        values.iter().next(),
+                    )
+                    .into());
+                }
+
+                // Anti-join against the vars that must not be in the relationship. Unlike
+                // above, an unconstrained var excludes everything related to this var.
+                for (lhs, _, _) in vars.not_in_relationships.iter().filter(|r| r.1 == *child) {
+                    if constrain_var(result_set, types, vars, lhs, join.other_class_tag)? {
+                        let constraint = join.constraint(result_set, ConstraintKind::Nin, lhs);
+                        request.constraints.push(constraint);
+                    }
+                }
+
+                continue;
+            }
+            // Non relationship or unknown type info.
+            let mut contributed_constraints = false;
+            if let Some(value) = vars.eq_values.get(child) {
+                request.constraints.push(Constraint {
+                    kind: ConstraintKind::Eq,
+                    field: field.clone(),
+                    value: ConstraintValue::Term(value.clone()),
+                });
+                contributed_constraints = true;
+            }
+            if let Some(values) = vars.contained_values.get(child) {
+                for value in values {
+                    request.constraints.push(Constraint {
+                        kind: ConstraintKind::Contains,
+                        field: field.clone(),
+                        value: ConstraintValue::Term(value.clone()),
+                    });
+                }
+                contributed_constraints = true;
+            }
+            for eqf in vars
+                .field_relationships
+                .iter()
+                .filter(|r| r.0 == *parent && r.1 != *field && r.2 == *child)
+            {
+                request.constraints.push(Constraint {
+                    kind: ConstraintKind::Eq,
+                    field: field.clone(),
+                    value: ConstraintValue::Field(eqf.1.clone()),
+                });
+                contributed_constraints = true;
+            }
+            if let Some(values) = vars.not_contained_values.get(child) {
+                for value in values {
+                    request.constraints.push(Constraint {
+                        kind: ConstraintKind::NotContains,
+                        field: field.clone(),
+                        value: ConstraintValue::Term(value.clone()),
+                    });
+                }
+                contributed_constraints = true;
+            }
+            if let Some((_, _, term)) = vars.not_in_relationships.iter().find(|r| r.1 == *child) {
+                return Err(DataFilteringError::new(
+                    &format!("`not _ in {}` is only supported for relationships", field),
+                    Some(term),
+                )
+                .into());
+            }
+            if let Some(comparisons) = vars.comparisons.get(child) {
+                for (kind, value) in comparisons {
+                    request.constraints.push(Constraint {
+                        kind: kind.clone(),
+                        field: field.clone(),
+                        value: ConstraintValue::Term(value.clone()),
+                    });
+                }
+                contributed_constraints = true;
+            }
+            for (_, kind, other, term) in vars.var_comparisons.iter().filter(|c| c.0 == *child) {
+                let other_field = match vars
+                    .field_relationships
+                    .iter()
+                    .find(|r| r.0 == *parent && r.2 == *other)
+                {
+                    Some((_, other_field, _)) => other_field.clone(),
+                    None => {
+                        return Err(DataFilteringError::new(
+                            &format!(
+                            "comparing {} to a field of a different object is not yet supported",
+                            field
+                        ),
+                            Some(term),
+                        )
+                        .into())
+                    }
+                };
+                request.constraints.push(Constraint {
+                    kind: kind.clone(),
+                    field: field.clone(),
+                    value: ConstraintValue::Field(other_field),
+                });
+                contributed_constraints = true;
+            }
+            // The field is compared to by the constraint on the other field.
+            if vars.var_comparisons.iter().any(|c| c.2 == *child) {
+                contributed_constraints = true;
+            }
+            if !contributed_constraints {
+                return invalid_state(format!("no constraints on field {}", field));
+            }
+        }
+    }
+
+    // Constrain any vars that are `in` this var.
+    // Add their constraints to this one.
+    // @NOTE(steve): I think this is right, but I'm not totally sure.
+    // This might assume that the current var is a relationship of kind "children".
+    for (lhs, rhs) in &vars.in_relationships {
+        if rhs == var_id {
+            if !constrain_var(result_set, types, vars, lhs, var_type)? {
+                return Ok(false);
+            }
+            match (
+                result_set.requests.remove(lhs),
+                result_set.resolve_order.pop(),
+            ) {
+                (Some(in_result_set), Some(id)) if id == *lhs => {
+                    request.constraints.extend(in_result_set.constraints)
+                }
+                _ => return invalid_state(format!("no fetch request for {}", lhs)),
+            }
+        }
+    }
+
+    result_set.requests.insert(var_id.to_string(), request);
+    result_set.resolve_order.push(var_id.to_string());
+    Ok(true)
+}
+
+fn build_result_set(
+    types: &Types,
+    conjunction: &[Term],
+    class_tag: &str,
+) -> PolarResult<Option<ResultSet>> {
+    let var_info = process_result(conjunction)?;
+    let vars = collapse_vars(var_info)?;
+
+    let result_set = constrain_vars(types, &vars, class_tag)?;
+    if result_set.is_some() {
+        check_unrelated_vars(&vars)?;
+    }
+    Ok(result_set)
+}
+
+/// Only constraints on vars related to the result end up in the result set, so the
+/// constraints on any other var would be silently dropped. These come from negating
+/// lookups through a relationship, like `not (x in y.xs and x.a = 1)`, which would
+/// otherwise match too much.
+fn check_unrelated_vars(vars: &Vars) -> PolarResult<()> {
+    let mut related = HashSet::new();
+    let mut pending = vec![&vars.this_id];
+    while let Some(id) = pending.pop() {
+        if !related.insert(id) {
+            continue;
+        }
+        let children = vars
+            .field_relationships
+            .iter()
+            .filter(|r| r.0 == *id)
+            .map(|r| &r.2);
+        let members = vars
+            .in_relationships
+            .iter()
+            .filter(|r| r.1 == *id)
+            .map(|r| &r.0);
+        let non_members = vars
+            .not_in_relationships
+            .iter()
+            .filter(|r| r.1 == *id)
+            .map(|r| &r.0);
+        pending.extend(children.chain(members).chain(non_members));
+    }
+
+    match vars
+        .field_relationships
+        .iter()
+        .find(|r| !related.contains(&r.0))
+    {
+        Some((_, field, child)) => Err(DataFilteringError::new(
+            &format!(
+                "looking up {} on a variable that isn't related to the result is not yet supported",
+                field
+            ),
+            vars.field_terms.get(child),
+        )
+        .into()),
+        None => Ok(()),
+    }
+}
+
+pub fn build_filter_plan(
+    types: Types,
+    partial_results: PartialResults,
+    variable: &str,
+    class_tag: &str,
+) -> PolarResult<FilterPlan> {
+    let filter_plan = build_result_sets(&types, &partial_results, variable, class_tag)?;
+    Ok(optimize(filter_plan))
+}
+
+/// Build the unoptimized filter plan, with a result set for each way of satisfying
+/// each partial result.
+fn build_result_sets(
+    types: &Types,
+    partial_results: &[ResultEvent],
+    variable: &str,
+    class_tag: &str,
+) -> PolarResult<FilterPlan> {
+    let mut filter_plan = FilterPlan {
+        result_sets: vec![],
+    };
+
+    for result in partial_results {
+        let term = match result.bindings.get(&Symbol::new(variable)) {
+            Some(term) => term,
+            None => {
+                return Err(ParameterError(format!(
+                    "partial result without a binding for {}",
+                    variable
+                ))
+                .into())
+            }
+        };
+        if !matches!(term.value().as_expression(), Ok(exp) if exp.operator == Operator::And) {
+            return unsupported(
+                "expected the partial result to be an `and` expression",
+                term,
+            );
+        }
+
+        // Each way of satisfying the disjunctions in the result is a separate result set.
+        for conjunction in expand_disjunctions(term) {
+            if let Some(result_set) = build_result_set(types, &conjunction, class_tag)? {
+                filter_plan.result_sets.push(result_set);
+            }
+        }
+    }
+
+    Ok(filter_plan)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::events::QueryEvent;
+    use crate::polar::Polar;
+
+    fn base(class_tag: &str) -> Type {
+        Type::Base {
+            class_tag: class_tag.to_owned(),
+        }
+    }
+
+    fn relationship(kind: &str, other: &str, my_field: &str, other_field: &str) -> Type {
+        Type::Relationship {
+            kind: kind.to_owned(),
+            other_class_tag: other.to_owned(),
+            my_field: my_field.to_owned(),
+            other_field: other_field.to_owned(),
+        }
+    }
+
+    /// The types used by the data filtering tests.
+    pub(super) fn types() -> Types {
+        hashmap! {
+            "Bar".to_owned() => hashmap! {
+                "id".to_owned() => base("String"),
+                "is_cool".to_owned() => base("Boolean"),
+                "is_still_cool".to_owned() => base("Boolean"),
+            },
+            "Foo".to_owned() => hashmap! {
+                "id".to_owned() => base("String"),
+                "bar_id".to_owned() => base("String"),
+                "is_fooey".to_owned() => base("Boolean"),
+                "numbers".to_owned() => base("List"),
+                "metadata".to_owned() => base("Dictionary"),
+                "bar".to_owned() => relationship("parent", "Bar", "bar_id", "id"),
+                "logs".to_owned() => relationship("children", "FooLogRecord", "id", "foo_id"),
+                "tags".to_owned() => Type::ManyToMany {
+                    other_class_tag: "Tag".to_owned(),
+                    my_field: "id".to_owned(),
+                    through_class_tag: "FooTag".to_owned(),
+                    through_my_field: "foo_id".to_owned(),
+                    through_other_field: "tag_id".to_owned(),
+                    other_field: "id".to_owned(),
+                },
+            },
+            "FooLogRecord".to_owned() => hashmap! {
+                "id".to_owned() => base("String"),
+                "foo_id".to_owned() => base("String"),
+                "data".to_owned() => base("String"),
+            },
+            "FooTag".to_owned() => hashmap! {
+                "foo_id".to_owned() => base("String"),
+                "tag_id".to_owned() => base("String"),
+            },
+            "Tag".to_owned() => hashmap! {
+                "id".to_owned() => base("String"),
+                "name".to_owned() => base("String"),
+            },
+        }
+    }
+
+    /// Query `allow("steve", "get", resource)` with `resource` constrained to `class_tag`
+    /// and collect the partial results.
+    pub(super) fn partial_results(p: &Polar, class_tag: &str) -> PolarResult<PartialResults> {
+        let mut q = p.new_query_from_term(
+            term!(call!("allow", ["steve", "get", sym!("resource")])),
+            false,
+        );
+        q.bind(
+            sym!("resource"),
+            term!(op!(
+                And,
+                term!(op!(
+                    Isa,
+                    term!(sym!("resource")),
+                    term!(pattern!(instance!(class_tag)))
+                ))
+            )),
+        )?;
+        let mut results = vec![];
+        loop {
+            match q.next_event()? {
+                QueryEvent::Result { bindings, .. } => results.push(ResultEvent::new(bindings)),
+                QueryEvent::ExternalIsa { call_id, .. }
+                | QueryEvent::ExternalIsaWithPath { call_id, .. }
+                | QueryEvent::ExternalIsSubclass { call_id, .. } => {
+                    q.question_result(call_id, true)?
+                }
+                QueryEvent::Done { .. } => return Ok(results),
+                event => panic!("unexpected event: {:?}", event),
+            }
+        }
+    }
+
+    /// Build the filter plan for the resources of `class_tag` allowed by `policy`.
+    pub(super) fn filter_plan(policy: &str, class_tag: &str) -> PolarResult<FilterPlan> {
+        let p = Polar::new();
+        p.load_str(policy)?;
+        p.build_filter_plan(
+            types(),
+            partial_results(&p, class_tag)?,
+            "resource",
+            class_tag,
+        )
+    }
+
+    #[test]
+    fn test_relationship_plan() -> PolarResult<()> {
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if
+                   resource.bar = bar and
+                   bar.is_cool = true and
+                   resource.is_fooey = true;"#,
+            "Foo",
+        )?;
+        assert_eq!(plan.result_sets.len(), 1);
+        let result_set = &plan.result_sets[0];
+        assert_eq!(result_set.resolve_order.len(), 2);
+        assert_eq!(result_set.resolve_order.last(), Some(&result_set.result_id));
+
+        let bar_id = &result_set.resolve_order[0];
+        let bar = &result_set.requests[bar_id];
+        assert_eq!(bar.class_tag, "Bar");
+        assert_eq!(
+            bar.constraints,
+            vec![Constraint {
+                kind: ConstraintKind::Eq,
+                field: "is_cool".to_owned(),
+                value: ConstraintValue::Term(term!(true)),
+            }]
+        );
+
+        let foo = &result_set.requests[&result_set.result_id];
+        assert_eq!(foo.class_tag, "Foo");
+        assert_eq!(foo.constraints.len(), 2);
+        assert!(foo.constraints.contains(&Constraint {
+            kind: ConstraintKind::In,
+            field: "bar_id".to_owned(),
+            value: ConstraintValue::Ref(Ref {
+                field: Some("id".to_owned()),
+                result_id: bar_id.clone(),
+            }),
+        }));
+        assert!(foo.constraints.contains(&Constraint {
+            kind: ConstraintKind::Eq,
+            field: "is_fooey".to_owned(),
+            value: ConstraintValue::Term(term!(true)),
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_disjunctions() {
+        let (a, b, c, d) = (
+            term!(sym!("a")),
+            term!(sym!("b")),
+            term!(sym!("c")),
+            term!(sym!("d")),
+        );
+        let exp = term!(op!(
+            And,
+            term!(op!(Or, a.clone(), b.clone())),
+            c.clone(),
+            term!(op!(Or, d.clone(), term!(op!(And))))
+        ));
+        assert_eq!(
+            expand_disjunctions(&exp),
+            vec![
+                vec![a.clone(), c.clone(), d.clone()],
+                vec![a, c.clone()],
+                vec![b.clone(), c.clone(), d],
+                vec![b, c],
+            ]
+        );
+        assert!(expand_disjunctions(&term!(op!(Or))).is_empty());
+    }
+
+    #[test]
+    fn test_or_plan() -> PolarResult<()> {
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if
+                   not (resource.id != "something" and resource.bar_id != "goodbye");"#,
+            "Foo",
+        )?;
+        assert_eq!(plan.result_sets.len(), 2);
+        let constraints = plan
+            .result_sets
+            .iter()
+            .map(|result_set| {
+                result_set.requests[&result_set.result_id]
+                    .constraints
+                    .clone()
+            })
+            .collect::<Vec<_>>();
+        assert!(constraints.contains(&vec![Constraint {
+            kind: ConstraintKind::Eq,
+            field: "id".to_owned(),
+            value: ConstraintValue::Term(term!("something")),
+        }]));
+        assert!(constraints.contains(&vec![Constraint {
+            kind: ConstraintKind::Eq,
+            field: "bar_id".to_owned(),
+            value: ConstraintValue::Term(term!("goodbye")),
+        }]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_or_distributes_over_and() -> PolarResult<()> {
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if
+                   not (resource.id != "something" and resource.bar_id != "goodbye") and
+                   resource.bar.is_cool = true;"#,
+            "Foo",
+        )?;
+        assert_eq!(plan.result_sets.len(), 2);
+        for result_set in &plan.result_sets {
+            assert_eq!(result_set.resolve_order.len(), 2);
+            let foo = &result_set.requests[&result_set.result_id];
+            assert_eq!(foo.constraints.len(), 2);
+        }
+        Ok(())
+    }
+
+    fn result_constraints(plan: &FilterPlan) -> Vec<Constraint> {
+        assert_eq!(plan.result_sets.len(), 1);
+        let result_set = &plan.result_sets[0];
+        result_set.requests[&result_set.result_id]
+            .constraints
+            .clone()
+    }
+
+    #[test]
+    fn test_comparison_plan() -> PolarResult<()> {
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if
+                   resource.id > "m" and
+                   "third" >= resource.id and
+                   resource.bar_id != "hello";"#,
+            "Foo",
+        )?;
+        let constraints = result_constraints(&plan);
+        assert_eq!(constraints.len(), 3);
+        assert!(constraints.contains(&Constraint {
+            kind: ConstraintKind::Gt,
+            field: "id".to_owned(),
+            value: ConstraintValue::Term(term!("m")),
+        }));
+        // The value is on the left, so the comparison is flipped.
+        assert!(constraints.contains(&Constraint {
+            kind: ConstraintKind::Leq,
+            field: "id".to_owned(),
+            value: ConstraintValue::Term(term!("third")),
+        }));
+        assert!(constraints.contains(&Constraint {
+            kind: ConstraintKind::Neq,
+            field: "bar_id".to_owned(),
+            value: ConstraintValue::Term(term!("hello")),
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_comparison_plan() -> PolarResult<()> {
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if resource.id < resource.bar_id;"#,
+            "Foo",
+        )?;
+        // The comparison is only constrained once.
+        let constraints = result_constraints(&plan);
+        assert_eq!(
+            constraints,
+            vec![Constraint {
+                kind: ConstraintKind::Lt,
+                field: "id".to_owned(),
+                value: ConstraintValue::Field("bar_id".to_owned()),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_comparison_json() {
+        let constraint = Constraint {
+            kind: ConstraintKind::Geq,
+            field: "id".to_owned(),
+            value: ConstraintValue::Field("bar_id".to_owned()),
+        };
+        let json = serde_json::to_string(&constraint).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"Geq","field":"id","value":{"Field":"bar_id"}}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Constraint>(&json).unwrap(),
+            constraint
+        );
+    }
+
+    #[test]
+    fn test_unsupported_error() {
+        let err = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if
+                   resource.id + 1 = 2;"#,
+            "Foo",
+        )
+        .unwrap_err();
+        match &err.kind {
+            ErrorKind::DataFiltering(DataFilteringError {
+                term: Some(term),
+                span: Some(_),
+                ..
+            }) => assert_eq!(
+                term.value().as_expression().unwrap().operator,
+                Operator::Add
+            ),
+            _ => panic!("unexpected error: {}", err),
+        }
+        assert!(err
+            .to_string()
+            .starts_with("Data filtering error: addition is not supported"));
+        assert!(err.to_string().ends_with("at line 2, column 20"));
+    }
+
+    #[test]
+    fn test_unknown_field_error() {
+        let err = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if resource.nope = 1;"#,
+            "Foo",
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::DataFiltering(_)));
+        assert_eq!(
+            err.to_string(),
+            "Data filtering error: unknown field nope on Foo: \"nope\" at line 1, column 50"
+        );
+    }
+
+    #[test]
+    fn test_missing_binding_error() {
+        let results = vec![ResultEvent::new(hashmap! {})];
+        let err = build_filter_plan(types(), results, "resource", "Foo").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Parameter(_)));
+    }
+
+    #[test]
+    fn test_not_plan() -> PolarResult<()> {
+        // `not _this matches Foo{}` can't be satisfied, so only one of the
+        // expanded result sets is left.
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if not resource.is_fooey;"#,
+            "Foo",
+        )?;
+        assert_eq!(
+            result_constraints(&plan),
+            vec![Constraint {
+                kind: ConstraintKind::Neq,
+                field: "is_fooey".to_owned(),
+                value: ConstraintValue::Term(term!(true)),
+            }]
+        );
+
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if not resource.id in ["a", "b"];"#,
+            "Foo",
+        )?;
+        let constraints = result_constraints(&plan);
+        assert_eq!(constraints.len(), 2);
+        assert!(constraints
+            .iter()
+            .all(|constraint| constraint.kind == ConstraintKind::Neq));
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_contains_plan() -> PolarResult<()> {
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if not 1 in resource.numbers;"#,
+            "Foo",
+        )?;
+        assert_eq!(
+            result_constraints(&plan),
+            vec![Constraint {
+                kind: ConstraintKind::NotContains,
+                field: "numbers".to_owned(),
+                value: ConstraintValue::Term(term!(1)),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_anti_join_plan() -> PolarResult<()> {
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if not _ in resource.logs;"#,
+            "Foo",
+        )?;
+        assert_eq!(plan.result_sets.len(), 1);
+        let result_set = &plan.result_sets[0];
+        assert_eq!(result_set.resolve_order.len(), 2);
+        let log_id = &result_set.resolve_order[0];
+        let logs = &result_set.requests[log_id];
+        assert_eq!(logs.class_tag, "FooLogRecord");
+        assert!(logs.constraints.is_empty());
+        assert_eq!(
+            result_set.requests[&result_set.result_id].constraints,
+            vec![Constraint {
+                kind: ConstraintKind::Nin,
+                field: "id".to_owned(),
+                value: ConstraintValue::Ref(Ref {
+                    field: Some("foo_id".to_owned()),
+                    result_id: log_id.clone(),
+                }),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_value_in_relationship_error() {
+        let err = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if not "log" in resource.logs;"#,
+            "Foo",
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::DataFiltering(_)));
+        assert!(err
+            .to_string()
+            .contains("not in relationship logs is not yet supported"));
+        assert!(err.to_string().ends_with("at line 1, column 45"));
+    }
+
+    #[test]
+    fn test_negated_relationship_lookup_error() {
+        let err = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if
+                   not (log in resource.logs and log.data = "x");"#,
+            "Foo",
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::DataFiltering(_)));
+        assert!(err.to_string().contains(
+            "looking up data on a variable that isn't related to the result is not yet supported"
+        ));
+    }
+
+    #[test]
+    fn test_specializer_fields_plan() -> PolarResult<()> {
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo{is_fooey: true, id: "a"}) if
+                   resource matches Foo;"#,
+            "Foo",
+        )?;
+        let constraints = result_constraints(&plan);
+        assert_eq!(constraints.len(), 2);
+        assert!(constraints.contains(&Constraint {
+            kind: ConstraintKind::Eq,
+            field: "is_fooey".to_owned(),
+            value: ConstraintValue::Term(term!(true)),
+        }));
+        assert!(constraints.contains(&Constraint {
+            kind: ConstraintKind::Eq,
+            field: "id".to_owned(),
+            value: ConstraintValue::Term(term!("a")),
+        }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_dictionary_value_plan() -> PolarResult<()> {
+        // Unlike a dictionary pattern, a dictionary value must be equal to the whole field.
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if resource.metadata = {owner: "steve"};"#,
+            "Foo",
+        )?;
+        assert_eq!(
+            result_constraints(&plan),
+            vec![Constraint {
+                kind: ConstraintKind::Eq,
+                field: "metadata".to_owned(),
+                value: ConstraintValue::Term(term!(btreemap! {
+                    sym!("owner") => term!("steve"),
+                })),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_specializer_fields_plan() -> PolarResult<()> {
+        for policy in &[
+            r#"allow("steve", "get", resource: Foo{bar: {is_cool: true}}) if resource matches Foo;"#,
+            r#"allow("steve", "get", resource: Foo{bar: Bar{is_cool: true}}) if resource matches Foo;"#,
+        ] {
+            let plan = filter_plan(policy, "Foo")?;
+            assert_eq!(plan.result_sets.len(), 1);
+            let result_set = &plan.result_sets[0];
+            assert_eq!(result_set.resolve_order.len(), 2);
+            let bar_id = &result_set.resolve_order[0];
+            let bar = &result_set.requests[bar_id];
+            assert_eq!(bar.class_tag, "Bar");
+            assert_eq!(
+                bar.constraints,
+                vec![Constraint {
+                    kind: ConstraintKind::Eq,
+                    field: "is_cool".to_owned(),
+                    value: ConstraintValue::Term(term!(true)),
+                }]
+            );
+            assert_eq!(
+                result_set.requests[&result_set.result_id].constraints,
+                vec![Constraint {
+                    kind: ConstraintKind::In,
+                    field: "bar_id".to_owned(),
+                    value: ConstraintValue::Ref(Ref {
+                        field: Some("id".to_owned()),
+                        result_id: bar_id.clone(),
+                    }),
+                }]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_many_to_many_plan() -> PolarResult<()> {
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if
+                   tag in resource.tags and tag.name = "cool";"#,
+            "Foo",
+        )?;
+        assert_eq!(plan.result_sets.len(), 1);
+        let result_set = &plan.result_sets[0];
+        assert_eq!(result_set.resolve_order.len(), 3);
+        let (tag_id, foo_tag_id) = (&result_set.resolve_order[0], &result_set.resolve_order[1]);
+        assert_eq!(
+            result_set.requests[tag_id],
+            FetchRequest {
+                class_tag: "Tag".to_owned(),
+                constraints: vec![Constraint {
+                    kind: ConstraintKind::Eq,
+                    field: "name".to_owned(),
+                    value: ConstraintValue::Term(term!("cool")),
+                }],
+            }
+        );
+        assert_eq!(
+            result_set.requests[foo_tag_id],
+            FetchRequest {
+                class_tag: "FooTag".to_owned(),
+                constraints: vec![Constraint {
+                    kind: ConstraintKind::In,
+                    field: "tag_id".to_owned(),
+                    value: ConstraintValue::Ref(Ref {
+                        field: Some("id".to_owned()),
+                        result_id: tag_id.clone(),
+                    }),
+                }],
+            }
+        );
+        assert_eq!(
+            result_constraints(&plan),
+            vec![Constraint {
+                kind: ConstraintKind::In,
+                field: "id".to_owned(),
+                value: ConstraintValue::Ref(Ref {
+                    field: Some("foo_id".to_owned()),
+                    result_id: foo_tag_id.clone(),
+                }),
+            }]
+        );
+
+        let plan = filter_plan(
+            r#"allow("steve", "get", resource: Foo) if not _ in resource.tags;"#,
+            "Foo",
+        )?;
+        let result_set = &plan.result_sets[0];
+        assert_eq!(result_set.resolve_order.len(), 3);
+        assert_eq!(
+            result_constraints(&plan),
+            vec![Constraint {
+                kind: ConstraintKind::Nin,
+                field: "id".to_owned(),
+                value: ConstraintValue::Ref(Ref {
+                    field: Some("foo_id".to_owned()),
+                    result_id: result_set.resolve_order[1].clone(),
+                }),
+            }]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,535 @@
+//! Optimize a `FilterPlan` before it's resolved.
+//!
+//! Plans are built with a result set for every way of satisfying every partial result,
+//! so they are often repetitive. The optimizer:
+//!
+//! * removes duplicate result sets,
+//! * removes result sets whose results are all returned by another result set,
+//! * merges result sets that only differ by the value of a single field into one
+//!   `In` constraint, "un-expanding" an `in` or an `or` from the policy,
+//! * reorders the fetches in each result set to fetch the most selective ones first.
+
+use std::collections::{HashMap, HashSet};
+
+use super::{Constraint, ConstraintKind, ConstraintValue, FilterPlan, Ref, ResultSet};
+use crate::formatting::ToPolarString;
+use crate::terms::*;
+
+pub fn opt_pass(filter_plan: &mut FilterPlan) -> bool {
+    let result_sets = &mut filter_plan.result_sets;
+
+    // Remove duplicate result set in a union.
+    let mut drop_plan = None;
+    'plans: for (i, result_set_a) in result_sets.iter().enumerate() {
+        for (j, result_set_b) in result_sets.iter().enumerate() {
+            if i != j && result_set_a == result_set_b {
+                drop_plan = Some(j);
+                break 'plans;
+            }
+        }
+    }
+    if let Some(plan_id) = drop_plan {
+        result_sets.remove(plan_id);
+        return true;
+    }
+
+    // Remove result sets that return a subset of the results of another one.
+    let mut drop_plan = None;
+    'subsumed: for (i, result_set_a) in result_sets.iter().enumerate() {
+        for (j, result_set_b) in result_sets.iter().enumerate() {
+            let (a, b) = (result_set_a, result_set_b);
+            if i != j && subsumes(a, &a.result_id, b, &b.result_id) {
+                drop_plan = Some(j);
+                break 'subsumed;
+            }
+        }
+    }
+    if let Some(plan_id) = drop_plan {
+        result_sets.remove(plan_id);
+        return true;
+    }
+
+    // Merge result sets that differ only by the value of one field.
+    for i in 0..result_sets.len() {
+        for j in i + 1..result_sets.len() {
+            if let Some(merged) = merge(&result_sets[i], &result_sets[j]) {
+                result_sets[i] = merged;
+                result_sets.remove(j);
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+pub fn optimize(mut filter_plan: FilterPlan) -> FilterPlan {
+    for result_set in &mut filter_plan.result_sets {
+        normalize(result_set);
+    }
+    while opt_pass(&mut filter_plan) {}
+    for result_set in &mut filter_plan.result_sets {
+        resolve_order(result_set);
+    }
+    filter_plan
+}
+
+/// A key to sort constraints by, so equivalent requests have equal constraint lists.
+fn constraint_key(constraint: &Constraint) -> (String, String, String) {
+    let value = match &constraint.value {
+        ConstraintValue::Term(term) => format!("TERM({})", term.to_polar()),
+        ConstraintValue::Field(field) => format!("FIELD({})", field),
+        ConstraintValue::Ref(Ref { field, result_id }) => {
+            format!("REF({:?}, {})", field, result_id)
+        }
+    };
+    (
+        constraint.field.clone(),
+        format!("{:?}", constraint.kind),
+        value,
+    )
+}
+
+fn sort_constraints(constraints: &mut Vec<Constraint>) {
+    constraints.sort_by_key(constraint_key);
+    constraints.dedup();
+}
+
+/// Number the fetch requests in resolve order and sort their constraints, so that
+/// result sets built from equivalent partial results compare equal.
+fn normalize(result_set: &mut ResultSet) {
+    let ids: HashMap<String, String> = result_set
+        .resolve_order
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (id.clone(), i.to_string()))
+        .collect();
+    let renumber = |id: &String| ids.get(id).cloned().unwrap_or_else(|| id.clone());
+
+    let mut requests = HashMap::new();
+    for (id, mut request) in result_set.requests.drain() {
+        for constraint in &mut request.constraints {
+            if let ConstraintValue::Ref(Ref { result_id, .. }) = &mut constraint.value {
+                *result_id = renumber(result_id);
+            }
+        }
+        sort_constraints(&mut request.constraints);
+        requests.insert(renumber(&id), request);
+    }
+    result_set.requests = requests;
+    result_set.resolve_order = result_set.resolve_order.iter().map(renumber).collect();
+    result_set.result_id = renumber(&result_set.result_id);
+}
+
+/// The values a field can take under an `Eq` or `In` constraint on a value.
+fn field_values(constraint: &Constraint) -> Option<Vec<Term>> {
+    match (&constraint.kind, &constraint.value) {
+        (ConstraintKind::Eq, ConstraintValue::Term(term)) => Some(vec![term.clone()]),
+        (ConstraintKind::In, ConstraintValue::Term(term)) => match term.value() {
+            Value::List(list) => Some(list.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether every result of request `b_id` in `b` is also a result of request `a_id` in `a`.
+fn subsumes(a: &ResultSet, a_id: &str, b: &ResultSet, b_id: &str) -> bool {
+    let (a_request, b_request) = match (a.requests.get(a_id), b.requests.get(b_id)) {
+        (Some(a_request), Some(b_request)) => (a_request, b_request),
+        _ => return false,
+    };
+    a_request.class_tag == b_request.class_tag
+        && a_request.constraints.iter().all(|a_constraint| {
+            b_request
+                .constraints
+                .iter()
+                .any(|b_constraint| implies(b, b_constraint, a, a_constraint))
+        })
+}
+
+/// Whether constraint `b_constraint` of result set `b` implies `a_constraint` of `a`.
+fn implies(
+    b: &ResultSet,
+    b_constraint: &Constraint,
+    a: &ResultSet,
+    a_constraint: &Constraint,
+) -> bool {
+    if b_constraint.field != a_constraint.field {
+        return false;
+    }
+    if let (Some(b_values), Some(a_values)) =
+        (field_values(b_constraint), field_values(a_constraint))
+    {
+        return b_values.iter().all(|value| a_values.contains(value));
+    }
+    if b_constraint.kind != a_constraint.kind {
+        return false;
+    }
+    match (&b_constraint.value, &a_constraint.value) {
+        (ConstraintValue::Ref(b_ref), ConstraintValue::Ref(a_ref))
+            if b_ref.field == a_ref.field =>
+        {
+            match b_constraint.kind {
+                // Being in fewer results is more restrictive.
+                ConstraintKind::In => subsumes(a, &a_ref.result_id, b, &b_ref.result_id),
+                // Not being in more results is more restrictive.
+                ConstraintKind::Nin => subsumes(b, &b_ref.result_id, a, &a_ref.result_id),
+                _ => false,
+            }
+        }
+        (ConstraintValue::Ref(_), _) | (_, ConstraintValue::Ref(_)) => false,
+        (b_value, a_value) => b_value == a_value,
+    }
+}
+
+/// Whether the results of a result set are a union over the results of fetch request
+/// `id`, i.e. whether a fetch for `x or y` gives the same results as separate fetches
+/// for `x` and `y`. That's the case when every request on the way from `id` to the
+/// result is only used once, by an `In` constraint.
+fn is_distributive(result_set: &ResultSet, id: &str) -> bool {
+    let mut references = result_set.requests.iter().flat_map(|(ref_id, request)| {
+        request
+            .constraints
+            .iter()
+            .filter_map(move |constraint| match &constraint.value {
+                ConstraintValue::Ref(Ref { result_id, .. }) if result_id == id => {
+                    Some((ref_id, &constraint.kind))
+                }
+                _ => None,
+            })
+    });
+    match (references.next(), references.next()) {
+        (None, _) => id == result_set.result_id,
+        (Some((ref_id, ConstraintKind::In)), None) => is_distributive(result_set, ref_id),
+        _ => false,
+    }
+}
+
+/// Merge two result sets that only differ by the values allowed for one field of one
+/// fetch request into a single result set with an `In` constraint on that field.
+fn merge(a: &ResultSet, b: &ResultSet) -> Option<ResultSet> {
+    if a.result_id != b.result_id
+        || a.resolve_order != b.resolve_order
+        || a.requests.len() != b.requests.len()
+    {
+        return None;
+    }
+
+    let mut differing = a
+        .requests
+        .iter()
+        .filter(|(id, request)| b.requests.get(*id) != Some(request));
+    let (id, a_request) = match (differing.next(), differing.next()) {
+        (Some(different), None) => different,
+        _ => return None,
+    };
+    let b_request = b.requests.get(id)?;
+    if a_request.class_tag != b_request.class_tag
+        || a_request.constraints.len() != b_request.constraints.len()
+        || !is_distributive(a, id)
+    {
+        return None;
+    }
+
+    // Both requests are sorted, so the differing constraints line up.
+    let mut differing = a_request
+        .constraints
+        .iter()
+        .zip(&b_request.constraints)
+        .enumerate()
+        .filter(|(_, (a_constraint, b_constraint))| a_constraint != b_constraint);
+    let (index, (a_constraint, b_constraint)) = match (differing.next(), differing.next()) {
+        (Some(different), None) => different,
+        _ => return None,
+    };
+    if a_constraint.field != b_constraint.field {
+        return None;
+    }
+    let mut values = field_values(a_constraint)?;
+    for value in field_values(b_constraint)? {
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+
+    let mut merged = a.clone();
+    let request = merged.requests.get_mut(id)?;
+    request.constraints[index] = Constraint {
+        kind: ConstraintKind::In,
+        field: a_constraint.field.clone(),
+        value: ConstraintValue::Term(Term::new_temporary(Value::List(values))),
+    };
+    sort_constraints(&mut request.constraints);
+    Some(merged)
+}
+
+/// A rough estimate of how few results a fetch request returns: the higher, the fewer.
+fn selectivity(constraints: &[Constraint]) -> usize {
+    constraints
+        .iter()
+        .map(|constraint| match (&constraint.kind, &constraint.value) {
+            (ConstraintKind::Eq, ConstraintValue::Term(_)) => 4,
+            (ConstraintKind::In, _) | (ConstraintKind::Contains, _) => 3,
+            (ConstraintKind::Lt, _)
+            | (ConstraintKind::Leq, _)
+            | (ConstraintKind::Gt, _)
+            | (ConstraintKind::Geq, _) => 2,
+            (ConstraintKind::Eq, _) => 1,
+            (ConstraintKind::Neq, _)
+            | (ConstraintKind::Nin, _)
+            | (ConstraintKind::NotContains, _) => 0,
+        })
+        .sum()
+}
+
+/// Order the fetches so that, of the requests whose dependencies have been fetched,
+/// the most selective one is fetched next. Returns whether the order changed.
+fn resolve_order(result_set: &mut ResultSet) -> bool {
+    let dependencies: HashMap<&String, HashSet<&String>> = result_set
+        .requests
+        .iter()
+        .map(|(id, request)| {
+            let refs = request
+                .constraints
+                .iter()
+                .filter_map(|constraint| match &constraint.value {
+                    ConstraintValue::Ref(Ref { result_id, .. }) => Some(result_id),
+                    _ => None,
+                })
+                .collect();
+            (id, refs)
+        })
+        .collect();
+
+    let mut remaining = result_set.resolve_order.clone();
+    let mut order: Vec<String> = vec![];
+    while !remaining.is_empty() {
+        let next = remaining
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| match dependencies.get(id) {
+                Some(deps) => deps.iter().all(|dep| order.contains(dep)),
+                None => true,
+            })
+            // Prefer the earliest of equally selective requests.
+            .max_by_key(|(i, id)| {
+                let constraints = result_set
+                    .requests
+                    .get(*id)
+                    .map(|request| &request.constraints[..])
+                    .unwrap_or_default();
+                (selectivity(constraints), std::cmp::Reverse(*i))
+            })
+            .map(|(i, _)| i);
+        match next {
+            Some(i) => order.push(remaining.remove(i)),
+            // A dependency cycle or a missing request; leave the rest as they were.
+            None => order.append(&mut remaining),
+        }
+    }
+
+    let changed = order != result_set.resolve_order;
+    result_set.resolve_order = order;
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::{filter_plan, partial_results, types};
+    use super::super::{build_result_sets, FetchRequest};
+    use super::*;
+    use crate::error::PolarResult;
+    use crate::polar::Polar;
+
+    /// The number of result sets and fetch requests in a plan.
+    fn plan_size(plan: &FilterPlan) -> (usize, usize) {
+        let requests = plan.result_sets.iter().map(|rs| rs.requests.len()).sum();
+        (plan.result_sets.len(), requests)
+    }
+
+    /// The unoptimized and optimized plans for the resources of `class_tag` allowed by `policy`.
+    fn plans(policy: &str, class_tag: &str) -> PolarResult<(FilterPlan, FilterPlan)> {
+        let p = Polar::new();
+        p.load_str(policy)?;
+        let results = partial_results(&p, class_tag)?;
+        let raw = build_result_sets(&types(), &results, "resource", class_tag)?;
+        Ok((raw, filter_plan(policy, class_tag)?))
+    }
+
+    fn constraint(kind: ConstraintKind, field: &str, value: ConstraintValue) -> Constraint {
+        Constraint {
+            kind,
+            field: field.to_owned(),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_remove_duplicates() -> PolarResult<()> {
+        let (raw, optimized) = plans(
+            r#"allow("steve", "get", resource: Foo) if resource.is_fooey = true;
+               allow("steve", "get", resource: Foo) if resource.is_fooey = true;"#,
+            "Foo",
+        )?;
+        assert_eq!(plan_size(&raw), (2, 2));
+        assert_eq!(plan_size(&optimized), (1, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_subsumed() -> PolarResult<()> {
+        let (raw, optimized) = plans(
+            r#"allow("steve", "get", resource: Foo) if
+                   resource.is_fooey = true and resource.id = "a";
+               allow("steve", "get", resource: Foo) if
+                   resource.is_fooey = true;"#,
+            "Foo",
+        )?;
+        assert_eq!(plan_size(&raw), (2, 2));
+        assert_eq!(plan_size(&optimized), (1, 1));
+        assert_eq!(
+            optimized.result_sets[0].requests["0"].constraints,
+            vec![constraint(
+                ConstraintKind::Eq,
+                "is_fooey",
+                ConstraintValue::Term(term!(true))
+            )]
+        );
+
+        // Related to more bars is less restrictive.
+        let (raw, optimized) = plans(
+            r#"allow("steve", "get", resource: Foo) if
+                   resource.bar.is_cool = true;
+               allow("steve", "get", resource: Foo) if
+                   resource.bar.is_cool = true and resource.bar.id = "hello";"#,
+            "Foo",
+        )?;
+        assert_eq!(plan_size(&raw), (2, 4));
+        assert_eq!(plan_size(&optimized), (1, 2));
+        assert_eq!(optimized.result_sets[0].requests["0"].constraints.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_into_in() -> PolarResult<()> {
+        let (raw, optimized) = plans(
+            r#"allow("steve", "get", resource: Foo) if
+                   resource.is_fooey = true and resource.id in ["a", "b", "c"];"#,
+            "Foo",
+        )?;
+        assert_eq!(plan_size(&raw), (3, 3));
+        assert_eq!(plan_size(&optimized), (1, 1));
+        let constraints = &optimized.result_sets[0].requests["0"].constraints;
+        assert_eq!(constraints.len(), 2);
+        assert!(constraints.contains(&constraint(
+            ConstraintKind::In,
+            "id",
+            ConstraintValue::Term(term!(["a", "b", "c"]))
+        )));
+
+        // Fetches of related objects merge too.
+        let (raw, optimized) = plans(
+            r#"allow("steve", "get", resource: Foo) if
+                   resource.bar.id = "hello" or resource.bar.id = "goodbye";"#,
+            "Foo",
+        )?;
+        assert_eq!(plan_size(&raw), (2, 4));
+        assert_eq!(plan_size(&optimized), (1, 2));
+        let result_set = &optimized.result_sets[0];
+        assert_eq!(
+            result_set.requests[&result_set.resolve_order[0]].constraints,
+            vec![constraint(
+                ConstraintKind::In,
+                "id",
+                ConstraintValue::Term(term!(["hello", "goodbye"]))
+            )]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_merge_across_fields() -> PolarResult<()> {
+        let (raw, optimized) = plans(
+            r#"allow("steve", "get", resource: Foo) if
+                   resource.id = "a" or resource.bar_id = "b";"#,
+            "Foo",
+        )?;
+        assert_eq!(plan_size(&raw), plan_size(&optimized));
+
+        // Foos without logs of either value aren't the same as foos without logs of both.
+        let anti_join = |data: &str| ResultSet {
+            requests: hashmap! {
+                "0".to_owned() => FetchRequest {
+                    class_tag: "FooLogRecord".to_owned(),
+                    constraints: vec![constraint(
+                        ConstraintKind::Eq,
+                        "data",
+                        ConstraintValue::Term(term!(data)),
+                    )],
+                },
+                "1".to_owned() => FetchRequest {
+                    class_tag: "Foo".to_owned(),
+                    constraints: vec![constraint(
+                        ConstraintKind::Nin,
+                        "id",
+                        ConstraintValue::Ref(Ref {
+                            field: Some("foo_id".to_owned()),
+                            result_id: "0".to_owned(),
+                        }),
+                    )],
+                },
+            },
+            resolve_order: vec!["0".to_owned(), "1".to_owned()],
+            result_id: "1".to_owned(),
+        };
+        let raw = FilterPlan {
+            result_sets: vec![anti_join("a"), anti_join("b")],
+        };
+        let optimized = optimize(raw.clone());
+        assert_eq!(plan_size(&raw), plan_size(&optimized));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_order() {
+        let mut result_set = ResultSet {
+            requests: hashmap! {
+                "0".to_owned() => FetchRequest {
+                    class_tag: "Bar".to_owned(),
+                    constraints: vec![constraint(
+                        ConstraintKind::Neq,
+                        "id",
+                        ConstraintValue::Term(term!("hello")),
+                    )],
+                },
+                "1".to_owned() => FetchRequest {
+                    class_tag: "FooLogRecord".to_owned(),
+                    constraints: vec![constraint(
+                        ConstraintKind::Eq,
+                        "data",
+                        ConstraintValue::Term(term!("hello")),
+                    )],
+                },
+                "2".to_owned() => FetchRequest {
+                    class_tag: "Foo".to_owned(),
+                    constraints: vec![
+                        constraint(ConstraintKind::In, "bar_id", ConstraintValue::Ref(Ref {
+                            field: Some("id".to_owned()),
+                            result_id: "0".to_owned(),
+                        })),
+                        constraint(ConstraintKind::In, "id", ConstraintValue::Ref(Ref {
+                            field: Some("foo_id".to_owned()),
+                            result_id: "1".to_owned(),
+                        })),
+                    ],
+                },
+            },
+            resolve_order: vec!["0".to_owned(), "1".to_owned(), "2".to_owned()],
+            result_id: "2".to_owned(),
+        };
+        assert!(resolve_order(&mut result_set));
+        assert_eq!(result_set.resolve_order, vec!["1", "0", "2"]);
+        assert!(!resolve_order(&mut result_set));
+    }
+}
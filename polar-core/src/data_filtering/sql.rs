@@ -0,0 +1,756 @@
+//! Compile a `FilterPlan` to a parameterised SQL query.
+//!
+//! Each `ResultSet` becomes a `SELECT` over the table of its result fetch request. Fetch
+//! requests referenced with a `Ref` constraint are `JOIN`ed in, `In` constraints on values
+//! become `IN` lists, and multiple result sets are combined with `UNION`. Values are never
+//! inlined: every one is replaced by a `?` placeholder and returned in `SqlQuery::params`.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Constraint, ConstraintKind, ConstraintValue, FetchRequest, FilterPlan, Ref, ResultSet,
+};
+use crate::error::{PolarResult, RuntimeError};
+use crate::formatting::ToPolarString;
+use crate::terms::*;
+
+/// How a class is stored in the database.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Table {
+    /// The name of the table.
+    pub name: String,
+    /// Map from field names to column names. Fields without an entry are
+    /// stored in a column with the same name.
+    #[serde(default)]
+    pub columns: HashMap<String, String>,
+}
+
+impl Table {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            columns: HashMap::new(),
+        }
+    }
+
+    /// Store `field` in the column `column`.
+    pub fn column(mut self, field: &str, column: &str) -> Self {
+        self.columns.insert(field.to_owned(), column.to_owned());
+        self
+    }
+
+    fn column_for(&self, field: &str) -> String {
+        quote(self.columns.get(field).map(String::as_str).unwrap_or(field))
+    }
+}
+
+/// Map from class tags to the tables they are stored in.
+pub type Tables = HashMap<String, Table>;
+
+/// A SQL query with `?` placeholders for its parameters.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SqlQuery {
+    pub sql: String,
+    /// The values of the placeholders, in order. Always strings, numbers or booleans.
+    pub params: Vec<Term>,
+}
+
+fn unsupported<T>(msg: String) -> PolarResult<T> {
+    Err(RuntimeError::Unsupported { msg }.into())
+}
+
+/// The SQL operator for a comparison constraint.
+fn comparison_operator(kind: &ConstraintKind) -> &'static str {
+    match kind {
+        ConstraintKind::Eq => "=",
+        ConstraintKind::Neq => "<>",
+        ConstraintKind::Lt => "<",
+        ConstraintKind::Leq => "<=",
+        ConstraintKind::Gt => ">",
+        ConstraintKind::Geq => ">=",
+        ConstraintKind::In
+        | ConstraintKind::Contains
+        | ConstraintKind::Nin
+        | ConstraintKind::NotContains => unreachable!("not a comparison"),
+    }
+}
+
+/// Quote an identifier.
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn table_for<'a>(tables: &'a Tables, class_tag: &str) -> PolarResult<&'a Table> {
+    match tables.get(class_tag) {
+        Some(table) => Ok(table),
+        None => unsupported(format!(
+            "no table for class {} in SQL filter plan",
+            class_tag
+        )),
+    }
+}
+
+/// Builds the `SELECT` for a single result set.
+struct SelectBuilder<'a> {
+    tables: &'a Tables,
+    result_set: &'a ResultSet,
+    /// Table alias of each fetch request that is in scope.
+    aliases: HashMap<&'a str, String>,
+    /// Number of aliases made so far, in any scope.
+    alias_count: usize,
+    joins: Vec<String>,
+    conditions: Vec<String>,
+    params: Vec<Term>,
+}
+
+impl<'a> SelectBuilder<'a> {
+    fn new(tables: &'a Tables, result_set: &'a ResultSet) -> Self {
+        Self {
+            tables,
+            result_set,
+            aliases: HashMap::new(),
+            alias_count: 0,
+            joins: vec![],
+            conditions: vec![],
+            params: vec![],
+        }
+    }
+
+    fn request(&self, id: &str) -> PolarResult<&'a FetchRequest> {
+        match self.result_set.requests.get(id) {
+            Some(request) => Ok(request),
+            None => Err(RuntimeError::Unsupported {
+                msg: format!("filter plan references unknown fetch request {}", id),
+            }
+            .into()),
+        }
+    }
+
+    fn alias(&mut self, id: &'a str) -> String {
+        let alias = format!("t{}", self.alias_count);
+        self.alias_count += 1;
+        self.aliases.insert(id, alias.clone());
+        alias
+    }
+
+    fn param(&mut self, term: &Term) -> PolarResult<String> {
+        match term.value() {
+            Value::String(_) | Value::Number(_) | Value::Boolean(_) => {
+                self.params.push(term.clone());
+                Ok("?".to_owned())
+            }
+            _ => unsupported(format!(
+                "{} can't be used as a SQL parameter",
+                term.to_polar()
+            )),
+        }
+    }
+
+    fn build(mut self) -> PolarResult<(String, Vec<Term>)> {
+        let result_id = self.result_set.result_id.as_str();
+        let request = self.request(result_id)?;
+        let table = table_for(self.tables, &request.class_tag)?;
+        let alias = self.alias(result_id);
+        self.add_constraints(&alias, table, request)?;
+
+        let mut sql = format!(
+            "SELECT DISTINCT {alias}.* FROM {table} AS {alias}",
+            alias = alias,
+            table = quote(&table.name)
+        );
+        for join in &self.joins {
+            sql.push(' ');
+            sql.push_str(join);
+        }
+        if !self.conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.conditions.join(" AND "));
+        }
+        Ok((sql, self.params))
+    }
+
+    fn add_constraints(
+        &mut self,
+        alias: &str,
+        table: &Table,
+        request: &'a FetchRequest,
+    ) -> PolarResult<()> {
+        for constraint in &request.constraints {
+            self.add_constraint(alias, table, constraint)?;
+        }
+        Ok(())
+    }
+
+    fn add_constraint(
+        &mut self,
+        alias: &str,
+        table: &Table,
+        constraint: &'a Constraint,
+    ) -> PolarResult<()> {
+        let column = format!("{}.{}", alias, table.column_for(&constraint.field));
+        match (&constraint.kind, &constraint.value) {
+            (ConstraintKind::In, ConstraintValue::Term(term)) => {
+                let values = match term.value() {
+                    Value::List(values) => values,
+                    _ => {
+                        return unsupported(format!(
+                            "`in` constraint on {} with a non-list value {}",
+                            constraint.field,
+                            term.to_polar()
+                        ))
+                    }
+                };
+                if values.is_empty() {
+                    // `IN ()` is not valid SQL, and nothing is in an empty list.
+                    self.conditions.push("1 = 0".to_owned());
+                } else {
+                    let params = values
+                        .iter()
+                        .map(|value| self.param(value))
+                        .collect::<PolarResult<Vec<_>>>()?;
+                    self.conditions
+                        .push(format!("{} IN ({})", column, params.join(", ")));
+                }
+            }
+            (
+                ConstraintKind::Eq,
+                ConstraintValue::Ref(Ref {
+                    field: Some(other_field),
+                    result_id,
+                }),
+            )
+            | (
+                ConstraintKind::In,
+                ConstraintValue::Ref(Ref {
+                    field: Some(other_field),
+                    result_id,
+                }),
+            ) => {
+                let other = self.request(result_id)?;
+                let other_table = table_for(self.tables, &other.class_tag)?;
+                let other_column = other_table.column_for(other_field);
+                if let Some(other_alias) = self.aliases.get(result_id.as_str()) {
+                    // Already joined in through another reference.
+                    self.conditions
+                        .push(format!("{} = {}.{}", column, other_alias, other_column));
+                } else {
+                    let other_alias = self.alias(result_id);
+                    self.joins.push(format!(
+                        "JOIN {table} AS {other_alias} ON {column} = {other_alias}.{other_column}",
+                        table = quote(&other_table.name),
+                        other_alias = other_alias,
+                        column = column,
+                        other_column = other_column,
+                    ));
+                    self.add_constraints(&other_alias, other_table, other)?;
+                }
+            }
+            (ConstraintKind::Nin, ConstraintValue::Term(term)) => {
+                let values = match term.value() {
+                    Value::List(values) => values,
+                    _ => {
+                        return unsupported(format!(
+                            "`not in` constraint on {} with a non-list value {}",
+                            constraint.field,
+                            term.to_polar()
+                        ))
+                    }
+                };
+                // Everything is not in an empty list.
+                if !values.is_empty() {
+                    let params = values
+                        .iter()
+                        .map(|value| self.param(value))
+                        .collect::<PolarResult<Vec<_>>>()?;
+                    self.conditions
+                        .push(format!("{} NOT IN ({})", column, params.join(", ")));
+                }
+            }
+            (
+                ConstraintKind::Nin,
+                ConstraintValue::Ref(Ref {
+                    field: Some(other_field),
+                    result_id,
+                }),
+            ) => {
+                // Anti-join: there's no matching row in the other table. The tables of
+                // the subquery are only in scope inside it.
+                let other = self.request(result_id)?;
+                let other_table = table_for(self.tables, &other.class_tag)?;
+                let aliases = self.aliases.clone();
+                let other_alias = self.alias(result_id);
+                let joins = std::mem::take(&mut self.joins);
+                let mut conditions = std::mem::replace(
+                    &mut self.conditions,
+                    vec![format!(
+                        "{}.{} = {}",
+                        other_alias,
+                        other_table.column_for(other_field),
+                        column
+                    )],
+                );
+                self.add_constraints(&other_alias, other_table, other)?;
+                let mut subquery = format!(
+                    "SELECT 1 FROM {} AS {}",
+                    quote(&other_table.name),
+                    other_alias
+                );
+                for join in &self.joins {
+                    subquery.push(' ');
+                    subquery.push_str(join);
+                }
+                subquery.push_str(" WHERE ");
+                subquery.push_str(&self.conditions.join(" AND "));
+                conditions.push(format!("NOT EXISTS ({})", subquery));
+                self.aliases = aliases;
+                self.joins = joins;
+                self.conditions = conditions;
+            }
+            (ConstraintKind::Eq, ConstraintValue::Ref(Ref { field: None, .. }))
+            | (ConstraintKind::In, ConstraintValue::Ref(Ref { field: None, .. }))
+            | (ConstraintKind::Nin, ConstraintValue::Ref(Ref { field: None, .. })) => {
+                return unsupported(format!(
+                    "comparing {} to whole objects is not supported in SQL",
+                    constraint.field
+                ));
+            }
+            (ConstraintKind::In, ConstraintValue::Field(field))
+            | (ConstraintKind::Nin, ConstraintValue::Field(field)) => {
+                return unsupported(format!(
+                    "`{} in {}` is not supported in SQL",
+                    constraint.field, field
+                ));
+            }
+            (ConstraintKind::Contains, _) | (ConstraintKind::NotContains, _) => {
+                return unsupported(format!(
+                    "collection field {} is not supported in SQL",
+                    constraint.field
+                ));
+            }
+            (kind, ConstraintValue::Ref(_)) => {
+                return unsupported(format!(
+                    "`{}` comparisons of {} to other results are not supported in SQL",
+                    comparison_operator(kind),
+                    constraint.field
+                ));
+            }
+            (kind, ConstraintValue::Term(term)) => {
+                let param = self.param(term)?;
+                self.conditions.push(format!(
+                    "{} {} {}",
+                    column,
+                    comparison_operator(kind),
+                    param
+                ));
+            }
+            (kind, ConstraintValue::Field(field)) => {
+                self.conditions.push(format!(
+                    "{} {} {}.{}",
+                    column,
+                    comparison_operator(kind),
+                    alias,
+                    table.column_for(field)
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compile `filter_plan` into a SQL query returning the rows of the table of `class_tag` that
+/// the plan selects.
+pub fn to_sql(filter_plan: &FilterPlan, tables: &Tables, class_tag: &str) -> PolarResult<SqlQuery> {
+    if filter_plan.result_sets.is_empty() {
+        // Nothing is allowed.
+        let table = table_for(tables, class_tag)?;
+        return Ok(SqlQuery {
+            sql: format!("SELECT * FROM {} WHERE 1 = 0", quote(&table.name)),
+            params: vec![],
+        });
+    }
+
+    let mut selects = vec![];
+    let mut params = vec![];
+    let mut seen = HashSet::new();
+    for result_set in &filter_plan.result_sets {
+        let (select, select_params) = SelectBuilder::new(tables, result_set).build()?;
+        // Identical selects would be removed by the `UNION` anyway.
+        if seen.insert((select.clone(), select_params.clone())) {
+            selects.push(select);
+            params.extend(select_params);
+        }
+    }
+
+    Ok(SqlQuery {
+        sql: selects.join(" UNION "),
+        params,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::test::filter_plan;
+    use super::*;
+
+    use rusqlite::{params, types::Value as SqlValue, Connection};
+
+    fn tables() -> Tables {
+        hashmap! {
+            "Foo".to_owned() => Table::new("foos").column("is_fooey", "fooey"),
+            "Bar".to_owned() => Table::new("bars"),
+            "FooLogRecord".to_owned() => Table::new("foo_logs"),
+            "FooTag".to_owned() => Table::new("foo_tags"),
+            "Tag".to_owned() => Table::new("tags"),
+        }
+    }
+
+    fn db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE bars (id TEXT PRIMARY KEY, is_cool BOOLEAN, is_still_cool BOOLEAN);
+               CREATE TABLE foos (id TEXT PRIMARY KEY, bar_id TEXT REFERENCES bars(id), fooey BOOLEAN);
+               CREATE TABLE foo_logs (id TEXT PRIMARY KEY, foo_id TEXT REFERENCES foos(id), data TEXT);
+               CREATE TABLE tags (id TEXT PRIMARY KEY, name TEXT);
+               CREATE TABLE foo_tags (foo_id TEXT REFERENCES foos(id), tag_id TEXT REFERENCES tags(id));"#,
+        )
+        .unwrap();
+        for (id, is_cool, is_still_cool) in &[
+            ("hello", true, true),
+            ("goodbye", false, true),
+            ("hershey", false, false),
+        ] {
+            conn.execute(
+                "INSERT INTO bars VALUES (?, ?, ?)",
+                params![id, is_cool, is_still_cool],
+            )
+            .unwrap();
+        }
+        for (id, bar_id, fooey) in &[
+            ("something", "hello", false),
+            ("another", "hello", true),
+            ("third", "hello", true),
+            ("fourth", "goodbye", true),
+        ] {
+            conn.execute(
+                "INSERT INTO foos VALUES (?, ?, ?)",
+                params![id, bar_id, fooey],
+            )
+            .unwrap();
+        }
+        for (id, foo_id, data) in &[
+            ("a", "fourth", "hello"),
+            ("b", "third", "world"),
+            ("c", "another", "steve"),
+            ("d", "fourth", "steve"),
+        ] {
+            conn.execute(
+                "INSERT INTO foo_logs VALUES (?, ?, ?)",
+                params![id, foo_id, data],
+            )
+            .unwrap();
+        }
+        for (id, name) in &[("t1", "cool"), ("t2", "boring")] {
+            conn.execute("INSERT INTO tags VALUES (?, ?)", params![id, name])
+                .unwrap();
+        }
+        for (foo_id, tag_id) in &[
+            ("another", "t1"),
+            ("third", "t2"),
+            ("fourth", "t1"),
+            ("fourth", "t2"),
+        ] {
+            conn.execute(
+                "INSERT INTO foo_tags VALUES (?, ?)",
+                params![foo_id, tag_id],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn sql_value(term: &Term) -> SqlValue {
+        match term.value() {
+            Value::String(s) => SqlValue::Text(s.clone()),
+            Value::Number(Numeric::Integer(i)) => SqlValue::Integer(*i),
+            Value::Number(Numeric::Float(f)) => SqlValue::Real(*f),
+            Value::Boolean(b) => SqlValue::Integer(*b as i64),
+            _ => panic!("not a SQL value: {}", term.to_polar()),
+        }
+    }
+
+    /// Run the query and return the sorted ids of the selected rows.
+    fn run(query: &SqlQuery) -> Vec<String> {
+        let conn = db();
+        let mut stmt = conn.prepare(&query.sql).unwrap();
+        let params = query.params.iter().map(sql_value).collect::<Vec<_>>();
+        let mut ids = stmt
+            .query_map(&params, |row| row.get::<_, String>("id"))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        ids.sort();
+        ids
+    }
+
+    fn query(policy: &str, class_tag: &str) -> PolarResult<SqlQuery> {
+        to_sql(&filter_plan(policy, class_tag)?, &tables(), class_tag)
+    }
+
+    #[test]
+    fn test_sql_no_relationships() -> PolarResult<()> {
+        let query = query(
+            r#"allow("steve", "get", resource: Foo) if resource.is_fooey = true;"#,
+            "Foo",
+        )?;
+        assert_eq!(
+            query.sql,
+            r#"SELECT DISTINCT t0.* FROM "foos" AS t0 WHERE t0."fooey" = ?"#
+        );
+        assert_eq!(query.params, vec![term!(true)]);
+        assert_eq!(run(&query), vec!["another", "fourth", "third"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_relationship_join() -> PolarResult<()> {
+        let query = query(
+            r#"allow("steve", "get", resource: Foo) if
+                   resource.bar = bar and
+                   bar.is_cool = true and
+                   resource.is_fooey = true;"#,
+            "Foo",
+        )?;
+        assert!(query
+            .sql
+            .contains(r#"JOIN "bars" AS t1 ON t0."bar_id" = t1."id""#));
+        assert_eq!(run(&query), vec!["another", "third"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_children_join() -> PolarResult<()> {
+        let query = query(
+            r#"allow("steve", "get", resource: Foo) if
+                   log in resource.logs and
+                   log.data = "steve";"#,
+            "Foo",
+        )?;
+        assert!(query
+            .sql
+            .contains(r#"JOIN "foo_logs" AS t1 ON t0."id" = t1."foo_id""#));
+        assert_eq!(run(&query), vec!["another", "fourth"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_many_to_many_join() -> PolarResult<()> {
+        let join = query(
+            r#"allow("steve", "get", resource: Foo) if
+                   tag in resource.tags and
+                   tag.name = "cool";"#,
+            "Foo",
+        )?;
+        assert!(join
+            .sql
+            .contains(r#"JOIN "foo_tags" AS t1 ON t0."id" = t1."foo_id""#));
+        assert!(join
+            .sql
+            .contains(r#"JOIN "tags" AS t2 ON t1."tag_id" = t2."id""#));
+        assert_eq!(run(&join), vec!["another", "fourth"]);
+
+        let anti_join = query(
+            r#"allow("steve", "get", resource: Foo) if
+                   not _ in resource.tags;"#,
+            "Foo",
+        )?;
+        assert_eq!(run(&anti_join), vec!["something"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_field_cmp_field() -> PolarResult<()> {
+        let query = query(
+            r#"allow("steve", "get", bar: Bar) if bar.is_cool = bar.is_still_cool;"#,
+            "Bar",
+        )?;
+        assert!(query.sql.contains(r#"t0."is_cool" = t0."is_still_cool""#));
+        assert_eq!(run(&query), vec!["hello", "hershey"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_comparisons() -> PolarResult<()> {
+        let values = query(
+            r#"allow("steve", "get", resource: Foo) if
+                   resource.id > "f" and resource.bar_id != "goodbye";"#,
+            "Foo",
+        )?;
+        assert!(values.sql.contains(r#"t0."id" > ?"#));
+        assert!(values.sql.contains(r#"t0."bar_id" <> ?"#));
+        assert_eq!(run(&values), vec!["something", "third"]);
+
+        let fields = query(
+            r#"allow("steve", "get", resource: Foo) if resource.id <= resource.bar_id;"#,
+            "Foo",
+        )?;
+        assert!(fields.sql.contains(r#"t0."id" <= t0."bar_id""#));
+        assert_eq!(run(&fields), vec!["another", "fourth"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_not() -> PolarResult<()> {
+        let not_in = query(
+            r#"allow("steve", "get", resource: Foo) if not resource.id in ["another", "third"];"#,
+            "Foo",
+        )?;
+        assert_eq!(run(&not_in), vec!["fourth", "something"]);
+
+        let anti_join = query(
+            r#"allow("steve", "get", resource: Foo) if
+                   not _ in resource.logs;"#,
+            "Foo",
+        )?;
+        assert!(anti_join.sql.contains(
+            r#"NOT EXISTS (SELECT 1 FROM "foo_logs" AS t1 WHERE t1."foo_id" = t0."id")"#
+        ));
+        assert_eq!(run(&anti_join), vec!["something"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_nin_values() -> PolarResult<()> {
+        let plan = FilterPlan {
+            result_sets: vec![ResultSet {
+                requests: hashmap! {
+                    "0".to_owned() => FetchRequest {
+                        class_tag: "Foo".to_owned(),
+                        constraints: vec![Constraint {
+                            kind: ConstraintKind::Nin,
+                            field: "bar_id".to_owned(),
+                            value: ConstraintValue::Term(term!(["hello"])),
+                        }],
+                    },
+                },
+                resolve_order: vec!["0".to_owned()],
+                result_id: "0".to_owned(),
+            }],
+        };
+        let query = to_sql(&plan, &tables(), "Foo")?;
+        assert_eq!(
+            query.sql,
+            r#"SELECT DISTINCT t0.* FROM "foos" AS t0 WHERE t0."bar_id" NOT IN (?)"#
+        );
+        assert_eq!(run(&query), vec!["fourth"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_nin_and_ref_to_same_request() -> PolarResult<()> {
+        // Foos that aren't fooey, but share a bar with a fooey foo.
+        let ref_to = |field: &str| {
+            ConstraintValue::Ref(Ref {
+                field: Some(field.to_owned()),
+                result_id: "1".to_owned(),
+            })
+        };
+        let plan = FilterPlan {
+            result_sets: vec![ResultSet {
+                requests: hashmap! {
+                    "0".to_owned() => FetchRequest {
+                        class_tag: "Foo".to_owned(),
+                        constraints: vec![
+                            Constraint {
+                                kind: ConstraintKind::Nin,
+                                field: "id".to_owned(),
+                                value: ref_to("id"),
+                            },
+                            Constraint {
+                                kind: ConstraintKind::In,
+                                field: "bar_id".to_owned(),
+                                value: ref_to("bar_id"),
+                            },
+                        ],
+                    },
+                    "1".to_owned() => FetchRequest {
+                        class_tag: "Foo".to_owned(),
+                        constraints: vec![Constraint {
+                            kind: ConstraintKind::Eq,
+                            field: "is_fooey".to_owned(),
+                            value: ConstraintValue::Term(term!(true)),
+                        }],
+                    },
+                },
+                resolve_order: vec!["1".to_owned(), "0".to_owned()],
+                result_id: "0".to_owned(),
+            }],
+        };
+        let query = to_sql(&plan, &tables(), "Foo")?;
+        assert_eq!(
+            query.sql,
+            r#"SELECT DISTINCT t0.* FROM "foos" AS t0 JOIN "foos" AS t2 ON t0."bar_id" = t2."bar_id" WHERE NOT EXISTS (SELECT 1 FROM "foos" AS t1 WHERE t1."id" = t0."id" AND t1."fooey" = ?) AND t2."fooey" = ?"#
+        );
+        assert_eq!(run(&query), vec!["something"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_union() -> PolarResult<()> {
+        let query = query(
+            r#"allow("steve", "get", resource: FooLogRecord) if resource.data = "hello";
+               allow("steve", "get", resource: FooLogRecord) if resource.foo_id = "third";"#,
+            "FooLogRecord",
+        )?;
+        assert_eq!(query.sql.matches(" UNION ").count(), 1);
+        assert_eq!(query.params, vec![term!("hello"), term!("third")]);
+        assert_eq!(run(&query), vec!["a", "b"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_in_values() -> PolarResult<()> {
+        let plan = FilterPlan {
+            result_sets: vec![ResultSet {
+                requests: hashmap! {
+                    "0".to_owned() => FetchRequest {
+                        class_tag: "FooLogRecord".to_owned(),
+                        constraints: vec![Constraint {
+                            kind: ConstraintKind::In,
+                            field: "data".to_owned(),
+                            value: ConstraintValue::Term(term!(["hello", "world"])),
+                        }],
+                    }
+                },
+                resolve_order: vec!["0".to_owned()],
+                result_id: "0".to_owned(),
+            }],
+        };
+        let query = to_sql(&plan, &tables(), "FooLogRecord")?;
+        assert_eq!(
+            query.sql,
+            r#"SELECT DISTINCT t0.* FROM "foo_logs" AS t0 WHERE t0."data" IN (?, ?)"#
+        );
+        assert_eq!(run(&query), vec!["a", "b"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_nothing_allowed() -> PolarResult<()> {
+        let query = query(r#"allow("gwen", "get", _resource: Foo);"#, "Foo")?;
+        assert_eq!(query.sql, r#"SELECT * FROM "foos" WHERE 1 = 0"#);
+        assert!(run(&query).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sql_unsupported_contains() {
+        let err = query(
+            r#"allow("steve", "get", resource: Foo) if 1 in resource.numbers;"#,
+            "Foo",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("not supported in SQL"));
+    }
+}
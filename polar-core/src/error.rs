@@ -35,6 +35,7 @@ pub enum ErrorKind {
     Parameter(ParameterError),
     RolesValidation(RolesValidationError),
     Validation(ValidationError),
+    DataFiltering(DataFilteringError),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -135,6 +136,15 @@ impl From<ValidationError> for PolarError {
     }
 }
 
+impl From<DataFilteringError> for PolarError {
+    fn from(err: DataFilteringError) -> Self {
+        Self {
+            kind: ErrorKind::DataFiltering(err),
+            context: None,
+        }
+    }
+}
+
 pub type PolarResult<T> = std::result::Result<T, PolarError>;
 
 impl std::error::Error for PolarError {}
@@ -148,6 +158,7 @@ impl fmt::Display for PolarError {
             ErrorKind::Parameter(e) => write!(f, "{}", e)?,
             ErrorKind::RolesValidation(e) => write!(f, "{}", e)?,
             ErrorKind::Validation(e) => write!(f, "{}", e)?,
+            ErrorKind::DataFiltering(e) => write!(f, "{}", e)?,
         }
         if let Some(ref context) = self.context {
             write!(f, "{}", context)?;
@@ -392,6 +403,8 @@ impl fmt::Display for RolesValidationError {
 pub enum ValidationError {
     InvalidRule { rule: String, msg: String },
     InvalidPrototype { prototype: String, msg: String },
+    UnresolvedImport { module: String },
+    InvalidModule { module: String, msg: String },
     // TODO: add SingletonVariable, RolesValidationError and Macro errors here
 }
 
@@ -404,6 +417,42 @@ impl fmt::Display for ValidationError {
             Self::InvalidPrototype { prototype, msg } => {
                 write!(f, "Invalid prototype: {} {}", prototype, msg)
             }
+            Self::UnresolvedImport { module } => {
+                write!(f, "Unresolved import: no module named {} is loaded", module)
+            }
+            Self::InvalidModule { module, msg } => {
+                write!(f, "Invalid module {}: {}", module, msg)
+            }
+        }
+    }
+}
+
+/// A policy that can't be turned into a filter plan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataFilteringError {
+    pub msg: String,
+    /// The term that couldn't be handled, if there is one.
+    pub term: Option<Term>,
+    /// The location of `term` in its source, if it came from a policy.
+    pub span: Option<(usize, usize)>,
+}
+
+impl DataFilteringError {
+    pub fn new(msg: &str, term: Option<&Term>) -> Self {
+        Self {
+            msg: msg.to_owned(),
+            term: term.cloned(),
+            span: term.and_then(Term::span),
         }
     }
 }
+
+impl fmt::Display for DataFilteringError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Data filtering error: {}", self.msg)?;
+        if let Some(ref term) = self.term {
+            write!(f, ": {}", term)?;
+        }
+        Ok(())
+    }
+}
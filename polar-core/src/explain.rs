@@ -0,0 +1,230 @@
+//! Explanations of query results.
+//!
+//! A query that succeeded is explained by a proof tree built from its
+//! `Trace`. A call to a rule that failed is explained rule by rule: the host
+//! runs the `RuleGoalQueries` for each rule, in order, and the first one that
+//! fails shows how far the rule got.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use super::kb::KnowledgeBase;
+use super::rules::Rule;
+use super::sources::SourceInfo;
+use super::terms::*;
+use super::traces::{Node, Trace};
+
+/// Where a term or rule was loaded from.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub filename: Option<String>,
+    /// 1-based.
+    pub row: usize,
+    /// 0-based.
+    pub column: usize,
+}
+
+impl SourceLocation {
+    fn new(kb: &KnowledgeBase, src_id: u64, offset: usize) -> Option<Self> {
+        let source = kb.sources.get_source(src_id)?;
+        let (row, column) = crate::lexer::loc_to_pos(&source.src, offset);
+        Some(Self {
+            filename: source.filename,
+            row: row + 1,
+            column,
+        })
+    }
+
+    fn of_term(kb: &KnowledgeBase, term: &Term) -> Option<Self> {
+        Self::new(kb, term.get_source_id()?, term.offset())
+    }
+
+    fn of_rule(kb: &KnowledgeBase, rule: &Rule) -> Option<Self> {
+        match rule.source_info {
+            SourceInfo::Parser { src_id, left, .. } => Self::new(kb, src_id, left),
+            _ => None,
+        }
+    }
+}
+
+/// A rule applied or goal satisfied in a successful query, with the rules and
+/// goals that satisfied it.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProofNode {
+    pub source: String,
+    pub location: Option<SourceLocation>,
+    pub children: Vec<ProofNode>,
+}
+
+impl ProofNode {
+    /// Build a proof tree from a trace, leaving out conjunctions in favor of
+    /// their conjuncts and attribute lookups in favor of the goals that use
+    /// them.
+    pub fn from_trace(kb: &KnowledgeBase, trace: &Trace) -> Vec<Self> {
+        let children = || {
+            trace
+                .children
+                .iter()
+                .flat_map(|child| Self::from_trace(kb, child))
+                .collect()
+        };
+        match &trace.node {
+            Node::Term(term)
+                if matches!(
+                    term.value(),
+                    Value::Expression(Operation {
+                        operator: Operator::And | Operator::Dot,
+                        ..
+                    })
+                ) =>
+            {
+                children()
+            }
+            Node::Term(term) => vec![Self {
+                source: kb.term_source(term, false),
+                location: SourceLocation::of_term(kb, term),
+                children: children(),
+            }],
+            Node::Rule(rule) => vec![Self {
+                source: kb.rule_source(rule),
+                location: SourceLocation::of_rule(kb, rule),
+                children: children(),
+            }],
+        }
+    }
+}
+
+/// A goal in the body of a rule.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FailedGoal {
+    pub source: String,
+    pub location: Option<SourceLocation>,
+}
+
+/// A rule that did not apply to a call, and how far it got.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FailedRule {
+    pub rule: String,
+    pub location: Option<SourceLocation>,
+    /// How many goals in the body succeeded before `failed_goal`.
+    pub satisfied_goals: usize,
+    /// The first goal in the body that failed, or `None` if the arguments of
+    /// the call didn't match the rule's parameters.
+    pub failed_goal: Option<FailedGoal>,
+}
+
+/// Queries for how far a rule gets with the arguments of a call: the first
+/// checks that the arguments match the rule's parameters, and each one after
+/// it adds the next goal of the rule's body.
+pub struct RuleGoalQueries {
+    pub rule: Arc<Rule>,
+    pub queries: Vec<Term>,
+}
+
+impl RuleGoalQueries {
+    pub fn new(rule: Arc<Rule>, args: &[Term]) -> Self {
+        let mut goals = vec![];
+        for (arg, param) in args.iter().zip(rule.params.iter()) {
+            if let Some(specializer) = &param.specializer {
+                goals.push(Term::new_temporary(Value::Expression(Operation {
+                    operator: Operator::Isa,
+                    args: vec![arg.clone(), specializer.clone()],
+                })));
+            }
+            goals.push(Term::new_temporary(Value::Expression(Operation {
+                operator: Operator::Unify,
+                args: vec![arg.clone(), param.parameter.clone()],
+            })));
+        }
+        let body = body_goals(&rule);
+
+        let mut queries = vec![];
+        for goal in std::iter::once(None).chain(body.iter().map(Some)) {
+            if let Some(goal) = goal {
+                goals.push(goal.clone());
+            }
+            queries.push(Term::new_temporary(Value::Expression(Operation {
+                operator: Operator::And,
+                args: goals.clone(),
+            })));
+        }
+        Self { rule, queries }
+    }
+
+    /// Explain the rule failing, given the number of its queries that
+    /// succeeded.
+    pub fn failed_rule(&self, kb: &KnowledgeBase, succeeded: usize) -> FailedRule {
+        let body = body_goals(&self.rule);
+        let satisfied_goals = succeeded.saturating_sub(1);
+        let failed_goal = if succeeded == 0 {
+            None
+        } else {
+            body.get(satisfied_goals).map(|goal| FailedGoal {
+                source: kb.term_source(goal, false),
+                location: SourceLocation::of_term(kb, goal),
+            })
+        };
+        FailedRule {
+            rule: kb.rule_source(&self.rule),
+            location: SourceLocation::of_rule(kb, &self.rule),
+            satisfied_goals,
+            failed_goal,
+        }
+    }
+}
+
+/// The top-level conjuncts of a rule body.
+fn body_goals(rule: &Rule) -> Vec<Term> {
+    match rule.body.value() {
+        Value::Expression(Operation {
+            operator: Operator::And,
+            args,
+        }) => args.clone(),
+        _ => vec![rule.body.clone()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::polar::Polar;
+
+    #[test]
+    fn test_rule_goal_queries() {
+        let polar = Polar::new();
+        polar
+            .load(
+                "f(x, y: Integer) if\n  x = 1 and\n  y > 2;",
+                Some("test.polar".to_owned()),
+            )
+            .unwrap();
+        let queries = polar
+            .rule_goal_queries("f", &[term!(1), term!(2)])
+            .remove(0);
+        assert_eq!(queries.queries.len(), 3);
+        assert_eq!(
+            queries.queries[0].to_polar(),
+            "1 = x and 2 matches Integer{} and 2 = y"
+        );
+        assert!(queries.queries[2]
+            .to_polar()
+            .ends_with("and 2 = y and x = 1 and y > 2"));
+
+        let failed = polar.failed_rule(&queries, 2);
+        assert_eq!(failed.rule, "f(x, y: Integer) if x = 1 and\n  y > 2;");
+        assert_eq!(failed.satisfied_goals, 1);
+        assert_eq!(
+            failed.failed_goal,
+            Some(FailedGoal {
+                source: "y > 2".to_owned(),
+                location: Some(SourceLocation {
+                    filename: Some("test.polar".to_owned()),
+                    row: 3,
+                    column: 2
+                })
+            })
+        );
+        assert!(polar.failed_rule(&queries, 0).failed_goal.is_none());
+    }
+}
@@ -125,6 +125,11 @@ fn precedence(o: &Operator) -> i32 {
         Operator::New => 10,
         Operator::Cut => 10,
         Operator::ForAll => 10,
+        Operator::FindAll => 10,
+        Operator::Count => 10,
+        Operator::Sum => 10,
+        Operator::Min => 10,
+        Operator::Max => 10,
         Operator::Dot => 9,
         Operator::In => 8,
         Operator::Isa => 8,
@@ -145,6 +150,7 @@ fn precedence(o: &Operator) -> i32 {
         Operator::Not => 3,
         Operator::Or => 2,
         Operator::And => 1,
+        Operator::IfThenElse => 0,
     }
 }
 
@@ -414,6 +420,12 @@ pub mod to_polar {
                 In => "in",
                 Cut => "cut",
                 ForAll => "forall",
+                IfThenElse => "if",
+                FindAll => "findall",
+                Count => "count",
+                Sum => "sum",
+                Min => "min",
+                Max => "max",
                 Debug => "debug",
                 Print => "print",
                 Isa => "matches",
@@ -436,6 +448,32 @@ pub mod to_polar {
                     self.args[0].to_polar(),
                     self.args[1].to_polar()
                 ),
+                // The condition and consequent only need parentheses if they
+                // are conditionals themselves.
+                IfThenElse => format!(
+                    "if {} then {} else {}",
+                    to_polar_parens(And, &self.args[0]),
+                    to_polar_parens(And, &self.args[1]),
+                    self.args[2].to_polar()
+                ),
+                // Aggregates, with the result after rewriting
+                FindAll | Count | Sum | Min | Max => {
+                    let arity = if self.operator == Count { 1 } else { 2 };
+                    let aggregate = format!(
+                        "{}({})",
+                        self.operator.to_polar(),
+                        self.args
+                            .iter()
+                            .take(arity)
+                            .map(|arg| arg.to_polar())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    );
+                    match self.args.get(arity) {
+                        Some(result) => format!("{} = {}", aggregate, result.to_polar()),
+                        None => aggregate,
+                    }
+                }
                 New => {
                     if self.args.len() == 1 {
                         format!("new {}", to_polar_parens(self.operator, &self.args[0]))
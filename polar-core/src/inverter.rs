@@ -21,6 +21,10 @@ use crate::vm::{Goals, PolarVirtualMachine};
 /// If the inverter has no results, the inverted query is considered successful.
 /// If the inverter has results, the new bindings or constraints made during the query
 /// are inverted, and returned to the outer VM.
+///
+/// A negated if-then-else never reaches the inverter as a whole: the VM keeps its
+/// condition and negates each branch instead, so that the constraints of the
+/// condition stay with the branch they select.
 #[derive(Clone)]
 pub struct Inverter {
     vm: PolarVirtualMachine,
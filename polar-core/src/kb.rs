@@ -1,13 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::error::ParameterError;
 use crate::error::{PolarError, PolarResult};
 
 pub use super::bindings::Bindings;
 use super::counter::Counter;
+use super::modules::Module;
+use super::role_graph::RoleGraph;
+use super::roles_model::RolesModel;
 use super::rules::*;
 use super::sources::*;
 use super::terms::*;
+use super::warnings::UncheckedCalls;
 use std::sync::Arc;
 
 enum RuleParamMatch {
@@ -37,12 +41,23 @@ pub struct KnowledgeBase {
 
     rules: HashMap<Symbol, GenericRule>,
     rule_prototypes: HashMap<Symbol, Vec<Rule>>,
+    /// Rules declared with `type tabled`, whose answers are memoised per query, and the
+    /// sources that declared them.
+    tabled_rules: HashMap<Symbol, HashSet<u64>>,
+    /// Modules by name, whose rules are loaded under qualified names.
+    modules: HashMap<Symbol, Module>,
     pub sources: Sources,
     /// For symbols returned from gensym.
     gensym_counter: Counter,
     /// For call IDs, instance IDs, symbols, etc.
     id_counter: Counter,
     pub inline_queries: Vec<Term>,
+    /// The calls of the sources loaded since the last query, which are checked for
+    /// undefined rules once all of a policy's files have been loaded.
+    pub unchecked_calls: Vec<UncheckedCalls>,
+    /// The roles configuration and its compiled form, set once it has been validated.
+    roles_model: Option<RolesModel>,
+    role_graph: Option<Arc<RoleGraph>>,
 }
 
 impl KnowledgeBase {
@@ -54,10 +69,15 @@ impl KnowledgeBase {
             loaded_content: Default::default(),
             rules: HashMap::new(),
             rule_prototypes: HashMap::new(),
+            tabled_rules: HashMap::new(),
+            modules: HashMap::new(),
             sources: Sources::default(),
             id_counter: Counter::default(),
             gensym_counter: Counter::default(),
             inline_queries: vec![],
+            unchecked_calls: vec![],
+            roles_model: None,
+            role_graph: None,
         }
     }
 
@@ -440,6 +460,16 @@ impl KnowledgeBase {
         self.rules.get(name)
     }
 
+    /// Whether there are rules or rule prototypes named `name`.
+    pub fn is_rule_defined(&self, name: &Symbol) -> bool {
+        self.rules.contains_key(name) || self.rule_prototypes.contains_key(name)
+    }
+
+    /// The names of the rules and rule prototypes.
+    pub fn rule_names(&self) -> impl Iterator<Item = &Symbol> {
+        self.rules.keys().chain(self.rule_prototypes.keys())
+    }
+
     pub fn add_rule_prototype(&mut self, prototype: Rule) {
         let name = prototype.name.clone();
         // get rule prototypes
@@ -447,6 +477,22 @@ impl KnowledgeBase {
         prototypes.push(prototype);
     }
 
+    pub fn add_tabled_rule(&mut self, name: Symbol, src_id: u64) {
+        self.tabled_rules.entry(name).or_default().insert(src_id);
+    }
+
+    pub fn is_tabled(&self, name: &Symbol) -> bool {
+        self.tabled_rules.contains_key(name)
+    }
+
+    pub fn add_module(&mut self, module: Module) {
+        self.modules.insert(module.name.clone(), module);
+    }
+
+    pub fn get_module(&self, name: &Symbol) -> Option<&Module> {
+        self.modules.get(name)
+    }
+
     /// Define a constant variable.
     pub fn constant(&mut self, name: Symbol, value: Term) {
         self.constants.insert(name, value);
@@ -483,10 +529,29 @@ impl KnowledgeBase {
     pub fn clear_rules(&mut self) {
         self.rules.clear();
         self.rule_prototypes.clear();
+        self.tabled_rules.clear();
+        self.modules.clear();
         self.sources = Sources::default();
         self.inline_queries.clear();
+        self.unchecked_calls.clear();
         self.loaded_content.clear();
         self.loaded_files.clear();
+        self.roles_model = None;
+        self.role_graph = None;
+    }
+
+    pub fn roles_model(&self) -> Option<&RolesModel> {
+        self.roles_model.as_ref()
+    }
+
+    pub fn role_graph(&self) -> Option<Arc<RoleGraph>> {
+        self.role_graph.clone()
+    }
+
+    /// Set the validated roles configuration, compiling it into a `RoleGraph`.
+    pub fn set_roles_model(&mut self, roles_model: Option<RolesModel>) {
+        self.role_graph = roles_model.as_ref().map(|m| Arc::new(RoleGraph::new(m)));
+        self.roles_model = roles_model;
     }
 
     /// Removes a file from the knowledge base by finding the associated
@@ -529,9 +594,19 @@ impl KnowledgeBase {
             .expect("source doesn't exist in KB");
         let filename = source.filename;
 
+        // remove modules
+        self.modules.retain(|_, module| module.src_id != source_id);
+
+        // remove tabled declarations
+        self.tabled_rules.retain(|_, src_ids| {
+            src_ids.remove(&source_id);
+            !src_ids.is_empty()
+        });
+
         // remove queries
         self.inline_queries
             .retain(|q| q.get_source_id() != Some(source_id));
+        self.unchecked_calls.retain(|u| u.src_id != source_id);
 
         // remove from files
         if let Some(filename) = filename {
@@ -575,6 +650,61 @@ impl KnowledgeBase {
         Ok(())
     }
 
+    /// The source text of `term`, or its Polar representation if it has no
+    /// source, optionally followed by its location.
+    pub fn term_source(&self, term: &Term, include_info: bool) -> String {
+        let source = term
+            .get_source_id()
+            .and_then(|id| self.sources.get_source(id));
+        let span = term.span();
+
+        let mut source_string = match (&source, &span) {
+            (Some(source), Some((left, right))) => {
+                source.src.chars().take(*right).skip(*left).collect()
+            }
+            _ => term.to_polar(),
+        };
+
+        if include_info {
+            if let Some(source) = source {
+                let offset = term.offset();
+                let (row, column) = crate::lexer::loc_to_pos(&source.src, offset);
+                source_string.push_str(&format!(" at line {}, column {}", row + 1, column));
+                if let Some(filename) = source.filename {
+                    source_string.push_str(&format!(" in file {}", filename));
+                }
+            }
+        }
+
+        source_string
+    }
+
+    /// The source text of `rule`'s head and body.
+    pub fn rule_source(&self, rule: &Rule) -> String {
+        let head = format!(
+            "{}({})",
+            rule.name,
+            rule.params.iter().fold(String::new(), |mut acc, p| {
+                if !acc.is_empty() {
+                    acc += ", ";
+                }
+                acc += &self.term_source(&p.parameter, false);
+                if let Some(spec) = &p.specializer {
+                    acc += ": ";
+                    acc += &self.term_source(spec, false);
+                }
+                acc
+            })
+        );
+        match rule.body.value() {
+            Value::Expression(Operation {
+                operator: Operator::And,
+                args,
+            }) if !args.is_empty() => head + " if " + &self.term_source(&rule.body, false) + ";",
+            _ => head + ";",
+        }
+    }
+
     pub fn set_error_context(&self, term: &Term, error: impl Into<PolarError>) -> PolarError {
         let source = term
             .get_source_id()
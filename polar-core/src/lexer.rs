@@ -30,6 +30,12 @@ pub struct Lexer<'input> {
     c: Option<(usize, char)>,
     chars: Peekable<CharIndices<'input>>,
     buf: String,
+    /// Whether the next token starts a line, i.e. is first or follows a `;`.
+    line_start: bool,
+    /// Whether the last token ended a term, so that the next can't be a name.
+    /// `then` and `else` are only keywords there, so that they can still be used
+    /// as names, e.g. `f(then) if then = 1;` or `x.else`.
+    term_end: bool,
 }
 
 impl<'input> Lexer<'input> {
@@ -37,7 +43,21 @@ impl<'input> Lexer<'input> {
         let mut chars = input.char_indices().peekable();
         let c = chars.next();
         let buf = String::new();
-        Lexer { c, chars, buf }
+        Lexer {
+            c,
+            chars,
+            buf,
+            line_start: true,
+            term_end: false,
+        }
+    }
+
+    /// A lexer for a single term, which has no lines to start.
+    pub fn new_term(input: &'input str) -> Self {
+        Lexer {
+            line_start: false,
+            ..Self::new(input)
+        }
     }
 }
 
@@ -84,12 +104,22 @@ pub enum Token {
     Print,     // print()
     Isa,       // isa
     ForAll,    // forall
+    FindAll,   // findall
+    Count,     // count
+    Sum,       // sum
+    Min,       // min
+    Max,       // max
     If,        // if
+    Then,      // then
+    Else,      // else
     And,       // and
     Or,        // or
     Not,       // not
     Matches,   // matches
     Type,      // type
+    Module,    // module
+    Import,    // import
+    Private,   // private
 }
 
 impl ToString for Token {
@@ -134,12 +164,22 @@ impl ToString for Token {
             Token::Print => "print".to_owned(),     // print
             Token::Isa => "isa".to_owned(),         // isa
             Token::ForAll => "forall".to_owned(),   // forall
+            Token::FindAll => "findall".to_owned(), // findall
+            Token::Count => "count".to_owned(),     // count
+            Token::Sum => "sum".to_owned(),         // sum
+            Token::Min => "min".to_owned(),         // min
+            Token::Max => "max".to_owned(),         // max
             Token::If => "if".to_owned(),           // if
+            Token::Then => "then".to_owned(),       // then
+            Token::Else => "else".to_owned(),       // else
             Token::And => "and".to_owned(),         // and
             Token::Or => "or".to_owned(),           // or
             Token::Not => "not".to_owned(),         // not
             Token::Matches => "matches".to_owned(), // matches
             Token::Type => "type".to_owned(),       // type
+            Token::Module => "module".to_owned(),   // module
+            Token::Import => "import".to_owned(),   // import
+            Token::Private => "private".to_owned(), // private
         }
     }
 }
@@ -172,6 +212,24 @@ impl<'input> Lexer<'input> {
 
     #[inline]
     #[allow(clippy::unnecessary_wraps)]
+    /// Whether the next character other than whitespace opens an argument list.
+    /// Aggregation keywords are only keywords when called, so that they can still
+    /// be used as names, e.g. `f(count) if count > 1;`.
+    fn opens_call(&self) -> bool {
+        self.c
+            .into_iter()
+            .chain(self.chars.clone())
+            .find(|(_, c)| !c.is_whitespace())
+            .is_some_and(|(_, c)| c == '(')
+    }
+
+    /// Whether the symbol just scanned starts a line without naming a rule. Module
+    /// keywords are only keywords there, so that they can still be used as names,
+    /// e.g. `x.private` or `module(x) if x = 1;`.
+    fn starts_line(&self) -> bool {
+        self.line_start && !self.opens_call()
+    }
+
     fn scan_symbol(&mut self, i: usize, chr: char) -> Option<Spanned<Token, usize, ParseError>> {
         let start = i;
         let mut last = i;
@@ -237,8 +295,22 @@ impl<'input> Lexer<'input> {
             Some(Ok((start, Token::Isa, last + 1)))
         } else if &self.buf == "forall" {
             Some(Ok((start, Token::ForAll, last + 1)))
+        } else if &self.buf == "findall" && self.opens_call() {
+            Some(Ok((start, Token::FindAll, last + 1)))
+        } else if &self.buf == "count" && self.opens_call() {
+            Some(Ok((start, Token::Count, last + 1)))
+        } else if &self.buf == "sum" && self.opens_call() {
+            Some(Ok((start, Token::Sum, last + 1)))
+        } else if &self.buf == "min" && self.opens_call() {
+            Some(Ok((start, Token::Min, last + 1)))
+        } else if &self.buf == "max" && self.opens_call() {
+            Some(Ok((start, Token::Max, last + 1)))
         } else if &self.buf == "if" {
             Some(Ok((start, Token::If, last + 1)))
+        } else if &self.buf == "then" && self.term_end {
+            Some(Ok((start, Token::Then, last + 1)))
+        } else if &self.buf == "else" && self.term_end {
+            Some(Ok((start, Token::Else, last + 1)))
         } else if &self.buf == "and" {
             Some(Ok((start, Token::And, last + 1)))
         } else if &self.buf == "or" {
@@ -249,6 +321,12 @@ impl<'input> Lexer<'input> {
             Some(Ok((start, Token::Matches, last + 1)))
         } else if &self.buf == "type" {
             Some(Ok((start, Token::Type, last + 1)))
+        } else if &self.buf == "module" && self.starts_line() {
+            Some(Ok((start, Token::Module, last + 1)))
+        } else if &self.buf == "import" && self.starts_line() {
+            Some(Ok((start, Token::Import, last + 1)))
+        } else if &self.buf == "private" && self.starts_line() {
+            Some(Ok((start, Token::Private, last + 1)))
         } else if &self.buf == "mod" {
             Some(Ok((start, Token::Mod, last + 1)))
         } else if &self.buf == "rem" {
@@ -464,7 +542,7 @@ impl<'input> Iterator for Lexer<'input> {
 
     fn next(&mut self) -> Option<Self::Item> {
         self.skip_whitespace();
-        match self.c {
+        let token = match self.c {
             None => None,
             Some((i, char)) => match char {
                 x if x == '_' || (!x.is_ascii_punctuation() && !x.is_ascii_digit()) => {
@@ -498,7 +576,25 @@ impl<'input> Iterator for Lexer<'input> {
                     loc: i,
                 })),
             },
-        }
+        };
+        self.line_start = matches!(token, Some(Ok((_, Token::SemiColon, _))));
+        self.term_end = matches!(
+            token,
+            Some(Ok((
+                _,
+                Token::Integer(_)
+                    | Token::Float(_)
+                    | Token::String(_)
+                    | Token::Boolean(_)
+                    | Token::Symbol(_)
+                    | Token::Cut
+                    | Token::RP
+                    | Token::RB
+                    | Token::RCB,
+                _
+            )))
+        );
+        token
     }
 }
 
@@ -7,27 +7,33 @@ extern crate maplit;
 #[macro_use]
 pub mod macros;
 
+mod aggregate;
 mod bindings;
 mod counter;
 pub mod data_filtering;
 mod debugger;
 pub mod error;
 pub mod events;
+pub mod explain;
 mod folder;
 pub mod formatting;
 mod inverter;
 pub mod kb;
 mod lexer;
 pub mod messages;
+mod modules;
 mod numerics;
 pub mod parser;
 mod partial;
 pub mod polar;
 mod rewrites;
+pub mod role_graph;
+pub mod roles_model;
 pub mod roles_validation;
 pub mod rules;
 mod runnable;
 mod sources;
+mod tabling;
 pub mod terms;
 pub mod traces;
 mod visitor;
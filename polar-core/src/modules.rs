@@ -0,0 +1,306 @@
+//! Modules, which give the rules of a policy file their own namespace.
+//!
+//! A file that starts with `module billing;` defines module `billing`, and its
+//! rules are loaded as `billing::<name>`. Inside the module they are called by
+//! their plain names. Other files call them by their qualified names, e.g.
+//! `billing::can_edit(user, doc)`, after an `import billing;` declaration, and
+//! can't call the module's `private` rules at all. A call to a rule that the
+//! calling module doesn't define refers to a rule outside of any module.
+
+use std::collections::HashSet;
+
+use crate::error::{PolarError, PolarResult, ValidationError};
+use crate::folder::{fold_term, Folder};
+use crate::formatting::ToPolarString;
+use crate::kb::KnowledgeBase;
+use crate::parser::Line;
+use crate::rules::Rule;
+use crate::terms::{Call, Operation, Operator, Symbol, Term, Value};
+
+#[derive(Clone, Debug)]
+pub struct Module {
+    pub name: Symbol,
+    /// The source the module was loaded from.
+    pub src_id: u64,
+    /// The unqualified names of the module's rules.
+    pub rules: HashSet<Symbol>,
+    pub private_rules: HashSet<Symbol>,
+}
+
+/// The name of `rule` in `module`, e.g. `billing::can_edit`.
+pub fn qualify(module: &Symbol, rule: &Symbol) -> Symbol {
+    Symbol(format!("{}::{}", module.0, rule.0))
+}
+
+/// Split a qualified rule name into its module and rule names.
+pub fn split(name: &Symbol) -> Option<(Symbol, Symbol)> {
+    name.0
+        .rsplit_once("::")
+        .map(|(module, rule)| (Symbol::new(module), Symbol::new(rule)))
+}
+
+fn invalid_module(module: &Symbol, msg: String) -> ValidationError {
+    ValidationError::InvalidModule {
+        module: module.0.clone(),
+        msg,
+    }
+}
+
+/// Resolves the rule names and calls in the lines of a source, or in a query.
+pub struct Resolver {
+    /// The module the source defines, if any.
+    module: Option<Module>,
+    /// The modules the source imports, or `None` for a query, which may call
+    /// any loaded module.
+    imports: Option<HashSet<Symbol>>,
+}
+
+impl Resolver {
+    /// A resolver for queries outside of any source.
+    pub fn for_query() -> Self {
+        Self {
+            module: None,
+            imports: None,
+        }
+    }
+
+    /// Check the `module`, `import` and `private` declarations in `lines`,
+    /// registering the module they define, if any, in `kb`.
+    pub fn new(kb: &mut KnowledgeBase, src_id: u64, lines: &[Line]) -> PolarResult<Self> {
+        let mut module = None;
+        let mut imports = HashSet::new();
+        for (i, line) in lines.iter().enumerate() {
+            match line {
+                Line::Module(term) => {
+                    let name = term.value().as_symbol()?;
+                    if i != 0 {
+                        return Err(kb.set_error_context(
+                            term,
+                            invalid_module(
+                                name,
+                                "`module` must be the first line of a file".to_owned(),
+                            ),
+                        ));
+                    }
+                    if kb.get_module(name).is_some() {
+                        return Err(kb.set_error_context(
+                            term,
+                            invalid_module(name, "the module is already loaded".to_owned()),
+                        ));
+                    }
+                    module = Some(Module {
+                        name: name.clone(),
+                        src_id,
+                        rules: HashSet::new(),
+                        private_rules: HashSet::new(),
+                    });
+                }
+                Line::Import(term) => {
+                    let name = term.value().as_symbol()?;
+                    if kb.get_module(name).is_none() {
+                        return Err(kb.set_error_context(
+                            term,
+                            ValidationError::UnresolvedImport {
+                                module: name.0.clone(),
+                            },
+                        ));
+                    }
+                    imports.insert(name.clone());
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(module) = module.as_mut() {
+            for line in lines {
+                if let Line::Rule(rule) = line {
+                    module.rules.insert(rule.name.clone());
+                }
+            }
+        }
+        for line in lines {
+            if let Line::PrivateRule(rule) = line {
+                let module = match module.as_mut() {
+                    Some(module) => module,
+                    None => {
+                        return Err(kb.set_error_context(
+                            &rule.body,
+                            ValidationError::InvalidRule {
+                                rule: rule.to_polar(),
+                                msg: "\nOnly rules in a module can be private.".to_owned(),
+                            },
+                        ))
+                    }
+                };
+                if module.rules.contains(&rule.name) && !module.private_rules.contains(&rule.name) {
+                    return Err(kb.set_error_context(
+                        &rule.body,
+                        invalid_module(
+                            &module.name,
+                            format!("{} is defined as both private and public", rule.name.0),
+                        ),
+                    ));
+                }
+                module.rules.insert(rule.name.clone());
+                module.private_rules.insert(rule.name.clone());
+            }
+        }
+
+        if let Some(module) = &module {
+            kb.add_module(module.clone());
+        }
+        Ok(Self {
+            module,
+            imports: Some(imports),
+        })
+    }
+
+    /// The name of the module the source defines, if any.
+    pub fn module_name(&self) -> Option<&Symbol> {
+        self.module.as_ref().map(|module| &module.name)
+    }
+
+    /// The name `rule` is loaded under.
+    pub fn rule_name(&self, rule: &Symbol) -> Symbol {
+        match &self.module {
+            Some(module) if split(rule).is_none() => qualify(&module.name, rule),
+            _ => rule.clone(),
+        }
+    }
+
+    /// Qualify the name of `rule` and resolve the calls in its body.
+    pub fn resolve_rule(&self, kb: &KnowledgeBase, mut rule: Rule) -> PolarResult<Rule> {
+        if let Some((module, _)) = split(&rule.name) {
+            let own_module = self.module.as_ref().is_some_and(|m| m.name == module);
+            if own_module || kb.get_module(&module).is_some() {
+                return Err(kb.set_error_context(
+                    &rule.body,
+                    invalid_module(
+                        &module,
+                        format!("{} can only be defined inside the module", rule.name.0),
+                    ),
+                ));
+            }
+        }
+        rule.name = self.rule_name(&rule.name);
+        let mut resolver = CallResolver {
+            kb,
+            resolver: self,
+            error: None,
+        };
+        let rule = resolver.fold_rule(rule);
+        match resolver.error {
+            Some(error) => Err(error),
+            None => Ok(rule),
+        }
+    }
+
+    /// Resolve the calls in a query.
+    pub fn resolve_term(&self, kb: &KnowledgeBase, term: Term) -> PolarResult<Term> {
+        let mut resolver = CallResolver {
+            kb,
+            resolver: self,
+            error: None,
+        };
+        let term = resolver.fold_term(term);
+        match resolver.error {
+            Some(error) => Err(error),
+            None => Ok(term),
+        }
+    }
+
+    /// The name a call to `name` refers to.
+    fn resolve_call(&self, kb: &KnowledgeBase, name: &Symbol) -> Result<Symbol, ValidationError> {
+        let (module_name, rule) = match split(name) {
+            Some(split) => split,
+            None => {
+                return Ok(match &self.module {
+                    Some(module) if module.rules.contains(name) => qualify(&module.name, name),
+                    _ => name.clone(),
+                })
+            }
+        };
+        let module = match &self.module {
+            Some(module) if module.name == module_name => module,
+            _ => {
+                let imported = self
+                    .imports
+                    .as_ref()
+                    .is_none_or(|imports| imports.contains(&module_name));
+                match kb.get_module(&module_name) {
+                    Some(module) if imported => module,
+                    _ => {
+                        return Err(ValidationError::UnresolvedImport {
+                            module: module_name.0,
+                        })
+                    }
+                }
+            }
+        };
+        if !module.rules.contains(&rule) {
+            Err(invalid_module(
+                &module_name,
+                format!("{} is not defined", name.0),
+            ))
+        } else if module.private_rules.contains(&rule)
+            && self.module.as_ref().is_none_or(|m| m.name != module_name)
+        {
+            Err(invalid_module(
+                &module_name,
+                format!("{} is private", name.0),
+            ))
+        } else {
+            Ok(name.clone())
+        }
+    }
+}
+
+/// Resolves the calls in a rule or term, keeping the first error.
+struct CallResolver<'a> {
+    kb: &'a KnowledgeBase,
+    resolver: &'a Resolver,
+    error: Option<PolarError>,
+}
+
+impl<'a> Folder for CallResolver<'a> {
+    fn fold_term(&mut self, t: Term) -> Term {
+        if let Value::Call(call) = t.value() {
+            match self.resolver.resolve_call(self.kb, &call.name) {
+                Ok(name) => {
+                    let call = Call {
+                        name,
+                        ..call.clone()
+                    };
+                    return fold_term(t.clone_with_value(Value::Call(call)), self);
+                }
+                Err(error) => {
+                    if self.error.is_none() {
+                        self.error = Some(self.kb.set_error_context(&t, error));
+                    }
+                }
+            }
+        }
+        fold_term(t, self)
+    }
+
+    /// Method calls and constructors aren't calls to rules.
+    fn fold_operation(&mut self, o: Operation) -> Operation {
+        match o.operator {
+            Operator::Dot | Operator::New => Operation {
+                operator: o.operator,
+                args: o
+                    .args
+                    .into_iter()
+                    .map(|arg| match arg.value() {
+                        Value::Call(call) => {
+                            let call = self.fold_call(call.clone());
+                            arg.clone_with_value(Value::Call(call))
+                        }
+                        _ => self.fold_term(arg),
+                    })
+                    .collect(),
+            },
+            _ => crate::folder::fold_operation(o, self),
+        }
+    }
+}
@@ -22,6 +22,11 @@ use super::terms::*;
 pub enum Line {
     Rule(Rule),
     RulePrototype(Rule),
+    TabledRulePrototype(Rule),
+    /// A rule only visible inside its module.
+    PrivateRule(Rule),
+    Module(Term),
+    Import(Term),
     Query(Term),
 }
 
@@ -53,7 +58,7 @@ fn to_parse_error(e: ParseError<usize, lexer::Token, error::ParseError>) -> erro
 
 pub fn parse_term(src: &str) -> PolarResult<Term> {
     polar::TermParser::new()
-        .parse(0, Lexer::new(src))
+        .parse(0, Lexer::new_term(src))
         .map_err(|e| to_parse_error(e).into())
 }
 
@@ -65,7 +70,7 @@ pub fn parse_lines(src_id: u64, src: &str) -> PolarResult<Vec<Line>> {
 
 pub fn parse_query(src_id: u64, src: &str) -> PolarResult<Term> {
     polar::TermParser::new()
-        .parse(src_id, Lexer::new(src))
+        .parse(src_id, Lexer::new_term(src))
         .map_err(|e| to_parse_error(e).into())
 }
 
@@ -219,12 +224,27 @@ mod tests {
             line[0],
             Line::RulePrototype(rule!("f", ["x"; value!(instance!("String"))]))
         );
+
+        let prototype = r#"type tabled f(x, y);"#;
+        let line = parse_lines(prototype);
+        assert_eq!(
+            line[0],
+            Line::TabledRulePrototype(rule!("f", [sym!("x"), sym!("y")]))
+        );
+
+        let module = r#"module billing; import accounts; private f(x);"#;
+        let lines = parse_lines(module);
+        assert_eq!(lines[0], Line::Module(term!(sym!("billing"))));
+        assert_eq!(lines[1], Line::Import(term!(sym!("accounts"))));
+        assert_eq!(lines[2], Line::PrivateRule(rule!("f", [sym!("x")])));
     }
 
     #[test]
     fn test_rule_prototype_error() {
         let prototype = r#"type f(x: String) if x = "bad";"#;
         super::parse_lines(0, prototype).expect_err("parse error");
+        let prototype = r#"type memoized f(x);"#;
+        super::parse_lines(0, prototype).expect_err("parse error");
     }
 
     #[test]
@@ -389,4 +409,33 @@ mod tests {
             }
         ));
     }
+
+    #[test]
+    fn test_parse_if_then_else() {
+        let q = "if x = 1 then y = 1 else if x = 2 then y = 2 else y = 3 and z = 1";
+        assert_eq!(
+            parse_query(q),
+            term!(op!(
+                IfThenElse,
+                term!(op!(Unify, term!(sym!("x")), term!(1))),
+                term!(op!(Unify, term!(sym!("y")), term!(1))),
+                term!(op!(
+                    IfThenElse,
+                    term!(op!(Unify, term!(sym!("x")), term!(2))),
+                    term!(op!(Unify, term!(sym!("y")), term!(2))),
+                    term!(op!(
+                        And,
+                        term!(op!(Unify, term!(sym!("y")), term!(3))),
+                        term!(op!(Unify, term!(sym!("z")), term!(1)))
+                    ))
+                ))
+            ))
+        );
+        assert_eq!(parse_query(q).to_polar(), q);
+
+        let q = "(if a then b else c) and d";
+        assert_eq!(parse_query(q).to_polar(), q);
+        let q = "if (if a then b else c) then d else e";
+        assert_eq!(parse_query(q).to_polar(), q);
+    }
 }
@@ -58,15 +58,26 @@ pub fn invert_operation(Operation { operator, args }: Operation) -> Operation {
         Operator::Debug | Operator::Print | Operator::New | Operator::Dot => {
             Operation { operator, args }
         }
-        Operator::Isa => Operation {
+        Operator::Isa | Operator::In => Operation {
             operator: Operator::Not,
-            args: vec![term!(op!(Isa, args[0].clone(), args[1].clone()))],
+            args: vec![term!(Operation { operator, args })],
         },
         Operator::Not => args[0]
             .value()
             .as_expression()
             .expect("negated expression")
             .clone(),
+        // The condition still selects a branch; only the branches are negated.
+        Operator::IfThenElse => {
+            let mut args = args.into_iter();
+            let condition = args.next().expect("condition");
+            let mut branches = invert_args(args.collect());
+            branches.insert(0, condition);
+            Operation {
+                operator,
+                args: branches,
+            }
+        }
         _ => todo!("negate {:?}", operator),
     }
 }
@@ -1664,6 +1675,80 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_that_aggregate_over_partial_errors() -> TestResult {
+        let p = Polar::new();
+        p.load_str("f(x, n) if x > 1 and n = count(_y in x);")?;
+        let mut q = p.new_query_from_term(term!(call!("f", [sym!("x"), sym!("n")])), false);
+        let error = q.next_event().unwrap_err();
+        assert!(matches!(
+            error,
+            PolarError {
+                kind: ErrorKind::Runtime(RuntimeError::Unsupported { .. }),
+                ..
+            }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_if_then_else_with_partial() -> TestResult {
+        let p = Polar::new();
+        p.load_str(
+            r#"f(x, y) if if x > 1 then y = 1 else y = 2;
+               g(x, y) if if y = 1 then x > 1 else x < 0;
+               h(x) if not (if x > 1 then x < 3 else x = 0);
+               k(x, y) if not (if x > 1 then y = 1 else y = 2);
+               n(x, y) if if x > 1 then (if x < 3 then y = 1 else y = 2) else y = 3;"#,
+        )?;
+
+        // A condition that only succeeds under constraints leaves the
+        // alternative for when the constraints don't hold.
+        let mut q = p.new_query_from_term(term!(call!("f", [sym!("x"), sym!("y")])), false);
+        let next = next_binding(&mut q)?;
+        assert_partial_expression!(next, "x", "_this > 1");
+        assert_eq!(next[&sym!("y")], term!(1));
+        let next = next_binding(&mut q)?;
+        assert_partial_expression!(next, "x", "_this <= 1");
+        assert_eq!(next[&sym!("y")], term!(2));
+        assert_query_done!(q);
+
+        // A condition that succeeds outright prunes the alternative.
+        let mut q = p.new_query_from_term(term!(call!("g", [sym!("x"), 1])), false);
+        assert_partial_expression!(next_binding(&mut q)?, "x", "_this > 1");
+        assert_query_done!(q);
+
+        // Negated, each branch is negated under its own condition.
+        let mut q = p.new_query_from_term(term!(call!("h", [sym!("x")])), false);
+        assert_partial_expression!(next_binding(&mut q)?, "x", "_this > 1 and _this >= 3");
+        assert_partial_expression!(next_binding(&mut q)?, "x", "_this <= 1 and _this != 0");
+        assert_query_done!(q);
+
+        // The condition's constraints stay with the branch they select.
+        let mut q = p.new_query_from_term(term!(call!("k", [sym!("x"), sym!("y")])), false);
+        let next = next_binding(&mut q)?;
+        assert_partial_expression!(next, "x", "_this > 1");
+        assert_partial_expression!(next, "y", "_this != 1");
+        let next = next_binding(&mut q)?;
+        assert_partial_expression!(next, "x", "_this <= 1");
+        assert_partial_expression!(next, "y", "_this != 2");
+        assert_query_done!(q);
+
+        // Nested conditionals.
+        let mut q = p.new_query_from_term(term!(call!("n", [sym!("x"), sym!("y")])), false);
+        let next = next_binding(&mut q)?;
+        assert_partial_expression!(next, "x", "_this > 1 and _this < 3");
+        assert_eq!(next[&sym!("y")], term!(1));
+        let next = next_binding(&mut q)?;
+        assert_partial_expression!(next, "x", "_this > 1 and _this >= 3");
+        assert_eq!(next[&sym!("y")], term!(2));
+        let next = next_binding(&mut q)?;
+        assert_partial_expression!(next, "x", "_this <= 1");
+        assert_eq!(next[&sym!("y")], term!(3));
+        assert_query_done!(q);
+        Ok(())
+    }
+
     #[test]
     fn test_cut_with_partial() -> TestResult {
         let p = Polar::new();
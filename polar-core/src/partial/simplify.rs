@@ -542,6 +542,24 @@ impl Simplifier {
                 )
             }
 
+            // Conditional. Simplify the condition and both branches separately,
+            // because bindings may not leak between them. A trivial condition
+            // selects its branch.
+            Operator::IfThenElse => {
+                assert_eq!(o.args.len(), 3);
+                for arg in &mut o.args {
+                    self.clone().simplify_partial(arg);
+                }
+                let branch = match o.args[0].value().as_expression() {
+                    Ok(condition) if condition == &TRUE => Some(1),
+                    Ok(condition) if condition == &FALSE => Some(2),
+                    _ => None,
+                };
+                if let Some(Value::Expression(operation)) = branch.map(|i| o.args[i].value()) {
+                    *o = operation.clone();
+                }
+            }
+
             // Default case.
             _ => {
                 for arg in &mut o.args {
@@ -706,4 +724,43 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn test_simplify_if_then_else() {
+        let mut vs: HashSet<Symbol> = HashSet::new();
+        vs.insert(sym!("x"));
+        vs.insert(sym!("y"));
+
+        // A trivial condition selects its branch.
+        let mut term = opn!(
+            IfThenElse,
+            opn!(And, opn!(Unify, var!("y"), var!("y"))),
+            opn!(Gt, var!("x"), term!(1)),
+            opn!(Lt, var!("x"), term!(0))
+        );
+        Simplifier::new(vs.clone(), false).simplify_partial(&mut term);
+        assert_eq!(term, opn!(Gt, var!("x"), term!(1)));
+
+        // Bindings in the condition don't leak into the branches.
+        let mut term = opn!(
+            IfThenElse,
+            opn!(
+                And,
+                opn!(Unify, var!("z"), var!("y")),
+                opn!(Gt, var!("z"), term!(1))
+            ),
+            opn!(Unify, var!("x"), var!("z")),
+            opn!(Lt, var!("x"), var!("z"))
+        );
+        Simplifier::new(vs, false).simplify_partial(&mut term);
+        assert_eq!(
+            term,
+            opn!(
+                IfThenElse,
+                opn!(Gt, var!("y"), term!(1)),
+                opn!(Unify, var!("x"), var!("z")),
+                opn!(Lt, var!("x"), var!("z"))
+            )
+        );
+    }
 }
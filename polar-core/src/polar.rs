@@ -1,16 +1,21 @@
 use super::data_filtering::{build_filter_plan, FilterPlan, PartialResults, Types};
-use super::error::PolarResult;
+use super::error::{DataFilteringError, ErrorKind, PolarResult};
 use super::events::*;
+use super::explain::{FailedRule, ProofNode, RuleGoalQueries};
 use super::kb::*;
 use super::messages::*;
+use super::modules::Resolver;
 use super::parser;
 use super::rewrites::*;
+use super::roles_model::RolesModel;
 use super::roles_validation::{validate_roles_config, VALIDATE_ROLES_CONFIG_RESOURCES};
+use super::rules::Rule;
 use super::runnable::Runnable;
 use super::sources::*;
 use super::terms::*;
+use super::traces::Node;
 use super::vm::*;
-use super::warnings::check_singletons;
+use super::warnings::{check_singletons, check_undefined_calls, UncheckedCalls};
 
 use std::sync::{Arc, RwLock};
 
@@ -114,6 +119,30 @@ impl Query {
         self.vm.term_source(&self.term, true)
     }
 
+    /// The source of `rule` as it was loaded.
+    pub fn rule_source(&self, rule: &Rule) -> String {
+        self.vm.rule_source(rule)
+    }
+
+    /// The top-level rule applied to produce the last result. The VM records
+    /// the rules it applies whether or not the query is traced.
+    pub fn applied_rule(&self) -> Option<Arc<Rule>> {
+        let query = self.vm.trace.first()?;
+        query.children.iter().find_map(|child| match &child.node {
+            Node::Rule(rule) => Some(rule.clone()),
+            Node::Term(_) => None,
+        })
+    }
+
+    /// The proof tree of the last result.
+    pub fn proof(&self) -> Vec<ProofNode> {
+        let kb = self.vm.kb.read().unwrap();
+        self.vm
+            .trace
+            .first()
+            .map_or_else(Vec::new, |trace| ProofNode::from_trace(&kb, trace))
+    }
+
     pub fn bind(&mut self, name: Symbol, value: Term) -> PolarResult<()> {
         self.vm.bind(&name, value)
     }
@@ -157,6 +186,16 @@ impl Polar {
     }
 
     pub fn load(&self, src: &str, filename: Option<String>) -> PolarResult<()> {
+        self.load_policy(src, filename, true)
+    }
+
+    /// Load a policy, warning about calls to undefined rules if `check_calls`.
+    fn load_policy(
+        &self,
+        src: &str,
+        filename: Option<String>,
+        check_calls: bool,
+    ) -> PolarResult<()> {
         let source = Source {
             filename,
             src: src.to_owned(),
@@ -170,24 +209,39 @@ impl Polar {
             source_id: u64,
             source: &Source,
             kb: &mut KnowledgeBase,
+            check_calls: bool,
         ) -> PolarResult<Vec<String>> {
             let mut lines = parser::parse_lines(source_id, &source.src)
                 .map_err(|e| e.set_context(Some(source), None))?;
+            let resolver = Resolver::new(kb, source_id, &lines)?;
             lines.reverse();
             let mut warnings = vec![];
+            let mut rules = vec![];
+            let mut queries = vec![];
             while let Some(line) = lines.pop() {
                 match line {
-                    parser::Line::Rule(rule) => {
+                    parser::Line::Rule(rule) | parser::Line::PrivateRule(rule) => {
                         let mut rule_warnings = check_singletons(&rule, &*kb)?;
                         warnings.append(&mut rule_warnings);
+                        let rule = resolver.resolve_rule(kb, rule)?;
+                        rules.push(rule.clone());
                         let rule = rewrite_rule(rule, kb);
                         kb.add_rule(rule);
                     }
                     parser::Line::Query(term) => {
+                        let term = resolver.resolve_term(kb, term)?;
+                        queries.push(term.clone());
                         kb.inline_queries.push(term);
                     }
+                    // Checked by the resolver.
+                    parser::Line::Module(_) | parser::Line::Import(_) => {}
+                    parser::Line::TabledRulePrototype(prototype) => {
+                        kb.add_tabled_rule(resolver.rule_name(&prototype.name), source_id);
+                        lines.push(parser::Line::RulePrototype(prototype));
+                    }
                     parser::Line::RulePrototype(prototype) => {
                         // make sure prototype doesn't have anything that needs to be rewritten in the head
+                        let prototype = resolver.resolve_rule(kb, prototype)?;
                         let prototype = rewrite_rule(prototype, kb);
                         if !matches!(
                             prototype.body.value(),
@@ -212,12 +266,20 @@ impl Polar {
             }
             // check rules are valid against rule prototypes
             kb.validate_rules()?;
+            if check_calls {
+                kb.unchecked_calls.push(UncheckedCalls {
+                    src_id: source_id,
+                    rules,
+                    queries,
+                    module: resolver.module_name().cloned(),
+                });
+            }
             Ok(warnings)
         }
 
         // if any of the lines fail to load, we need to remove the source from
         // the knowledge base
-        match load_source(source_id, &source, &mut kb) {
+        match load_source(source_id, &source, &mut kb, check_calls) {
             Ok(warnings) => {
                 self.messages.extend(warnings.iter().map(|m| Message {
                     kind: MessageKind::Warning,
@@ -264,12 +326,13 @@ impl Polar {
             let term =
                 parser::parse_query(src_id, src).map_err(|e| e.set_context(Some(&source), None))?;
             kb.sources.add_source(source, src_id);
-            term
+            Resolver::for_query().resolve_term(&kb, term)?
         };
         Ok(self.new_query_from_term(term, trace))
     }
 
     pub fn new_query_from_term(&self, mut term: Term, trace: bool) -> Query {
+        self.warn_undefined_calls();
         {
             let mut kb = self.kb.write().unwrap();
             term = rewrite_term(term, &mut kb);
@@ -280,6 +343,22 @@ impl Polar {
         Query::new(vm, term)
     }
 
+    /// Warn about calls to undefined rules in the sources loaded since the last query.
+    /// Checking here rather than when each source is loaded lets a policy's files be
+    /// loaded in any order.
+    fn warn_undefined_calls(&self) {
+        let mut kb = self.kb.write().unwrap();
+        let unchecked = std::mem::take(&mut kb.unchecked_calls);
+        for unchecked in &unchecked {
+            let warnings = check_undefined_calls(unchecked, &kb);
+            self.messages
+                .extend(warnings.into_iter().map(|msg| Message {
+                    kind: MessageKind::Warning,
+                    msg,
+                }));
+        }
+    }
+
     // @TODO: Direct load_rules endpoint.
 
     pub fn get_external_id(&self) -> u64 {
@@ -300,7 +379,9 @@ impl Polar {
 
     /// Load the Polar roles policy idempotently.
     pub fn enable_roles(&self) -> PolarResult<()> {
-        let result = match self.load(ROLES_POLICY, Some("Built-in Polar Roles Policy".to_owned())) {
+        // The roles policy calls rules the application's policy defines.
+        let filename = Some("Built-in Polar Roles Policy".to_owned());
+        let result = match self.load_policy(ROLES_POLICY, filename, false) {
             Err(error::PolarError {
                 kind: error::ErrorKind::Runtime(error::RuntimeError::FileLoading { .. }),
                 ..
@@ -316,8 +397,66 @@ impl Polar {
         result
     }
 
+    /// Validate the roles configuration and compile it into the role graph
+    /// consulted by the built-in roles policy.
     pub fn validate_roles_config(&self, results: Vec<Vec<ResultEvent>>) -> PolarResult<()> {
-        validate_roles_config(self.kb.read().unwrap().get_rules(), results)
+        let mut kb = self.kb.write().unwrap();
+        let roles_model = validate_roles_config(&kb, results)?;
+        kb.set_roles_model(Some(roles_model));
+        Ok(())
+    }
+
+    /// The validated roles configuration, for export as JSON or DOT.
+    pub fn roles_model(&self) -> PolarResult<RolesModel> {
+        self.kb
+            .read()
+            .unwrap()
+            .roles_model()
+            .cloned()
+            .ok_or_else(|| {
+                error::RolesValidationError(
+                    "Enable roles with `enable_roles` before requesting the roles model."
+                        .to_owned(),
+                )
+                .into()
+            })
+    }
+
+    /// Whether the knowledge base has a rule named `name` taking `arity` arguments.
+    pub fn has_rule(&self, name: &str, arity: usize) -> bool {
+        let kb = self.kb.read().unwrap();
+        kb.get_generic_rule(&Symbol::new(name))
+            .is_some_and(|generic_rule| {
+                generic_rule
+                    .rules
+                    .values()
+                    .any(|rule| rule.params.len() == arity)
+            })
+    }
+
+    /// The queries for how far each rule named `name` gets with `args`, in
+    /// the order the rules were loaded.
+    pub fn rule_goal_queries(&self, name: &str, args: &[Term]) -> Vec<RuleGoalQueries> {
+        let kb = self.kb.read().unwrap();
+        let generic_rule = match kb.get_generic_rule(&Symbol::new(name)) {
+            Some(generic_rule) => generic_rule,
+            None => return vec![],
+        };
+        let mut rules = generic_rule
+            .rules
+            .iter()
+            .filter(|(_, rule)| rule.params.len() == args.len())
+            .collect::<Vec<_>>();
+        rules.sort_by_key(|(id, _)| **id);
+        rules
+            .into_iter()
+            .map(|(_, rule)| RuleGoalQueries::new(rule.clone(), args))
+            .collect()
+    }
+
+    /// Explain why a rule failed, given how many of its goal queries succeeded.
+    pub fn failed_rule(&self, queries: &RuleGoalQueries, succeeded: usize) -> FailedRule {
+        queries.failed_rule(&self.kb.read().unwrap(), succeeded)
     }
 
     pub fn build_filter_plan(
@@ -327,7 +466,18 @@ impl Polar {
         variable: &str,
         class_tag: &str,
     ) -> PolarResult<FilterPlan> {
-        build_filter_plan(types, partial_results, variable, class_tag)
+        build_filter_plan(types, partial_results, variable, class_tag).map_err(|err| {
+            // Point at the part of the policy that couldn't be filtered on.
+            if let ErrorKind::DataFiltering(DataFilteringError {
+                term: Some(term), ..
+            }) = &err.kind
+            {
+                let term = term.clone();
+                self.kb.read().unwrap().set_error_context(&term, err)
+            } else {
+                err
+            }
+        })
     }
 }
 
@@ -382,4 +532,24 @@ mod tests {
             .load("f(x) if x = 1;", Some("test.polar".to_string()))
             .unwrap();
     }
+
+    #[test]
+    fn remove_file_untables_rules() {
+        let polar = Polar::new();
+        polar
+            .load("type tabled f(_);", Some("a.polar".to_string()))
+            .unwrap();
+        polar
+            .load("type tabled g(_);", Some("b.polar".to_string()))
+            .unwrap();
+        polar
+            .load("type tabled g(x);", Some("c.polar".to_string()))
+            .unwrap();
+        polar.remove_file("a.polar");
+        polar.remove_file("b.polar");
+        let kb = polar.kb.read().unwrap();
+        assert!(!kb.is_tabled(&Symbol::new("f")));
+        // Still declared by c.polar.
+        assert!(kb.is_tabled(&Symbol::new("g")));
+    }
 }
@@ -70,9 +70,51 @@ impl<'kb> Rewriter<'kb> {
                 true
             }
             Operator::New if o.args.len() == 1 => true,
+            Operator::Count if o.args.len() == 1 => true,
+            Operator::FindAll | Operator::Sum | Operator::Min | Operator::Max
+                if o.args.len() == 2 =>
+            {
+                true
+            }
             _ => false,
         }
     }
+
+    /// Rewrite a term in its own conjunction, e.g. an argument of `and`.
+    fn fold_scoped(&mut self, arg: Term) -> Term {
+        let arg_operator = arg.value().as_expression().map(|e| e.operator).ok();
+
+        self.stack.push(vec![]);
+        let mut arg = self.fold_term(arg);
+        let mut rewrites = self.stack.pop().unwrap();
+        // Decide whether to prepend, or append
+
+        // If the current operator is unify and rewrites are only
+        // dot operations we append the rewrites after the temporary variable.
+        // This ensures that grounding does not occur when performing dot
+        // operations on a partial.
+        //
+        // Append:
+        // - x.foo.bar = 1 => _value_1 = 1 and x.foo = _value_2 and _value_2.bar = _value_1
+        //
+        // Prepend:
+        //
+        // - x = new Foo(x: new Bar(x: 1)) =>
+        //   _instance_2 = new Bar(x: 1) and _instance_1 = new Foo(x: _instance_2) and x = _instance_1
+        //
+        // We prepend when the rewritten variable needs to be bound before it is
+        // used.
+        if only_dots(&rewrites) && arg_operator.map_or(false, |o| o == Operator::Unify) {
+            for rewrite in rewrites {
+                and_append(&mut arg, rewrite);
+            }
+        } else {
+            for rewrite in rewrites.drain(..).rev() {
+                and_prepend(&mut arg, rewrite);
+            }
+        }
+        arg
+    }
 }
 
 fn temp_name(o: &Operator) -> &'static str {
@@ -80,6 +122,9 @@ fn temp_name(o: &Operator) -> &'static str {
         Operator::Add | Operator::Div | Operator::Mul | Operator::Sub => "op",
         Operator::Dot => "value",
         Operator::New => "instance",
+        Operator::FindAll | Operator::Count | Operator::Sum | Operator::Min | Operator::Max => {
+            "aggregate"
+        }
         _ => "temp",
     }
 }
@@ -135,7 +180,7 @@ impl<'kb> Folder for Rewriter<'kb> {
             }
             Value::Expression(o) if self.needs_rewrite(o) => {
                 // Rewrite sub-expressions, then push a temp onto the args.
-                let mut new = fold_operation(o.clone(), self);
+                let mut new = self.fold_operation(o.clone());
                 let temp = Value::Variable(self.kb.gensym(temp_name(&o.operator)));
                 new.args.push(Term::new_temporary(temp.clone()));
 
@@ -154,49 +199,36 @@ impl<'kb> Folder for Rewriter<'kb> {
 
     fn fold_operation(&mut self, o: Operation) -> Operation {
         match o.operator {
-            Operator::And | Operator::Or | Operator::Not => Operation {
+            Operator::And | Operator::Or | Operator::Not | Operator::IfThenElse => Operation {
                 operator: fold_operator(o.operator, self),
                 args: o
                     .args
                     .into_iter()
-                    .map(|arg| {
-                        let arg_operator = arg.value().as_expression().map(|e| e.operator).ok();
-
-                        self.stack.push(vec![]);
-                        let mut arg = self.fold_term(arg);
-                        let mut rewrites = self.stack.pop().unwrap();
-                        // Decide whether to prepend, or append
-
-                        // If the current operator is unify and rewrites are only
-                        // dot operations we append the rewrites after the temporary variable.
-                        // This ensures that grounding does not occur when performing dot
-                        // operations on a partial.
-                        //
-                        // Append:
-                        // - x.foo.bar = 1 => _value_1 = 1 and x.foo = _value_2 and _value_2.bar = _value_1
-                        //
-                        // Prepend:
-                        //
-                        // - x = new Foo(x: new Bar(x: 1)) =>
-                        //   _instance_2 = new Bar(x: 1) and _instance_1 = new Foo(x: _instance_2) and x = _instance_1
-                        //
-                        // We prepend when the rewritten variable needs to be bound before it is
-                        // used.
-                        if only_dots(&rewrites)
-                            && arg_operator.map_or(false, |o| o == Operator::Unify)
-                        {
-                            for rewrite in rewrites {
-                                and_append(&mut arg, rewrite);
-                            }
-                        } else {
-                            for rewrite in rewrites.drain(..).rev() {
-                                and_prepend(&mut arg, rewrite);
-                            }
-                        }
-                        arg
-                    })
+                    .map(|arg| self.fold_scoped(arg))
                     .collect(),
             },
+            Operator::FindAll | Operator::Count | Operator::Sum | Operator::Min | Operator::Max => {
+                // The goal is rewritten in its own scope, and so is the template,
+                // whose rewrites must run after the goal binds its variables.
+                let goal_index = if o.operator == Operator::Count { 0 } else { 1 };
+                let mut args = o.args;
+                let mut goal = self.fold_scoped(args[goal_index].clone());
+                if goal_index == 1 {
+                    self.stack.push(vec![]);
+                    args[0] = self.fold_term(args[0].clone());
+                    for rewrite in self.stack.pop().unwrap() {
+                        and_append(&mut goal, rewrite);
+                    }
+                }
+                args[goal_index] = goal;
+                for arg in args.iter_mut().skip(goal_index + 1) {
+                    *arg = self.fold_term(arg.clone());
+                }
+                Operation {
+                    operator: o.operator,
+                    args,
+                }
+            }
             _ => fold_operation(o, self),
         }
     }
@@ -466,4 +498,16 @@ mod tests {
             "not (_value_1 = 1 and foo.x = _value_1)"
         )
     }
+
+    #[test]
+    fn rewrite_aggregates() {
+        let mut kb = KnowledgeBase::new();
+        let term = parse_query("sum(x.price, x in cart.items) > 10");
+        assert_eq!(term.to_polar(), "sum(x.price, x in cart.items) > 10");
+
+        pretty_assertions::assert_eq!(
+            rewrite_term(term, &mut kb).to_polar(),
+            "sum(_value_2, cart.items = _value_1 and x in _value_1 and x.price = _value_2) = _aggregate_3 and _aggregate_3 > 10"
+        )
+    }
 }
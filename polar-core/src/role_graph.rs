@@ -0,0 +1,465 @@
+//! A compiled form of a validated roles configuration.
+//!
+//! `roles.polar` delegates its permission and implication lookups to the
+//! predicates below, which the VM answers directly from the `RoleGraph`
+//! instead of walking the `resource` rule dictionaries on every query.
+//! Relationships between resources (`parent_child`) are still queried.
+
+use std::collections::{HashMap, HashSet};
+
+use super::roles_model::{RolesModel, GLOBAL_NAMESPACE};
+
+/// `__oso_internal__role_has_permission(role_namespace, permission_namespace, action, role)`
+pub const ROLE_HAS_PERMISSION: &str = "__oso_internal__role_has_permission";
+
+/// `__oso_internal__role_implied_by(namespace, role, implying_role)`
+pub const ROLE_IMPLIED_BY: &str = "__oso_internal__role_implied_by";
+
+/// `__oso_internal__role_implied_by_ancestor(namespace, role, ancestor_namespace, ancestor_role)`
+pub const ROLE_IMPLIED_BY_ANCESTOR: &str = "__oso_internal__role_implied_by_ancestor";
+
+/// `__oso_internal__role_denies_action(role_namespace, action_namespace, action, role)`
+pub const ROLE_DENIES_ACTION: &str = "__oso_internal__role_denies_action";
+
+/// `__oso_internal__denied_action(action)`
+pub const DENIED_ACTION: &str = "__oso_internal__denied_action";
+
+/// `__oso_internal__global_role(global_role)`
+pub const GLOBAL_ROLE: &str = "__oso_internal__global_role";
+
+/// `__oso_internal__global_role_has_permission(namespace, action, global_role)`
+pub const GLOBAL_ROLE_HAS_PERMISSION: &str = "__oso_internal__global_role_has_permission";
+
+/// `__oso_internal__role_implied_by_global(namespace, role, global_role)`
+pub const ROLE_IMPLIED_BY_GLOBAL: &str = "__oso_internal__role_implied_by_global";
+
+pub fn is_role_graph_predicate(name: &str) -> bool {
+    [
+        ROLE_HAS_PERMISSION,
+        ROLE_IMPLIED_BY,
+        ROLE_IMPLIED_BY_ANCESTOR,
+        ROLE_DENIES_ACTION,
+        DENIED_ACTION,
+        GLOBAL_ROLE,
+        GLOBAL_ROLE_HAS_PERMISSION,
+        ROLE_IMPLIED_BY_GLOBAL,
+    ]
+    .contains(&name)
+}
+
+type Index<T> = HashMap<String, HashMap<String, T>>;
+
+#[derive(Clone, Debug, Default)]
+pub struct RoleGraph {
+    /// role namespace → permission namespace → action → roles granting it.
+    permissions: Index<HashMap<String, Vec<String>>>,
+    /// role namespace → action namespace → action → roles denying it.
+    denials: Index<HashMap<String, Vec<String>>>,
+    /// Every action denied by some role, in any namespace.
+    denied_actions: Vec<String>,
+    /// namespace → role → roles in the same namespace that imply it,
+    /// including the role itself.
+    implied_by: Index<Vec<String>>,
+    /// namespace → role → ancestor namespace → roles in the ancestor
+    /// namespace that directly imply it.
+    implied_by_ancestor: Index<HashMap<String, Vec<String>>>,
+    global_roles: Vec<String>,
+    /// namespace → action → global roles granting it, directly or through
+    /// the global roles they imply.
+    global_permissions: Index<Vec<String>>,
+    /// namespace → role → global roles that imply it, directly or through
+    /// the global roles they imply.
+    implied_by_global: Index<Vec<String>>,
+}
+
+/// Split a `"namespace:name"` reference, defaulting to `namespace`.
+pub(crate) fn qualify<'a>(namespace: &'a str, name: &'a str) -> (&'a str, &'a str) {
+    match name.find(':') {
+        Some(i) => (&name[..i], &name[i + 1..]),
+        None => (namespace, name),
+    }
+}
+
+fn push<T: PartialEq>(values: &mut Vec<T>, value: T) {
+    if !values.contains(&value) {
+        values.push(value);
+    }
+}
+
+impl RoleGraph {
+    pub fn new(model: &RolesModel) -> Self {
+        let mut graph = Self::default();
+        for resource in &model.resources {
+            let namespace = &resource.namespace;
+            let roles = resource
+                .roles
+                .iter()
+                .map(|role| (role.name.as_str(), role))
+                .collect::<HashMap<_, _>>();
+
+            for role in &resource.roles {
+                for permission in &role.permissions {
+                    let (permission_namespace, action) = qualify(namespace, permission);
+                    push(
+                        graph
+                            .permissions
+                            .entry(namespace.clone())
+                            .or_default()
+                            .entry(permission_namespace.to_owned())
+                            .or_default()
+                            .entry(action.to_owned())
+                            .or_default(),
+                        role.name.clone(),
+                    );
+                }
+
+                for denied in &role.deny {
+                    let (action_namespace, action) = qualify(namespace, denied);
+                    push(
+                        graph
+                            .denials
+                            .entry(namespace.clone())
+                            .or_default()
+                            .entry(action_namespace.to_owned())
+                            .or_default()
+                            .entry(action.to_owned())
+                            .or_default(),
+                        role.name.clone(),
+                    );
+                    push(&mut graph.denied_actions, action.to_owned());
+                }
+
+                for implied in &role.implies_cross_resource {
+                    let (other_namespace, other_role) = qualify(namespace, implied);
+                    push(
+                        graph
+                            .implied_by_ancestor
+                            .entry(other_namespace.to_owned())
+                            .or_default()
+                            .entry(other_role.to_owned())
+                            .or_default()
+                            .entry(namespace.clone())
+                            .or_default(),
+                        role.name.clone(),
+                    );
+                }
+
+                // Walk the local implications from this role; every role
+                // reached is implied by it.
+                let mut seen = HashSet::new();
+                let mut stack = vec![role.name.as_str()];
+                while let Some(implied) = stack.pop() {
+                    if !seen.insert(implied) {
+                        continue;
+                    }
+                    push(
+                        graph
+                            .implied_by
+                            .entry(namespace.clone())
+                            .or_default()
+                            .entry(implied.to_owned())
+                            .or_default(),
+                        role.name.clone(),
+                    );
+                    if let Some(implied_role) = roles.get(implied) {
+                        stack.extend(implied_role.implies.iter().map(String::as_str));
+                    }
+                }
+            }
+        }
+
+        let global_roles = model
+            .global_roles
+            .iter()
+            .map(|role| (role.name.as_str(), role))
+            .collect::<HashMap<_, _>>();
+        for global_role in &model.global_roles {
+            graph.global_roles.push(global_role.name.clone());
+            let mut seen = HashSet::new();
+            let mut stack = vec![global_role.name.as_str()];
+            while let Some(implied) = stack.pop() {
+                if !seen.insert(implied) {
+                    continue;
+                }
+                let role = match global_roles.get(implied) {
+                    Some(role) => role,
+                    None => continue,
+                };
+                for permission in &role.permissions {
+                    let (namespace, action) = qualify(GLOBAL_NAMESPACE, permission);
+                    push(
+                        graph
+                            .global_permissions
+                            .entry(namespace.to_owned())
+                            .or_default()
+                            .entry(action.to_owned())
+                            .or_default(),
+                        global_role.name.clone(),
+                    );
+                }
+                for implied_role in &role.implies_cross_resource {
+                    let (namespace, implied_role) = qualify(GLOBAL_NAMESPACE, implied_role);
+                    push(
+                        graph
+                            .implied_by_global
+                            .entry(namespace.to_owned())
+                            .or_default()
+                            .entry(implied_role.to_owned())
+                            .or_default(),
+                        global_role.name.clone(),
+                    );
+                }
+                stack.extend(role.implies.iter().map(String::as_str));
+            }
+        }
+        graph
+    }
+
+    /// Roles in `role_namespace` that grant `action` on `permission_namespace`.
+    pub fn roles_with_permission(
+        &self,
+        role_namespace: &str,
+        permission_namespace: &str,
+        action: &str,
+    ) -> &[String] {
+        self.permissions
+            .get(role_namespace)
+            .and_then(|p| p.get(permission_namespace))
+            .and_then(|p| p.get(action))
+            .map_or(&[], |roles| &roles[..])
+    }
+
+    /// Roles in `role_namespace` that deny `action` on `action_namespace`.
+    pub fn roles_denying(
+        &self,
+        role_namespace: &str,
+        action_namespace: &str,
+        action: &str,
+    ) -> &[String] {
+        self.denials
+            .get(role_namespace)
+            .and_then(|d| d.get(action_namespace))
+            .and_then(|d| d.get(action))
+            .map_or(&[], |roles| &roles[..])
+    }
+
+    /// Actions denied by any role, so that checking for denials can be
+    /// skipped for all other actions.
+    pub fn denied_actions(&self) -> &[String] {
+        &self.denied_actions
+    }
+
+    /// Roles in `namespace` that imply `role`, including `role` itself.
+    pub fn roles_implying(&self, namespace: &str, role: &str) -> &[String] {
+        self.implied_by
+            .get(namespace)
+            .and_then(|r| r.get(role))
+            .map_or(&[], |roles| &roles[..])
+    }
+
+    /// Roles in `ancestor_namespace` that directly imply `role` in `namespace`.
+    pub fn ancestor_roles_implying(
+        &self,
+        namespace: &str,
+        role: &str,
+        ancestor_namespace: &str,
+    ) -> &[String] {
+        self.implied_by_ancestor
+            .get(namespace)
+            .and_then(|r| r.get(role))
+            .and_then(|r| r.get(ancestor_namespace))
+            .map_or(&[], |roles| &roles[..])
+    }
+
+    /// The names of all global roles.
+    pub fn global_roles(&self) -> &[String] {
+        &self.global_roles
+    }
+
+    /// Global roles that grant `action` on resources in `namespace`.
+    pub fn global_roles_with_permission(&self, namespace: &str, action: &str) -> &[String] {
+        self.global_permissions
+            .get(namespace)
+            .and_then(|p| p.get(action))
+            .map_or(&[], |roles| &roles[..])
+    }
+
+    /// Global roles that imply `role` on every resource in `namespace`.
+    pub fn global_roles_implying(&self, namespace: &str, role: &str) -> &[String] {
+        self.implied_by_global
+            .get(namespace)
+            .and_then(|r| r.get(role))
+            .map_or(&[], |roles| &roles[..])
+    }
+
+    /// Answer a call to one of the role graph predicates, given the values of
+    /// all but its last argument. Each answer is a value for the last argument.
+    pub fn answers(&self, predicate: &str, keys: &[&str]) -> &[String] {
+        match (predicate, keys) {
+            (ROLE_HAS_PERMISSION, [role_namespace, permission_namespace, action]) => {
+                self.roles_with_permission(role_namespace, permission_namespace, action)
+            }
+            (ROLE_DENIES_ACTION, [role_namespace, action_namespace, action]) => {
+                self.roles_denying(role_namespace, action_namespace, action)
+            }
+            (DENIED_ACTION, []) => self.denied_actions(),
+            (ROLE_IMPLIED_BY, [namespace, role]) => self.roles_implying(namespace, role),
+            (ROLE_IMPLIED_BY_ANCESTOR, [namespace, role, ancestor_namespace]) => {
+                self.ancestor_roles_implying(namespace, role, ancestor_namespace)
+            }
+            (GLOBAL_ROLE, []) => self.global_roles(),
+            (GLOBAL_ROLE_HAS_PERMISSION, [namespace, action]) => {
+                self.global_roles_with_permission(namespace, action)
+            }
+            (ROLE_IMPLIED_BY_GLOBAL, [namespace, role]) => {
+                self.global_roles_implying(namespace, role)
+            }
+            _ => &[],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roles_model::{ResourceModel, RoleModel};
+
+    fn resource(name: &str, roles: &[(&str, &[&str], &[&str])]) -> ResourceModel {
+        let strings = |s: &[&str]| s.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        ResourceModel {
+            namespace: name.to_owned(),
+            class_tag: name.to_owned(),
+            actions: vec![],
+            roles: roles
+                .iter()
+                .map(|(role, permissions, implies)| RoleModel {
+                    name: role.to_string(),
+                    permissions: strings(permissions),
+                    deny: vec![],
+                    implies: strings(implies)
+                        .into_iter()
+                        .filter(|i| !i.contains(':'))
+                        .collect(),
+                    implies_cross_resource: strings(implies)
+                        .into_iter()
+                        .filter(|i| i.contains(':'))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    fn graph(resources: Vec<ResourceModel>) -> RoleGraph {
+        RoleGraph::new(&RolesModel {
+            resources,
+            global_roles: vec![],
+            relationships: vec![],
+        })
+    }
+
+    #[test]
+    fn test_role_graph() {
+        let org = resource(
+            "org",
+            &[
+                ("member", &["create_repo"], &["repo:reader"]),
+                ("owner", &["invite"], &["member", "repo:writer"]),
+            ],
+        );
+        let repo = resource(
+            "repo",
+            &[
+                ("reader", &["pull"], &[]),
+                ("writer", &["push", "issue:edit"], &["reader"]),
+            ],
+        );
+        let graph = graph(vec![org, repo]);
+
+        assert_eq!(
+            graph.roles_with_permission("org", "org", "invite"),
+            ["owner"]
+        );
+        assert_eq!(
+            graph.roles_with_permission("repo", "issue", "edit"),
+            ["writer"]
+        );
+        assert!(graph
+            .roles_with_permission("repo", "repo", "edit")
+            .is_empty());
+
+        assert_eq!(graph.roles_implying("org", "member"), ["member", "owner"]);
+        assert_eq!(graph.roles_implying("repo", "reader"), ["reader", "writer"]);
+        assert_eq!(graph.roles_implying("repo", "writer"), ["writer"]);
+
+        assert_eq!(
+            graph.ancestor_roles_implying("repo", "reader", "org"),
+            ["member"]
+        );
+        assert_eq!(
+            graph.answers(ROLE_IMPLIED_BY_ANCESTOR, &["repo", "writer", "org"]),
+            ["owner"]
+        );
+        assert!(graph.answers(ROLE_IMPLIED_BY, &["repo"]).is_empty());
+    }
+
+    #[test]
+    fn test_role_graph_cycle() {
+        let org = resource("org", &[("a", &["x"], &["b"]), ("b", &["y"], &["a"])]);
+        let graph = graph(vec![org]);
+        assert_eq!(graph.roles_implying("org", "a"), ["a", "b"]);
+        assert_eq!(graph.roles_implying("org", "b"), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_role_graph_denials() {
+        let mut repo = resource(
+            "repo",
+            &[("guest", &[], &["reader"]), ("reader", &["pull"], &[])],
+        );
+        repo.roles[0].deny = vec!["delete".to_owned(), "issue:close".to_owned()];
+        let graph = graph(vec![repo]);
+
+        assert_eq!(
+            graph.answers(ROLE_DENIES_ACTION, &["repo", "repo", "delete"]),
+            ["guest"]
+        );
+        assert_eq!(graph.roles_denying("repo", "issue", "close"), ["guest"]);
+        assert!(graph.roles_denying("repo", "repo", "pull").is_empty());
+        assert_eq!(graph.answers(DENIED_ACTION, &[]), ["delete", "close"]);
+    }
+
+    #[test]
+    fn test_role_graph_global_roles() {
+        let org = resource("org", &[("owner", &["invite"], &[])]);
+        let global = resource(
+            GLOBAL_NAMESPACE,
+            &[
+                ("admin", &["org:invite"], &["support"]),
+                ("superadmin", &[], &["admin", "org:owner"]),
+                ("support", &["repo:pull"], &[]),
+            ],
+        );
+        let graph = RoleGraph::new(&RolesModel {
+            resources: vec![org],
+            global_roles: global.roles,
+            relationships: vec![],
+        });
+
+        assert_eq!(
+            graph.answers(GLOBAL_ROLE, &[]),
+            ["admin", "superadmin", "support"]
+        );
+        assert_eq!(
+            graph.global_roles_with_permission("org", "invite"),
+            ["admin", "superadmin"]
+        );
+        assert_eq!(
+            graph.global_roles_with_permission("repo", "pull"),
+            ["admin", "superadmin", "support"]
+        );
+        assert_eq!(graph.global_roles_implying("org", "owner"), ["superadmin"]);
+        assert!(graph
+            .roles_with_permission(GLOBAL_NAMESPACE, "org", "invite")
+            .is_empty());
+    }
+}
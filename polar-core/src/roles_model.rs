@@ -0,0 +1,339 @@
+//! The roles configuration as data, for review outside of Polar.
+//!
+//! After the roles configuration is validated, `Polar::roles_model` returns
+//! its resources, global roles and `parent_child` relationships, which render
+//! as JSON or as a Graphviz DOT graph.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::error::{ParameterError, PolarError};
+use super::roles_validation::Resource;
+
+/// The reserved resource name under which global roles are declared, e.g.
+/// `resource(_, "global", [], roles) if roles = {superadmin: {...}};`
+pub const GLOBAL_NAMESPACE: &str = "global";
+
+/// How to render a `RolesModel`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RolesModelFormat {
+    Json,
+    Dot,
+}
+
+impl FromStr for RolesModelFormat {
+    type Err = PolarError;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "json" => Ok(Self::Json),
+            "dot" => Ok(Self::Dot),
+            _ => Err(ParameterError(format!(
+                "unknown roles model format {}, expected json or dot",
+                format
+            ))
+            .into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RolesModel {
+    /// Resources sorted by namespace.
+    pub resources: Vec<ResourceModel>,
+    /// Roles an actor has independently of any resource, sorted by name.
+    /// Their permissions and cross-resource implications apply to every
+    /// resource in the namespace they name.
+    pub global_roles: Vec<RoleModel>,
+    /// Relationships between resources declared with `parent_child` rules.
+    pub relationships: Vec<ParentChild>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ResourceModel {
+    pub namespace: String,
+    pub class_tag: String,
+    pub actions: Vec<String>,
+    /// Roles sorted by name.
+    pub roles: Vec<RoleModel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RoleModel {
+    pub name: String,
+    /// Actions on this resource, or `namespace:action` for actions on another resource.
+    pub permissions: Vec<String>,
+    /// Actions denied by this role, in the same form as `permissions`. A
+    /// denial overrides the permissions of every role the actor has.
+    pub deny: Vec<String>,
+    /// Roles on the same resource implied by this role.
+    pub implies: Vec<String>,
+    /// Roles on other resources implied by this role, as `namespace:role`.
+    pub implies_cross_resource: Vec<String>,
+}
+
+/// A `parent_child` rule relating resources in the `parent` and `child` namespaces.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
+pub struct ParentChild {
+    pub parent: String,
+    pub child: String,
+}
+
+/// Quote a string for a DOT file.
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A role's name, permissions and denied actions, one per line.
+fn role_label(role: &RoleModel) -> String {
+    let mut label = role.name.clone();
+    if !role.permissions.is_empty() {
+        write!(label, "\npermissions: {}", role.permissions.join(", ")).unwrap();
+    }
+    if !role.deny.is_empty() {
+        write!(label, "\ndeny: {}", role.deny.join(", ")).unwrap();
+    }
+    label
+}
+
+impl RolesModel {
+    pub(crate) fn new(
+        resources: &HashMap<String, Resource>,
+        relationships: Vec<ParentChild>,
+    ) -> Self {
+        let roles = |resource: &Resource| {
+            let mut roles = resource
+                .roles
+                .values()
+                .map(|role| {
+                    let (implies_cross_resource, implies) = role
+                        .implied_roles
+                        .iter()
+                        .cloned()
+                        .partition(|implied| implied.contains(':'));
+                    RoleModel {
+                        name: role.name.clone(),
+                        permissions: role.actions.clone(),
+                        deny: role.denied_actions.clone(),
+                        implies,
+                        implies_cross_resource,
+                    }
+                })
+                .collect::<Vec<_>>();
+            roles.sort_by(|a, b| a.name.cmp(&b.name));
+            roles
+        };
+        let global_roles = resources
+            .get(GLOBAL_NAMESPACE)
+            .map(roles)
+            .unwrap_or_default();
+        let mut resources = resources
+            .values()
+            .filter(|resource| resource.name != GLOBAL_NAMESPACE)
+            .map(|resource| ResourceModel {
+                namespace: resource.name.clone(),
+                class_tag: resource.typ.clone(),
+                actions: resource.actions.clone(),
+                roles: roles(resource),
+            })
+            .collect::<Vec<_>>();
+        resources.sort_by(|a, b| a.namespace.cmp(&b.namespace));
+        Self {
+            resources,
+            global_roles,
+            relationships,
+        }
+    }
+
+    pub fn render(&self, format: RolesModelFormat) -> String {
+        match format {
+            RolesModelFormat::Json => self.to_json(),
+            RolesModelFormat::Dot => self.to_dot(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Each resource is a cluster holding a node for the resource and a node
+    /// for each of its roles, and global roles are a cluster of their own.
+    /// Local implications are solid edges between roles, cross-resource
+    /// implications are dashed, and `parent_child` relationships are bold
+    /// edges between resources.
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph roles_model {\n".to_owned();
+        for (i, resource) in self.resources.iter().enumerate() {
+            let namespace = &resource.namespace;
+            writeln!(dot, "  subgraph cluster_{} {{", i).unwrap();
+            writeln!(
+                dot,
+                "    label={};",
+                dot_quote(&format!("{} ({})", namespace, resource.class_tag))
+            )
+            .unwrap();
+            let label = format!("{}\nactions: {}", namespace, resource.actions.join(", "));
+            writeln!(
+                dot,
+                "    {} [shape=folder, label={}];",
+                dot_quote(namespace),
+                dot_quote(&label).replace('\n', "\\n")
+            )
+            .unwrap();
+            for role in &resource.roles {
+                let label = role_label(role);
+                writeln!(
+                    dot,
+                    "    {} [shape=box, label={}];",
+                    dot_quote(&format!("{}:{}", namespace, role.name)),
+                    dot_quote(&label).replace('\n', "\\n")
+                )
+                .unwrap();
+            }
+            dot.push_str("  }\n");
+        }
+        if !self.global_roles.is_empty() {
+            dot.push_str("  subgraph cluster_global {\n    label=\"global roles\";\n");
+            for role in &self.global_roles {
+                let label = role_label(role);
+                writeln!(
+                    dot,
+                    "    {} [shape=box, style=rounded, label={}];",
+                    dot_quote(&format!("{}:{}", GLOBAL_NAMESPACE, role.name)),
+                    dot_quote(&label).replace('\n', "\\n")
+                )
+                .unwrap();
+            }
+            dot.push_str("  }\n");
+        }
+        let global = ResourceModel {
+            namespace: GLOBAL_NAMESPACE.to_owned(),
+            class_tag: String::new(),
+            actions: vec![],
+            roles: self.global_roles.clone(),
+        };
+        for resource in self.resources.iter().chain(Some(&global)) {
+            for role in &resource.roles {
+                let from = dot_quote(&format!("{}:{}", resource.namespace, role.name));
+                for implied in &role.implies {
+                    let to = dot_quote(&format!("{}:{}", resource.namespace, implied));
+                    writeln!(dot, "  {} -> {} [label=\"implies\"];", from, to).unwrap();
+                }
+                for implied in &role.implies_cross_resource {
+                    writeln!(
+                        dot,
+                        "  {} -> {} [label=\"implies\", style=dashed];",
+                        from,
+                        dot_quote(implied)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        for relationship in &self.relationships {
+            writeln!(
+                dot,
+                "  {} -> {} [label=\"parent_child\", style=bold];",
+                dot_quote(&relationship.parent),
+                dot_quote(&relationship.child)
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn model() -> RolesModel {
+        RolesModel {
+            resources: vec![
+                ResourceModel {
+                    namespace: "org".to_owned(),
+                    class_tag: "Org".to_owned(),
+                    actions: vec!["invite".to_owned()],
+                    roles: vec![RoleModel {
+                        name: "owner".to_owned(),
+                        permissions: vec!["invite".to_owned()],
+                        deny: vec![],
+                        implies: vec![],
+                        implies_cross_resource: vec!["repo:reader".to_owned()],
+                    }],
+                },
+                ResourceModel {
+                    namespace: "repo".to_owned(),
+                    class_tag: "Repo".to_owned(),
+                    actions: vec!["pull".to_owned(), "push".to_owned()],
+                    roles: vec![
+                        RoleModel {
+                            name: "reader".to_owned(),
+                            permissions: vec!["pull".to_owned()],
+                            deny: vec!["push".to_owned()],
+                            implies: vec![],
+                            implies_cross_resource: vec![],
+                        },
+                        RoleModel {
+                            name: "writer".to_owned(),
+                            permissions: vec!["push".to_owned()],
+                            deny: vec![],
+                            implies: vec!["reader".to_owned()],
+                            implies_cross_resource: vec![],
+                        },
+                    ],
+                },
+            ],
+            global_roles: vec![RoleModel {
+                name: "superadmin".to_owned(),
+                permissions: vec!["repo:push".to_owned()],
+                deny: vec![],
+                implies: vec![],
+                implies_cross_resource: vec!["org:owner".to_owned()],
+            }],
+            relationships: vec![ParentChild {
+                parent: "org".to_owned(),
+                child: "repo".to_owned(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_roles_model_json() {
+        let model = model();
+        let json = model.render("json".parse().unwrap());
+        let parsed: RolesModel = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, model);
+    }
+
+    #[test]
+    fn test_roles_model_dot() {
+        let dot = model().render(RolesModelFormat::Dot);
+        assert!(dot.starts_with("digraph roles_model {\n"));
+        assert!(dot.contains(r#"    label="repo (Repo)";"#));
+        assert!(dot.contains(r#""repo" [shape=folder, label="repo\nactions: pull, push"];"#));
+        assert!(dot.contains(r#""repo:writer" [shape=box, label="writer\npermissions: push"];"#));
+        assert!(dot.contains(
+            r#""repo:reader" [shape=box, label="reader\npermissions: pull\ndeny: push"];"#
+        ));
+        assert!(dot.contains(r#""repo:writer" -> "repo:reader" [label="implies"];"#));
+        assert!(dot.contains(r#""org:owner" -> "repo:reader" [label="implies", style=dashed];"#));
+        assert!(dot.contains(r#""org" -> "repo" [label="parent_child", style=bold];"#));
+        assert!(dot.contains(
+            r#""global:superadmin" [shape=box, style=rounded, label="superadmin\npermissions: repo:push"];"#
+        ));
+        assert!(
+            dot.contains(r#""global:superadmin" -> "org:owner" [label="implies", style=dashed];"#)
+        );
+    }
+
+    #[test]
+    fn test_roles_model_format() {
+        let err = "yaml".parse::<RolesModelFormat>().unwrap_err();
+        assert!(err.to_string().contains("unknown roles model format yaml"));
+    }
+}
@@ -1,7 +1,10 @@
 #![allow(dead_code)]
 
-use super::error::{PolarResult, RolesValidationError};
+use super::error::{PolarError, PolarResult, RolesValidationError};
 use super::events::ResultEvent;
+use super::kb::KnowledgeBase;
+use super::role_graph::qualify;
+use super::roles_model::{ParentChild, RolesModel, GLOBAL_NAMESPACE};
 use super::rules::GenericRule;
 use super::terms::*;
 
@@ -12,18 +15,19 @@ struct Action {
     name: String,
 }
 
-struct Role {
-    name: String,
-    typ: String,
-    actions: Vec<String>,
-    implied_roles: Vec<String>,
+pub(crate) struct Role {
+    pub name: String,
+    pub typ: String,
+    pub actions: Vec<String>,
+    pub denied_actions: Vec<String>,
+    pub implied_roles: Vec<String>,
 }
 
-struct Resource {
-    typ: String,
-    name: String,
-    actions: Vec<String>,
-    roles: HashMap<String, Role>,
+pub(crate) struct Resource {
+    pub typ: String,
+    pub name: String,
+    pub actions: Vec<String>,
+    pub roles: HashMap<String, Role>,
 }
 
 pub const VALIDATE_ROLES_CONFIG_RESOURCES: &str = "resource(resource, name, actions, roles)";
@@ -55,11 +59,231 @@ pub fn validate_actor_has_role_for_resource(
     Ok(())
 }
 
+/// Global roles are assigned to actors by `actor_has_global_role`.
+fn validate_actor_has_global_role(kb: &KnowledgeBase, global: &Resource) -> PolarResult<()> {
+    let defined = kb
+        .get_generic_rule(&sym!("actor_has_global_role"))
+        .is_some_and(|rule| rule.rules.values().any(|r| r.params.len() == 2));
+    if defined {
+        Ok(())
+    } else {
+        Err(resource_error(
+            kb,
+            global,
+            "Need to define `actor_has_global_role(actor, role_name)` predicate to use global roles."
+                .to_owned(),
+        ))
+    }
+}
+
+/// The term to report errors about `resource` at: the namespace in the head
+/// of the last `resource` rule defining it, or failing that its specializer.
+fn resource_rule_term(kb: &KnowledgeBase, resource: &Resource) -> Option<Term> {
+    let generic_rule = kb.get_generic_rule(&sym!("resource"))?;
+    let mut rules = generic_rule
+        .rules
+        .iter()
+        .filter(|(_, rule)| rule.params.len() == 4)
+        .collect::<Vec<_>>();
+    rules.sort_by_key(|(id, _)| **id);
+
+    let has_name = |params: &[crate::rules::Parameter]| matches!(params[1].parameter.value(), Value::String(name) if name == &resource.name);
+    let has_type = |params: &[crate::rules::Parameter]| {
+        matches!(
+            params[0].specializer.as_ref().map(Term::value),
+            Some(Value::Pattern(Pattern::Instance(InstanceLiteral { tag, .. }))) if tag.0 == resource.typ
+        )
+    };
+    rules
+        .iter()
+        .rev()
+        .find(|(_, rule)| has_name(&rule.params) && has_type(&rule.params))
+        .or_else(|| rules.iter().rev().find(|(_, rule)| has_name(&rule.params)))
+        .map(|(_, rule)| rule.params[1].parameter.clone())
+        .or_else(|| {
+            rules
+                .iter()
+                .rev()
+                .find(|(_, rule)| has_type(&rule.params))
+                .and_then(|(_, rule)| rule.params[0].specializer.clone())
+        })
+}
+
+fn resource_error(kb: &KnowledgeBase, resource: &Resource, msg: String) -> PolarError {
+    match resource_rule_term(kb, resource) {
+        Some(term) => kb.set_error_context(&term, RolesValidationError(msg)),
+        None => RolesValidationError(msg).into(),
+    }
+}
+
+fn sorted<T>(map: &HashMap<String, T>) -> Vec<(&String, &T)> {
+    let mut entries = map.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|(key, _)| *key);
+    entries
+}
+
+/// Check that every permission and denied action is an action on its resource
+/// and that every implied role is defined.
+fn validate_role_references(
+    kb: &KnowledgeBase,
+    resources: &HashMap<String, Resource>,
+) -> PolarResult<()> {
+    for (namespace, resource) in sorted(resources) {
+        for (role_name, role) in sorted(&resource.roles) {
+            for permission in &role.actions {
+                let (permission_namespace, action) = qualify(namespace, permission);
+                let msg = match resources.get(permission_namespace) {
+                    _ if permission_namespace == GLOBAL_NAMESPACE => format!(
+                        "Role {} on {} has permission {}, but permissions of global roles must name a resource, like repo:{}.",
+                        role_name, namespace, permission, action
+                    ),
+                    None => format!(
+                        "Role {} on {} has permission {}, but there is no resource named {}.",
+                        role_name, namespace, permission, permission_namespace
+                    ),
+                    Some(other) if !other.actions.iter().any(|a| a == action) => format!(
+                        "Role {} on {} has permission {}, which is not an action on {}.",
+                        role_name, namespace, permission, permission_namespace
+                    ),
+                    Some(_) => continue,
+                };
+                return Err(resource_error(kb, resource, msg));
+            }
+            for denied in &role.denied_actions {
+                let (denied_namespace, action) = qualify(namespace, denied);
+                let msg = match resources.get(denied_namespace) {
+                    _ if namespace == GLOBAL_NAMESPACE => format!(
+                        "Role {} on {} denies {}, but global roles can't deny actions.",
+                        role_name, namespace, denied
+                    ),
+                    _ if role.actions.contains(denied) => format!(
+                        "Role {} on {} both has permission {} and denies it.",
+                        role_name, namespace, denied
+                    ),
+                    None => format!(
+                        "Role {} on {} denies {}, but there is no resource named {}.",
+                        role_name, namespace, denied, denied_namespace
+                    ),
+                    Some(other) if !other.actions.iter().any(|a| a == action) => format!(
+                        "Role {} on {} denies {}, which is not an action on {}.",
+                        role_name, namespace, denied, denied_namespace
+                    ),
+                    Some(_) => continue,
+                };
+                return Err(resource_error(kb, resource, msg));
+            }
+            for implied in &role.implied_roles {
+                let (implied_namespace, implied_role) = qualify(namespace, implied);
+                let msg = match resources.get(implied_namespace) {
+                    _ if implied_namespace == GLOBAL_NAMESPACE && namespace != GLOBAL_NAMESPACE => {
+                        format!(
+                            "Role {} on {} implies {}, but global roles can only be implied by global roles.",
+                            role_name, namespace, implied
+                        )
+                    }
+                    None => format!(
+                        "Role {} on {} implies {}, but there is no resource named {}.",
+                        role_name, namespace, implied, implied_namespace
+                    ),
+                    Some(other) if !other.roles.contains_key(implied_role) => format!(
+                        "Role {} on {} implies {}, which is not a role on {}.",
+                        role_name, namespace, implied, implied_namespace
+                    ),
+                    Some(_) => continue,
+                };
+                return Err(resource_error(kb, resource, msg));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find a cycle of role implications, as a path of `(namespace, role)`
+/// pairs that starts and ends with the same role.
+fn find_implication_cycle(resources: &HashMap<String, Resource>) -> Option<Vec<(&str, &str)>> {
+    fn visit<'a>(
+        resources: &'a HashMap<String, Resource>,
+        node: (&'a str, &'a str),
+        path: &mut Vec<(&'a str, &'a str)>,
+        done: &mut HashSet<(&'a str, &'a str)>,
+    ) -> Option<Vec<(&'a str, &'a str)>> {
+        if let Some(start) = path.iter().position(|n| *n == node) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(node);
+            return Some(cycle);
+        }
+        if done.contains(&node) {
+            return None;
+        }
+        path.push(node);
+        let (namespace, role_name) = node;
+        if let Some(role) = resources
+            .get(namespace)
+            .and_then(|resource| resource.roles.get(role_name))
+        {
+            for implied in &role.implied_roles {
+                let next = qualify(namespace, implied);
+                if let Some(cycle) = visit(resources, next, path, done) {
+                    return Some(cycle);
+                }
+            }
+        }
+        path.pop();
+        done.insert(node);
+        None
+    }
+
+    let mut done = HashSet::new();
+    for (namespace, resource) in sorted(resources) {
+        for (role_name, _) in sorted(&resource.roles) {
+            let node = (namespace.as_str(), role_name.as_str());
+            if let Some(cycle) = visit(resources, node, &mut vec![], &mut done) {
+                return Some(cycle);
+            }
+        }
+    }
+    None
+}
+
+/// The namespaces related by `parent_child` rules whose parameters are both
+/// specialized on resource classes.
+fn parent_child_relationships(
+    kb: &KnowledgeBase,
+    resources: &HashMap<String, Resource>,
+) -> Vec<ParentChild> {
+    let namespace = |param: &crate::rules::Parameter| match param.specializer.as_ref()?.value() {
+        Value::Pattern(Pattern::Instance(InstanceLiteral { tag, .. })) => resources
+            .values()
+            .find(|resource| resource.typ == tag.0)
+            .map(|resource| resource.name.clone()),
+        _ => None,
+    };
+    let mut relationships = kb
+        .get_generic_rule(&sym!("parent_child"))
+        .map(|generic_rule| {
+            generic_rule
+                .rules
+                .values()
+                .filter(|rule| rule.params.len() == 2)
+                .filter_map(|rule| {
+                    Some(ParentChild {
+                        parent: namespace(&rule.params[0])?,
+                        child: namespace(&rule.params[1])?,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    relationships.sort();
+    relationships.dedup();
+    relationships
+}
+
 pub fn validate_roles_config(
-    rules: &HashMap<Symbol, GenericRule>,
+    kb: &KnowledgeBase,
     roles_config: Vec<Vec<ResultEvent>>,
-) -> PolarResult<()> {
-    validate_actor_has_role_for_resource(rules)?;
+) -> PolarResult<RolesModel> {
+    validate_actor_has_role_for_resource(kb.get_rules())?;
     let role_resources = roles_config.first().ok_or_else(|| {
         // TODO: add link to docs in error message
         RolesValidationError(
@@ -88,7 +312,29 @@ pub fn validate_roles_config(
             .value();
         let resource_roles = result.bindings.get(&Symbol::new("roles")).unwrap().value();
 
-        let typ = {
+        let name = {
+            if let Value::String(name) = resource_name {
+                name.clone()
+            } else {
+                return Err(RolesValidationError(
+                    "Invalid resource, name is not a string.".to_owned(),
+                )
+                .into());
+            }
+        };
+
+        let typ = if name == GLOBAL_NAMESPACE {
+            // Global roles aren't scoped to a resource, so the global
+            // resource has no type.
+            if !matches!(resource_def, Value::Variable(_)) {
+                return Err(RolesValidationError(format!(
+                    "The {} resource can't have a type specializer, declare it with `resource(_, \"{0}\", [], roles)`.",
+                    GLOBAL_NAMESPACE
+                ))
+                .into());
+            }
+            String::new()
+        } else {
             if let Value::Expression(Operation {
                 operator: Operator::And,
                 args: and_args,
@@ -158,17 +404,6 @@ pub fn validate_roles_config(
             }
         };
 
-        let name = {
-            if let Value::String(name) = resource_name {
-                name.clone()
-            } else {
-                return Err(RolesValidationError(
-                    "Invalid resource, name is not a string.".to_owned(),
-                )
-                .into());
-            }
-        };
-
         let actions: Vec<String> = {
             let mut action_strings = vec![];
             match resource_actions {
@@ -208,7 +443,7 @@ pub fn validate_roles_config(
                 let role_name = name_sym.0.clone();
                 if let Value::Dictionary(Dictionary { fields: def_dict }) = definition.value() {
                     for key in def_dict.keys() {
-                        if key.0 != "permissions" && key.0 != "implies" {
+                        if key.0 != "permissions" && key.0 != "deny" && key.0 != "implies" {
                             return Err(RolesValidationError(format!(
                                 "Role definition contains invalid key: {}",
                                 key.0
@@ -244,6 +479,34 @@ pub fn validate_roles_config(
                             vec![]
                         }
                     };
+                    let denied_actions = {
+                        let denied_value = def_dict.get(&Symbol::new("deny"));
+                        if let Some(denied_term) = denied_value {
+                            if let Value::List(denied_list) = denied_term.value() {
+                                let mut denied_actions = vec![];
+                                for denied_term in denied_list {
+                                    if let Value::String(action) = denied_term.value() {
+                                        denied_actions.push(action.clone())
+                                    } else {
+                                        return Err(RolesValidationError(format!(
+                                            "Invalid deny for role {}, must be a string.",
+                                            role_name
+                                        ))
+                                        .into());
+                                    }
+                                }
+                                denied_actions
+                            } else {
+                                return Err(RolesValidationError(format!(
+                                    "Invalid deny for role {}",
+                                    role_name
+                                ))
+                                .into());
+                            }
+                        } else {
+                            vec![]
+                        }
+                    };
                     let implications = {
                         let implications_value = def_dict.get(&Symbol::new("implies"));
                         if let Some(implications_term) = implications_value {
@@ -272,7 +535,7 @@ pub fn validate_roles_config(
                             vec![]
                         }
                     };
-                    if actions.is_empty() && implications.is_empty() {
+                    if actions.is_empty() && denied_actions.is_empty() && implications.is_empty() {
                         return Err(RolesValidationError(
                             "Must define actions or implications for a role.".to_owned(),
                         )
@@ -282,6 +545,7 @@ pub fn validate_roles_config(
                         name: role_name.clone(),
                         typ: typ.clone(),
                         actions,
+                        denied_actions,
                         implied_roles: implications,
                     };
                     if role_definitions.contains_key(&role_name) {
@@ -298,6 +562,14 @@ pub fn validate_roles_config(
             }
         }
 
+        if name == GLOBAL_NAMESPACE && !actions.is_empty() {
+            return Err(RolesValidationError(format!(
+                "The {} resource can't have actions, give global roles permissions on other resources instead.",
+                GLOBAL_NAMESPACE
+            ))
+            .into());
+        }
+
         if actions.is_empty() && role_definitions.is_empty() {
             return Err(RolesValidationError("Must define actions or roles.".to_owned()).into());
         }
@@ -309,10 +581,33 @@ pub fn validate_roles_config(
             roles: role_definitions,
         };
         if resources.contains_key(&name) {
-            return Err(RolesValidationError(format!("Duplicate resource name {}.", name)).into());
+            return Err(resource_error(
+                kb,
+                &resource,
+                format!("Duplicate resource name {}.", name),
+            ));
         }
         resources.insert(name, resource);
     }
 
-    Ok(())
+    validate_role_references(kb, &resources)?;
+    if let Some(global) = resources.get(GLOBAL_NAMESPACE) {
+        validate_actor_has_global_role(kb, global)?;
+    }
+    if let Some(cycle) = find_implication_cycle(&resources) {
+        let (namespace, _) = cycle[0];
+        let path = cycle
+            .iter()
+            .map(|(namespace, role)| format!("{}:{}", namespace, role))
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        return Err(resource_error(
+            kb,
+            &resources[namespace],
+            format!("Role implications form a cycle: {}.", path),
+        ));
+    }
+
+    let relationships = parent_child_relationships(kb, &resources);
+    Ok(RolesModel::new(&resources, relationships))
 }
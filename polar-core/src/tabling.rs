@@ -0,0 +1,246 @@
+//! Tabled evaluation of rules declared with `type tabled`.
+//!
+//! Calls to a tabled rule are answered from a table kept for each call variant
+//! (the call's arguments, up to the names of their variables) for the length
+//! of a query. Application instances in arguments are compared by instance
+//! ID. The first call to a variant is evaluated by a `Tabler` in a sub-VM,
+//! which records every answer in the table. A recursive call to a variant that
+//! is still being evaluated is answered with the answers found so far, and the
+//! evaluation is repeated until it finds no new answers. So left recursion and
+//! cyclic data, which make plain evaluation loop, terminate, and each variant
+//! is evaluated once per query.
+//!
+//! A variant whose evaluation used the answers of a variant further up the
+//! stack can't be complete before that variant is. Its table is left
+//! incomplete, to be evaluated again when it is called next, and is completed
+//! along with the variant it depends on.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::counter::Counter;
+use crate::error::{PolarError, PolarResult};
+use crate::events::QueryEvent;
+use crate::folder::Folder;
+use crate::runnable::Runnable;
+use crate::terms::{Symbol, Term, TermList};
+use crate::vm::PolarVirtualMachine;
+
+/// Rename the variables in `args` in order of appearance, so that calls and
+/// answers that only differ in the names of their variables are the same.
+pub fn variant(args: &[Term]) -> TermList {
+    struct VariantRenamer(HashMap<Symbol, Symbol>);
+
+    impl Folder for VariantRenamer {
+        fn fold_variable(&mut self, v: Symbol) -> Symbol {
+            let n = self.0.len();
+            self.0
+                .entry(v)
+                .or_insert_with(|| Symbol(format!("_{}", n)))
+                .clone()
+        }
+    }
+
+    let mut renamer = VariantRenamer(HashMap::new());
+    args.iter()
+        .map(|arg| renamer.fold_term(arg.clone()))
+        .collect()
+}
+
+#[derive(Default)]
+struct Table {
+    answers: Vec<TermList>,
+    /// Variants of `answers`, to skip duplicates.
+    seen: HashSet<TermList>,
+    complete: bool,
+}
+
+/// A variant being evaluated.
+struct Frame {
+    variant: TermList,
+    /// The lowest stack index of an incomplete variant whose answers were
+    /// used by this evaluation, if any.
+    low: Option<usize>,
+    /// Variants evaluated during this one that can only be complete once it is.
+    dependents: Vec<TermList>,
+}
+
+impl Frame {
+    fn depend_on(&mut self, index: usize) {
+        self.low = Some(self.low.map_or(index, |low| low.min(index)));
+    }
+}
+
+/// The tables of a query, shared by its VM and sub-VMs.
+#[derive(Default)]
+pub struct Tables {
+    tables: HashMap<TermList, Table>,
+    stack: Vec<Frame>,
+    /// The number of answers in all tables.
+    answer_count: usize,
+}
+
+impl Tables {
+    /// Whether a call to `variant` can be answered from its table: if the
+    /// table is complete, or if the call is recursive. Otherwise the variant
+    /// must be evaluated first.
+    pub fn can_answer(&mut self, variant: &TermList) -> bool {
+        match self.tables.get(variant) {
+            Some(table) if table.complete => true,
+            Some(_) => match self
+                .stack
+                .iter()
+                .position(|frame| &frame.variant == variant)
+            {
+                Some(index) => {
+                    self.stack.last_mut().unwrap().depend_on(index);
+                    true
+                }
+                None => false,
+            },
+            None => false,
+        }
+    }
+
+    pub fn answers(&self, variant: &TermList) -> Vec<TermList> {
+        self.tables
+            .get(variant)
+            .map(|table| table.answers.clone())
+            .unwrap_or_default()
+    }
+
+    fn push(&mut self, variant: TermList) {
+        self.tables.entry(variant.clone()).or_default();
+        self.stack.push(Frame {
+            variant,
+            low: None,
+            dependents: vec![],
+        });
+    }
+
+    fn add_answer(&mut self, variant: &TermList, answer: TermList) {
+        let table = self.tables.get_mut(variant).expect("no table for answer");
+        if table.seen.insert(self::variant(&answer)) {
+            table.answers.push(answer);
+            self.answer_count += 1;
+        }
+    }
+
+    /// Whether the evaluation on top of the stack must be repeated, because it
+    /// used answers from an incomplete table and there have been new answers
+    /// since `answer_count`.
+    fn must_repeat(&self, answer_count: usize) -> bool {
+        self.answer_count != answer_count
+            && self.stack.last().is_some_and(|frame| frame.low.is_some())
+    }
+
+    /// Finish the evaluation on top of the stack, completing its table and
+    /// those of its dependents unless it depends on a variant further up.
+    fn pop(&mut self) {
+        let frame = self.stack.pop().expect("no variant being evaluated");
+        let index = self.stack.len();
+        match (frame.low, self.stack.last_mut()) {
+            (Some(low), Some(parent)) if low < index => {
+                parent.depend_on(low);
+                parent.dependents.push(frame.variant);
+                parent.dependents.extend(frame.dependents);
+            }
+            _ => {
+                for variant in std::iter::once(frame.variant).chain(frame.dependents) {
+                    if let Some(table) = self.tables.get_mut(&variant) {
+                        table.complete = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A `Runnable` that evaluates a call to a tabled rule in `vm` until its
+/// table reaches a fixpoint, recording every answer for `args`.
+#[derive(Clone)]
+pub struct Tabler {
+    /// The sub-VM as it was before evaluation, to repeat it from.
+    start: PolarVirtualMachine,
+    vm: PolarVirtualMachine,
+    args: TermList,
+    variant: TermList,
+    tables: Rc<RefCell<Tables>>,
+    /// The number of answers in all tables when the current evaluation
+    /// started. Initialized in `run`.
+    answer_count: Option<usize>,
+}
+
+impl Tabler {
+    pub fn new(
+        vm: PolarVirtualMachine,
+        args: TermList,
+        variant: TermList,
+        tables: Rc<RefCell<Tables>>,
+    ) -> Self {
+        Self {
+            start: vm.clone(),
+            vm,
+            args,
+            variant,
+            tables,
+            answer_count: None,
+        }
+    }
+}
+
+impl Runnable for Tabler {
+    fn run(&mut self, _: Option<&mut Counter>) -> PolarResult<QueryEvent> {
+        if self.answer_count.is_none() {
+            let mut tables = self.tables.borrow_mut();
+            tables.push(self.variant.clone());
+            self.answer_count = Some(tables.answer_count);
+        }
+
+        loop {
+            // Pass most events through, but record results in the table.
+            match self.vm.run(None)? {
+                QueryEvent::Result { .. } => {
+                    let answer = self
+                        .args
+                        .iter()
+                        .map(|arg| self.vm.deep_deref(arg))
+                        .collect();
+                    self.tables.borrow_mut().add_answer(&self.variant, answer);
+                }
+                QueryEvent::Done { .. } => {
+                    let mut tables = self.tables.borrow_mut();
+                    if tables.must_repeat(self.answer_count.unwrap()) {
+                        self.answer_count = Some(tables.answer_count);
+                        self.vm = self.start.clone();
+                    } else {
+                        tables.pop();
+                        return Ok(QueryEvent::Done { result: true });
+                    }
+                }
+                event => return Ok(event),
+            }
+        }
+    }
+
+    fn external_question_result(&mut self, call_id: u64, answer: bool) -> PolarResult<()> {
+        self.vm.external_question_result(call_id, answer)
+    }
+
+    fn external_call_result(&mut self, call_id: u64, term: Option<Term>) -> PolarResult<()> {
+        self.vm.external_call_result(call_id, term)
+    }
+
+    fn debug_command(&mut self, command: &str) -> PolarResult<()> {
+        self.vm.debug_command(command)
+    }
+
+    fn clone_runnable(&self) -> Box<dyn Runnable> {
+        Box::new(self.clone())
+    }
+
+    fn handle_error(&mut self, error: PolarError) -> PolarResult<QueryEvent> {
+        self.vm.handle_error(error)
+    }
+}
@@ -111,6 +111,12 @@ pub enum Operator {
     Or,
     And,
     ForAll,
+    IfThenElse,
+    FindAll,
+    Count,
+    Sum,
+    Min,
+    Max,
     Assign,
 }
 
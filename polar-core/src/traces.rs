@@ -1,7 +1,6 @@
 use super::rules::*;
 use super::terms::*;
 use serde::{Deserialize, Serialize};
-use std::rc::Rc;
 use std::sync::Arc;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -13,7 +12,7 @@ pub enum Node {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Trace {
     pub node: Node,
-    pub children: Vec<Rc<Trace>>,
+    pub children: Vec<Arc<Trace>>,
 }
 
 impl Trace {
@@ -28,6 +27,6 @@ impl Trace {
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TraceResult {
-    pub trace: Rc<Trace>,
+    pub trace: Arc<Trace>,
     pub formatted: String,
 }
@@ -10,6 +10,7 @@ use std::sync::{Arc, RwLock};
 use wasm_bindgen::prelude::*;
 
 use super::visitor::{walk_term, Visitor};
+use crate::aggregate::Collector;
 use crate::bindings::{BindingManager, BindingStack, Bindings, Bsp, FollowerId, VariableState};
 use crate::counter::Counter;
 use crate::debugger::{DebugEvent, Debugger};
@@ -24,9 +25,11 @@ use crate::messages::*;
 use crate::numerics::*;
 use crate::partial::{simplify_bindings, simplify_partial, sub_this, IsaConstraintCheck};
 use crate::rewrites::Renamer;
+use crate::role_graph::is_role_graph_predicate;
 use crate::rules::*;
 use crate::runnable::Runnable;
 use crate::sources::*;
+use crate::tabling::{self, Tabler, Tables};
 use crate::terms::*;
 use crate::traces::*;
 
@@ -41,6 +44,13 @@ pub enum Goal {
     Cut {
         choice_index: usize, // cuts all choices in range [choice_index..]
     },
+    /// Prune the alternative of an if-then-else whose condition succeeded.
+    SoftCut {
+        choice_id: u64, // the choice holding the alternative
+        condition: Term,
+        alternative: Term,
+        negated_consequent: Option<Term>, // the consequent of a negated if-then-else
+    },
     Debug {
         message: String,
     },
@@ -105,7 +115,7 @@ pub enum Goal {
         inner: usize,
     },
     TraceRule {
-        trace: Rc<Trace>,
+        trace: Arc<Trace>,
     },
     TraceStackPush,
     TraceStackPop,
@@ -119,6 +129,20 @@ pub enum Goal {
         runnable: Box<dyn Runnable>,
     },
 
+    /// Unify `args` with each answer in the table for `variant`.
+    TableAnswers {
+        args: TermList,
+        variant: TermList,
+    },
+
+    /// Combine the `values` collected for an aggregate and unify the
+    /// aggregated value with `result`.
+    Aggregate {
+        term: Term,
+        values: Rc<RefCell<TermList>>,
+        result: Term,
+    },
+
     /// Add a new constraint
     AddConstraint {
         term: Term,
@@ -134,17 +158,18 @@ pub enum Goal {
 #[derive(Clone, Debug)]
 pub struct Choice {
     pub alternatives: Vec<GoalStack>,
-    bsp: Bsp,              // binding stack pointer
-    pub goals: GoalStack,  // goal stack snapshot
-    queries: Queries,      // query stack snapshot
-    trace: Vec<Rc<Trace>>, // trace snapshot
+    bsp: Bsp,               // binding stack pointer
+    pub goals: GoalStack,   // goal stack snapshot
+    queries: Queries,       // query stack snapshot
+    trace: Vec<Arc<Trace>>, // trace snapshot
     trace_stack: TraceStack,
+    id: u64, // identifies the choice even if choices below it are cut
 }
 
 pub type Choices = Vec<Choice>;
 /// Shortcut type alias for a list of goals
 pub type Goals = Vec<Goal>;
-pub type TraceStack = Vec<Rc<Vec<Rc<Trace>>>>;
+pub type TraceStack = Vec<Arc<Vec<Arc<Trace>>>>;
 
 #[derive(Clone, Debug, Default)]
 pub struct GoalStack(Vec<Rc<Goal>>);
@@ -213,11 +238,13 @@ pub struct PolarVirtualMachine {
     pub goals: GoalStack,
     binding_manager: BindingManager,
     choices: Choices,
+    /// The id of the last choice pushed.
+    choice_id: u64,
     pub queries: Queries,
 
     pub tracing: bool,
     pub trace_stack: TraceStack, // Stack of traces higher up the tree.
-    pub trace: Vec<Rc<Trace>>,   // Traces for the current level of the trace tree.
+    pub trace: Vec<Arc<Trace>>,  // Traces for the current level of the trace tree.
 
     // Errors from outside the vm.
     pub external_error: Option<String>,
@@ -253,6 +280,12 @@ pub struct PolarVirtualMachine {
     pub query_contains_partial: bool,
     pub inverting: bool,
 
+    /// Answers to calls to tabled rules, shared with sub-VMs.
+    tables: Rc<RefCell<Tables>>,
+    /// The call to a tabled rule this VM is evaluating for its table, which
+    /// must not be answered from the table.
+    untabled_variant: Option<TermList>,
+
     /// Output messages.
     pub messages: MessageQueue,
 }
@@ -303,6 +336,7 @@ impl PolarVirtualMachine {
             stack_limit: MAX_STACK_SIZE,
             csp: Bsp::default(),
             choices: vec![],
+            choice_id: 0,
             queries: vec![],
             tracing,
             trace_stack: vec![],
@@ -319,6 +353,8 @@ impl PolarVirtualMachine {
             polar_log_mute: false,
             query_contains_partial: false,
             inverting: false,
+            tables: Rc::new(RefCell::new(Tables::default())),
+            untabled_variant: None,
             messages,
         };
         vm.bind_constants(constants);
@@ -376,6 +412,7 @@ impl PolarVirtualMachine {
         vm.binding_manager.clone_from(&self.binding_manager);
         vm.query_contains_partial = self.query_contains_partial;
         vm.debugger = self.debugger.clone();
+        vm.tables = self.tables.clone();
         vm
     }
 
@@ -429,6 +466,12 @@ impl PolarVirtualMachine {
         match goal.as_ref() {
             Goal::Backtrack => self.backtrack()?,
             Goal::Cut { choice_index } => self.cut(*choice_index),
+            Goal::SoftCut {
+                choice_id,
+                condition,
+                alternative,
+                negated_consequent,
+            } => self.soft_cut(*choice_id, condition, alternative, negated_consequent)?,
             Goal::Debug { message } => return Ok(self.debug(message)),
             Goal::Halt => return Ok(self.halt()),
             Goal::Error { error } => return Err(error.clone()),
@@ -476,16 +519,16 @@ impl PolarVirtualMachine {
                 args,
             } => self.sort_rules(rules, args, *outer, *inner)?,
             Goal::TraceStackPush => {
-                self.trace_stack.push(Rc::new(self.trace.clone()));
+                self.trace_stack.push(Arc::new(self.trace.clone()));
                 self.trace = vec![];
             }
             Goal::TraceStackPop => {
                 let mut children = self.trace.clone();
                 self.trace = self.trace_stack.pop().unwrap().as_ref().clone();
                 let mut trace = self.trace.pop().unwrap();
-                let trace = Rc::make_mut(&mut trace);
+                let trace = Arc::make_mut(&mut trace);
                 trace.children.append(&mut children);
-                self.trace.push(Rc::new(trace.clone()));
+                self.trace.push(Arc::new(trace.clone()));
                 self.maybe_break(DebugEvent::Pop)?;
             }
             Goal::TraceRule { trace } => {
@@ -509,6 +552,12 @@ impl PolarVirtualMachine {
                 )?
             }
             Goal::Run { runnable } => return self.run_runnable(runnable.clone_runnable()),
+            Goal::TableAnswers { args, variant } => self.table_answers(args, variant)?,
+            Goal::Aggregate {
+                term,
+                values,
+                result,
+            } => self.aggregate(term, values.borrow().clone(), result)?,
         }
         Ok(QueryEvent::None)
     }
@@ -549,8 +598,8 @@ impl PolarVirtualMachine {
     ///
     /// Do not modify the goals stack.  This function defers execution of the
     /// choice until a backtrack occurs.  To immediately execute the choice on
-    /// top of the current stack, use `choose`.
-    fn push_choice<I>(&mut self, alternatives: I)
+    /// top of the current stack, use `choose`. Returns the id of the choice.
+    fn push_choice<I>(&mut self, alternatives: I) -> u64
     where
         I: IntoIterator<Item = Goals>,
         I::IntoIter: std::iter::DoubleEndedIterator,
@@ -562,6 +611,7 @@ impl PolarVirtualMachine {
             .map(GoalStack::new_reversed)
             .collect();
         assert!(self.choices.len() < self.stack_limit, "too many choices");
+        self.choice_id += 1;
         self.choices.push(Choice {
             alternatives,
             bsp: self.bsp(),
@@ -569,7 +619,9 @@ impl PolarVirtualMachine {
             queries: self.queries.clone(),
             trace: self.trace.clone(),
             trace_stack: self.trace_stack.clone(),
+            id: self.choice_id,
         });
+        self.choice_id
     }
 
     /// Push a choice onto the choice stack, and execute immediately by
@@ -713,7 +765,7 @@ impl PolarVirtualMachine {
     }
 
     /// Recursively dereference variables in a term, including subterms, except operations.
-    fn deep_deref(&self, term: &Term) -> Term {
+    pub fn deep_deref(&self, term: &Term) -> Term {
         self.binding_manager.deep_deref(term)
     }
 
@@ -900,6 +952,7 @@ impl PolarVirtualMachine {
                     queries,
                     trace,
                     trace_stack,
+                    id,
                 }) => {
                     self.binding_manager.backtrack(&bsp);
                     if let Some(mut alternative) = alternatives.pop() {
@@ -920,6 +973,7 @@ impl PolarVirtualMachine {
                                 queries,
                                 trace,
                                 trace_stack,
+                                id,
                             })
                         }
                         self.goals.append(&mut alternative);
@@ -936,6 +990,63 @@ impl PolarVirtualMachine {
         let _ = self.choices.truncate(index);
     }
 
+    /// Prune the alternative of an if-then-else, leaving the remaining
+    /// choices of its condition. If the condition only succeeded by
+    /// constraining partial variables, the alternative still applies where
+    /// the constraints don't hold, so it is replaced by `alternative`, which
+    /// checks that the condition fails first. If a `cut` in the condition
+    /// already removed the choice, there is nothing left to prune.
+    ///
+    /// A negated if-then-else negates its consequent for each result of a
+    /// constrained condition, which holds only under that result's constraints.
+    /// Otherwise, the negation holds only if the consequent fails for every
+    /// result of the condition, so it backtracks to before the condition to
+    /// check that instead.
+    fn soft_cut(
+        &mut self,
+        choice_id: u64,
+        condition: &Term,
+        alternative: &Term,
+        negated_consequent: &Option<Term>,
+    ) -> PolarResult<()> {
+        let mut variables = HashSet::new();
+        self.deep_deref(condition).variables(&mut variables);
+        let constrained = variables
+            .iter()
+            .any(|var| matches!(self.variable_state(var), VariableState::Partial));
+        let index = match self.choices.iter().rposition(|c| c.id == choice_id) {
+            Some(index) => index,
+            None => return Ok(()),
+        };
+        match negated_consequent {
+            Some(consequent) if !constrained => {
+                let condition_and_consequent = condition.clone_with_value(Value::Expression(op!(
+                    And,
+                    condition.clone(),
+                    consequent.clone()
+                )));
+                let negation = condition
+                    .clone_with_value(Value::Expression(op!(Not, condition_and_consequent)));
+                self.choices.truncate(index + 1);
+                self.choices[index].alternatives =
+                    vec![GoalStack::new_reversed(vec![Goal::Query {
+                        term: negation,
+                    }])];
+                self.push_goal(Goal::Backtrack)?;
+            }
+            _ => {
+                self.choices[index].alternatives = if constrained {
+                    vec![GoalStack::new_reversed(vec![Goal::Query {
+                        term: alternative.clone(),
+                    }])]
+                } else {
+                    vec![]
+                };
+            }
+        }
+        Ok(())
+    }
+
     /// Clean up the query stack after completing a query.
     fn pop_query(&mut self) {
         self.queries.pop();
@@ -1427,7 +1538,7 @@ impl PolarVirtualMachine {
 
         self.queries.push(term.clone());
         self.push_goal(Goal::PopQuery { term: term.clone() })?;
-        self.trace.push(Rc::new(Trace {
+        self.trace.push(Arc::new(Trace {
             node: Node::Term(term.clone()),
             children: vec![],
         }));
@@ -1478,6 +1589,19 @@ impl PolarVirtualMachine {
     /// Create a choice over the applicable rules.
     fn query_for_predicate(&mut self, predicate: Call) -> PolarResult<()> {
         assert!(predicate.kwargs.is_none());
+        if is_role_graph_predicate(&predicate.name.0) {
+            return self.query_for_role_graph(predicate);
+        }
+        if !self.query_contains_partial && self.kb.read().unwrap().is_tabled(&predicate.name) {
+            let args: TermList = predicate.args.iter().map(|t| self.deep_deref(t)).collect();
+            let variant = tabling::variant(&args);
+            if self.untabled_variant.as_ref() == Some(&variant) {
+                // Evaluate the call for its table.
+                self.untabled_variant = None;
+            } else {
+                return self.query_for_table(predicate, args, variant);
+            }
+        }
         let goals = match self.kb.read().unwrap().get_generic_rule(&predicate.name) {
             None => vec![Goal::Backtrack],
             Some(generic_rule) => {
@@ -1504,6 +1628,144 @@ impl PolarVirtualMachine {
         self.append_goals(goals)
     }
 
+    /// Answer a call to a tabled rule from its table, evaluating the call in a
+    /// sub-VM first unless the table is complete or the call is recursive.
+    fn query_for_table(
+        &mut self,
+        predicate: Call,
+        args: TermList,
+        variant: TermList,
+    ) -> PolarResult<()> {
+        if self.tables.borrow_mut().can_answer(&variant) {
+            return self.table_answers(&args, &variant);
+        }
+        let term = Term::new_temporary(Value::Call(predicate));
+        let mut vm = self.clone_with_goals(vec![Goal::Query { term }]);
+        vm.untabled_variant = Some(variant.clone());
+        let tabler = Box::new(Tabler::new(
+            vm,
+            args.clone(),
+            variant.clone(),
+            self.tables.clone(),
+        ));
+        self.append_goals(vec![
+            Goal::Run { runnable: tabler },
+            Goal::TableAnswers { args, variant },
+        ])
+    }
+
+    fn table_answers(&mut self, args: &[Term], variant: &TermList) -> PolarResult<()> {
+        let answers = self.tables.borrow().answers(variant);
+        self.choose(answers.into_iter().map(|answer| {
+            args.iter()
+                .zip(answer)
+                .map(|(arg, value)| Goal::Unify {
+                    left: arg.clone(),
+                    right: value,
+                })
+                .collect::<Goals>()
+        }))
+    }
+
+    /// Combine the values collected for the aggregate `term` and unify the
+    /// aggregated value with `result`. The `min` and `max` of no values fail.
+    fn aggregate(&mut self, term: &Term, values: TermList, result: &Term) -> PolarResult<()> {
+        let operator = term.value().as_expression().unwrap().operator;
+        let value = match operator {
+            Operator::FindAll => Value::List(values),
+            Operator::Count => Value::Number(Numeric::Integer(values.len() as i64)),
+            Operator::Sum => {
+                let mut sum = Numeric::Integer(0);
+                for value in &values {
+                    let number = match value.value() {
+                        Value::Number(number) => *number,
+                        _ => {
+                            return Err(self.type_error(
+                                value,
+                                format!("sum expects numbers, got: {}", value.to_polar()),
+                            ))
+                        }
+                    };
+                    sum = match sum + number {
+                        Some(sum) => sum,
+                        None => {
+                            return Err(self.set_error_context(
+                                term,
+                                error::RuntimeError::ArithmeticError {
+                                    msg: term.to_polar(),
+                                },
+                            ))
+                        }
+                    };
+                }
+                Value::Number(sum)
+            }
+            Operator::Min | Operator::Max => {
+                let op = if operator == Operator::Min {
+                    Operator::Lt
+                } else {
+                    Operator::Gt
+                };
+                let mut values = values.into_iter();
+                let mut best = match values.next() {
+                    Some(value) => value,
+                    None => return self.push_goal(Goal::Backtrack),
+                };
+                for value in values {
+                    if compare(op, &value, &best)
+                        .map_err(|error| self.set_error_context(term, error))?
+                    {
+                        best = value;
+                    }
+                }
+                best.value().clone()
+            }
+            _ => unreachable!("{} is not an aggregate", operator.to_polar()),
+        };
+        self.push_goal(Goal::Unify {
+            left: term.clone_with_value(value),
+            right: result.clone(),
+        })
+    }
+
+    /// Answer a role graph predicate from the compiled roles configuration.
+    /// All arguments but the last must be bound to strings; there is an
+    /// alternative for each value of the last argument.
+    fn query_for_role_graph(&mut self, predicate: Call) -> PolarResult<()> {
+        let mut args: Vec<Term> = predicate.args.iter().map(|t| self.deep_deref(t)).collect();
+        let output = match args.pop() {
+            Some(output) => output,
+            None => return self.push_goal(Goal::Backtrack),
+        };
+        let mut keys = vec![];
+        for arg in &args {
+            match arg.value() {
+                Value::String(key) => keys.push(key.as_str()),
+                _ => {
+                    return Err(self.type_error(
+                        arg,
+                        format!(
+                            "{} expects its first {} arguments to be strings, got: {}",
+                            predicate.name,
+                            args.len(),
+                            arg.to_polar()
+                        ),
+                    ))
+                }
+            }
+        }
+        let answers = match self.kb.read().unwrap().role_graph() {
+            Some(role_graph) => role_graph.answers(&predicate.name.0, &keys).to_vec(),
+            None => vec![],
+        };
+        self.choose(answers.into_iter().map(|answer| {
+            vec![Goal::Unify {
+                left: output.clone(),
+                right: Term::new_temporary(Value::String(answer)),
+            }]
+        }))
+    }
+
     fn query_for_operation(&mut self, term: &Term) -> PolarResult<QueryEvent> {
         let operation = term.value().as_expression().unwrap();
         let mut args = operation.args.clone();
@@ -1522,6 +1784,17 @@ impl PolarVirtualMachine {
                 // Query in a sub-VM and invert the results.
                 assert_eq!(args.len(), 1);
                 let term = args.pop().unwrap();
+                if let Value::Expression(Operation {
+                    operator: Operator::IfThenElse,
+                    args,
+                }) = term.value()
+                {
+                    // Negate the branches of an if-then-else rather than the whole,
+                    // so that the constraints of its condition stay with the branch
+                    // they select.
+                    self.query_for_if_then_else(&term, args.clone(), true)?;
+                    return Ok(QueryEvent::None);
+                }
                 let add_constraints = Rc::new(RefCell::new(Bindings::new()));
                 let inverter = Box::new(Inverter::new(
                     self,
@@ -1704,10 +1977,99 @@ impl PolarVirtualMachine {
                     term: double_negation,
                 })?;
             }
+            Operator::IfThenElse => self.query_for_if_then_else(term, args, false)?,
+            Operator::FindAll | Operator::Count | Operator::Sum | Operator::Min | Operator::Max => {
+                let result = args.pop().unwrap();
+                let goal = args.pop().unwrap();
+                // A count has no template, so collect any value for each result.
+                let template = args
+                    .pop()
+                    .unwrap_or_else(|| Term::new_temporary(Value::Boolean(true)));
+
+                // Partial variables have no results to aggregate.
+                let mut variables = HashSet::new();
+                self.deep_deref(&goal).variables(&mut variables);
+                if let Some(var) = variables
+                    .iter()
+                    .find(|var| matches!(self.variable_state(var), VariableState::Partial))
+                {
+                    return Err(self.set_error_context(
+                        term,
+                        error::RuntimeError::Unsupported {
+                            msg: format!(
+                                "cannot aggregate over partially evaluated variable {}",
+                                var.to_polar()
+                            ),
+                        },
+                    ));
+                }
+
+                let values = Rc::new(RefCell::new(vec![]));
+                let collector = Box::new(Collector::new(
+                    self.clone_with_goals(vec![Goal::Query { term: goal }]),
+                    template,
+                    values.clone(),
+                ));
+                self.append_goals(vec![
+                    Goal::Run {
+                        runnable: collector,
+                    },
+                    Goal::Aggregate {
+                        term: term.clone(),
+                        values,
+                        result,
+                    },
+                ])?;
+            }
         }
         Ok(QueryEvent::None)
     }
 
+    /// Query an if-then-else, or if `negated`, its negation, by negating both
+    /// of its branches.
+    fn query_for_if_then_else(
+        &mut self,
+        term: &Term,
+        mut args: TermList,
+        negated: bool,
+    ) -> PolarResult<()> {
+        assert_eq!(args.len(), 3);
+        let alternative = args.pop().unwrap();
+        let consequent = args.pop().unwrap();
+        let condition = args.pop().unwrap();
+        let negate = |branch: &Term| {
+            if negated {
+                term.clone_with_value(Value::Expression(op!(Not, branch.clone())))
+            } else {
+                branch.clone()
+            }
+        };
+        let alternative = negate(&alternative);
+        // Used in place of the alternative if the condition only
+        // succeeds under constraints.
+        let not_condition = term.clone_with_value(Value::Expression(op!(Not, condition.clone())));
+        let guarded_alternative = term.clone_with_value(Value::Expression(op!(
+            And,
+            not_condition,
+            alternative.clone()
+        )));
+        let choice_id = self.push_choice(vec![vec![Goal::Query { term: alternative }]]);
+        self.append_goals(vec![
+            Goal::Query {
+                term: condition.clone(),
+            },
+            Goal::SoftCut {
+                choice_id,
+                condition,
+                alternative: guarded_alternative,
+                negated_consequent: negated.then(|| consequent.clone()),
+            },
+            Goal::Query {
+                term: negate(&consequent),
+            },
+        ])
+    }
+
     /// Handle variables & constraints as arguments to various operations.
     /// Calls the `eval` method to handle ground terms.
     ///
@@ -2630,7 +2992,7 @@ impl PolarVirtualMachine {
             for rule in rules.iter() {
                 let mut goals = Vec::with_capacity(2 * args.len() + 4);
                 goals.push(Goal::TraceRule {
-                    trace: Rc::new(Trace {
+                    trace: Arc::new(Trace {
                         node: Node::Rule(rule.clone()),
                         children: vec![],
                     }),
@@ -2784,53 +3146,11 @@ impl PolarVirtualMachine {
     }
 
     pub fn term_source(&self, term: &Term, include_info: bool) -> String {
-        let source = self.source(term);
-        let span = term.span();
-
-        let mut source_string = match (&source, &span) {
-            (Some(source), Some((left, right))) => {
-                source.src.chars().take(*right).skip(*left).collect()
-            }
-            _ => term.to_polar(),
-        };
-
-        if include_info {
-            if let Some(source) = source {
-                let offset = term.offset();
-                let (row, column) = crate::lexer::loc_to_pos(&source.src, offset);
-                source_string.push_str(&format!(" at line {}, column {}", row + 1, column));
-                if let Some(filename) = source.filename {
-                    source_string.push_str(&format!(" in file {}", filename));
-                }
-            }
-        }
-
-        source_string
+        self.kb.read().unwrap().term_source(term, include_info)
     }
 
     pub fn rule_source(&self, rule: &Rule) -> String {
-        let head = format!(
-            "{}({})",
-            rule.name,
-            rule.params.iter().fold(String::new(), |mut acc, p| {
-                if !acc.is_empty() {
-                    acc += ", ";
-                }
-                acc += &self.term_source(&p.parameter, false);
-                if let Some(spec) = &p.specializer {
-                    acc += ": ";
-                    acc += &self.term_source(spec, false);
-                }
-                acc
-            })
-        );
-        match rule.body.value() {
-            Value::Expression(Operation {
-                operator: Operator::And,
-                args,
-            }) if !args.is_empty() => head + " if " + &self.term_source(&rule.body, false) + ";",
-            _ => head + ";",
-        }
+        self.kb.read().unwrap().rule_source(rule)
     }
 
     fn set_error_context(
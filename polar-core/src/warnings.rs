@@ -1,10 +1,11 @@
 use super::error::*;
 use super::formatting::source_lines;
 use super::kb::*;
+use super::modules::split;
 use super::rules::*;
 use super::sources::Source;
 use super::terms::*;
-use super::visitor::{walk_rule, walk_term, Visitor};
+use super::visitor::{walk_call, walk_operation, walk_rule, walk_term, Visitor};
 
 use std::collections::{hash_map::Entry, HashMap};
 
@@ -135,3 +136,131 @@ pub fn check_singletons(rule: &Rule, kb: &KnowledgeBase) -> PolarResult<Vec<Stri
     walk_rule(&mut visitor, rule);
     visitor.warnings()
 }
+
+/// Rules defined by the built-in roles policy, which may be loaded after the
+/// policies that call them.
+const ROLES_POLICY_RULES: &[&str] = &["role_allows", "actor_can_assume_role"];
+
+/// The number of single-character edits that turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Record calls to rules that aren't defined.
+struct UndefinedCallVisitor<'kb> {
+    kb: &'kb KnowledgeBase,
+    calls: Vec<(Symbol, Term)>,
+}
+
+impl<'kb> UndefinedCallVisitor<'kb> {
+    fn is_defined(&self, name: &Symbol) -> bool {
+        self.kb.is_rule_defined(name)
+            || self.kb.is_constant(name)
+            || ROLES_POLICY_RULES.contains(&name.0.as_str())
+    }
+
+    /// The name of a rule that a call to `name` from `module` may have meant.
+    /// Calls to a module's rules from outside of it are qualified.
+    fn suggestion(&self, name: &Symbol, module: Option<&Symbol>) -> Option<String> {
+        let candidates = self.kb.rule_names().filter_map(|rule| match split(rule) {
+            None => Some((rule.0.clone(), rule.0.clone())),
+            Some((rule_module, rule_name)) if Some(&rule_module) == module => {
+                Some((rule_name.0.clone(), rule_name.0))
+            }
+            Some((rule_module, rule_name)) => {
+                let public = self
+                    .kb
+                    .get_module(&rule_module)
+                    .is_some_and(|m| !m.private_rules.contains(&rule_name));
+                public.then(|| (rule.0.clone(), rule_name.0))
+            }
+        });
+        let max_distance = name.0.len() / 3;
+        candidates
+            .map(|(suggestion, key)| (edit_distance(&name.0, &key), suggestion))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min()
+            .map(|(_, suggestion)| suggestion)
+    }
+
+    fn warnings(&self, module: Option<&Symbol>) -> Vec<String> {
+        let mut calls = self.calls.iter().collect::<Vec<_>>();
+        calls.sort_by_key(|(_name, term)| term.offset());
+        calls
+            .into_iter()
+            .map(|(name, term)| {
+                let mut msg = format!("Call to undefined rule {}", name);
+                if let Some(suggestion) = self.suggestion(name, module) {
+                    msg.push_str(&format!(", did you mean {}?", suggestion));
+                }
+                let src = term
+                    .get_source_id()
+                    .and_then(|id| self.kb.sources.get_source(id));
+                if let Some(ref source) = src {
+                    msg.push('\n');
+                    msg.push_str(&source_lines(source, term.offset(), 0));
+                }
+                msg
+            })
+            .collect()
+    }
+}
+
+impl<'kb> Visitor for UndefinedCallVisitor<'kb> {
+    fn visit_term(&mut self, t: &Term) {
+        if let Value::Call(call) = t.value() {
+            if !self.is_defined(&call.name) {
+                self.calls.push((call.name.clone(), t.clone()));
+            }
+        }
+        walk_term(self, t);
+    }
+
+    fn visit_operation(&mut self, o: &Operation) {
+        match o.operator {
+            // Method calls and constructors aren't calls to rules.
+            Operator::Dot | Operator::New => {
+                for arg in &o.args {
+                    match arg.value() {
+                        Value::Call(call) => walk_call(self, call),
+                        _ => self.visit_term(arg),
+                    }
+                }
+            }
+            _ => walk_operation(self, o),
+        }
+    }
+}
+
+/// The rules and inline queries of a loaded source, whose calls haven't been
+/// checked for undefined rules yet.
+pub struct UncheckedCalls {
+    pub src_id: u64,
+    pub rules: Vec<Rule>,
+    pub queries: Vec<Term>,
+    /// The module the source defines, if any.
+    pub module: Option<Symbol>,
+}
+
+/// Warn about calls to undefined rules in the rules and inline queries of a source.
+pub fn check_undefined_calls(unchecked: &UncheckedCalls, kb: &KnowledgeBase) -> Vec<String> {
+    let mut visitor = UndefinedCallVisitor { kb, calls: vec![] };
+    for rule in &unchecked.rules {
+        walk_rule(&mut visitor, rule);
+    }
+    for query in &unchecked.queries {
+        visitor.visit_term(query);
+    }
+    visitor.warnings(unchecked.module.as_ref())
+}
@@ -1539,6 +1539,95 @@ fn test_unknown_specializer_suggestions() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn test_undefined_rule_warnings() -> TestResult {
+    let p = Polar::new();
+    p.load_str("allow(actor, action, resource) if alow(actor, action, resource);")?;
+    // Calls are checked when the next query is made.
+    assert!(p.next_message().is_none());
+    p.new_query("1 = 1", false)?;
+    let msg = p.next_message().unwrap();
+    assert!(matches!(&msg.kind, MessageKind::Warning));
+    assert_eq!(
+        &msg.msg,
+        "Call to undefined rule alow, did you mean allow?\n001: allow(actor, action, resource) if alow(actor, action, resource);\n                                       ^"
+    );
+    assert!(p.next_message().is_none());
+
+    p.register_constant(sym!("Widget"), term!(1));
+    p.load_str(
+        r#"has_role(_, "admin");
+           type tabled edge(_, _);
+           f(x) if
+             has_rol(x, "admin") and g(x) and edge(x, _) and Widget(x) and
+             x.foo() and new Foo(x) = _ and role_allows(x, "read", x);
+           ?= h(1);"#,
+    )?;
+    p.next_inline_query(false);
+    let msgs = std::iter::from_fn(|| p.next_message())
+        .map(|msg| msg.msg.lines().next().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        msgs,
+        vec![
+            "Call to undefined rule has_rol, did you mean has_role?",
+            "Call to undefined rule g",
+            "Call to undefined rule h",
+        ]
+    );
+
+    // The roles policy calls rules the application's policy may not define.
+    p.enable_roles()?;
+    p.new_query("1 = 1", false)?;
+    assert!(p.next_message().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_undefined_rule_warnings_across_files() -> TestResult {
+    // Files can be loaded before the files defining the rules they call.
+    let p = Polar::new();
+    p.load("allow(x) if is_admin(x);", Some("a.polar".to_owned()))?;
+    p.load("is_admin(x) if x = 1;", Some("b.polar".to_owned()))?;
+    p.new_query("allow(1)", false)?;
+    assert!(p.next_message().is_none());
+
+    // Removed files aren't checked.
+    p.load("f(x) if g(x);", Some("c.polar".to_owned()))?;
+    p.remove_file("c.polar");
+    p.load("h(x) if j(x);", Some("d.polar".to_owned()))?;
+    p.new_query("allow(1)", false)?;
+    let msg = p.next_message().unwrap();
+    assert!(msg.msg.starts_with("Call to undefined rule j\n"));
+    assert!(p.next_message().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_undefined_rule_warnings_in_modules() -> TestResult {
+    let p = Polar::new();
+    p.load(
+        r#"module billing;
+           can_edit(user, invoice) if is_ownr(user, invoice);
+           private is_owner(user, invoice) if invoice.owner = user;"#,
+        None,
+    )?;
+    p.load("f(x) if can_edit(x, x) and is_owner(x, x);", None)?;
+    p.new_query("1 = 1", false)?;
+    let msgs = std::iter::from_fn(|| p.next_message())
+        .map(|msg| msg.msg.lines().next().unwrap().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        msgs,
+        vec![
+            "Call to undefined rule is_ownr, did you mean is_owner?",
+            "Call to undefined rule can_edit, did you mean billing::can_edit?",
+            "Call to undefined rule is_owner",
+        ]
+    );
+    Ok(())
+}
+
 #[test]
 fn test_partial_grounding() -> TestResult {
     let rules = r#"
@@ -2140,3 +2229,392 @@ fn test_lookup_in_rule_head() -> TestResult {
     assert_eq!(results.len(), 0);
     Ok(())
 }
+
+#[test]
+fn test_tabling() -> TestResult {
+    let mut p = Polar::new();
+    p.load_str(
+        r#"type tabled member_of(_, _);
+           member_of(user, group) if in_group(user, group);
+           member_of(user, group) if member_of(user, subgroup) and subgroup_of(subgroup, group);
+           in_group("alice", "eng");
+           in_group("bob", "sales");
+           subgroup_of("eng", "staff");
+           subgroup_of("staff", "eng");
+           subgroup_of("staff", "all");"#,
+    )?;
+
+    // Left recursion over a cycle of groups terminates.
+    qvar(
+        &mut p,
+        r#"member_of("alice", group)"#,
+        "group",
+        values!["eng", "staff", "all"],
+    );
+    qeval(&mut p, r#"member_of("alice", "all")"#);
+    qnull(&mut p, r#"member_of("bob", "eng")"#);
+    qvar(
+        &mut p,
+        r#"member_of(user, "staff")"#,
+        "user",
+        values!["alice"],
+    );
+
+    // Mutually dependent calls are completed together.
+    p.load_str(
+        r#"type tabled reachable(_, _);
+           reachable(x, y) if edge(x, y);
+           reachable(x, y) if edge(x, z) and reachable(z, y);
+           edge("a", "b");
+           edge("b", "c");
+           edge("c", "a");
+           edge("c", "d");"#,
+    )?;
+    qvar(
+        &mut p,
+        r#"reachable("a", x)"#,
+        "x",
+        values!["b", "c", "a", "d"],
+    );
+    qvar(
+        &mut p,
+        r#"reachable("b", x)"#,
+        "x",
+        values!["c", "a", "d", "b"],
+    );
+    qnull(&mut p, r#"reachable("d", _)"#);
+    Ok(())
+}
+
+#[test]
+fn test_aggregates() -> TestResult {
+    let mut p = Polar::new();
+    p.load_str(
+        r#"item("apple", 3);
+           item("pear", 5);
+           item("plum", 1);
+           many_items() if count(item(_, _)) > 2;"#,
+    )?;
+
+    qvar(&mut p, "n = count(item(_, _))", "n", values![3]);
+    qvar(&mut p, "n = count(x in [1, 2] and x > 5)", "n", values![0]);
+    qeval(&mut p, "many_items()");
+    qvar(
+        &mut p,
+        "names = findall(name, item(name, _))",
+        "names",
+        vec![value!(["apple", "pear", "plum"])],
+    );
+    qvar(&mut p, "total = sum(n, item(_, n))", "total", values![9]);
+    qvar(&mut p, "total = sum(x, x in [])", "total", values![0]);
+    qvar(
+        &mut p,
+        "total = sum(x, x in [1, 2.5])",
+        "total",
+        values![3.5],
+    );
+    qvar(&mut p, "m = min(n, item(_, n))", "m", values![1]);
+    qvar(
+        &mut p,
+        r#"m = max(name, item(name, _))"#,
+        "m",
+        values!["plum"],
+    );
+    qnull(&mut p, "min(x, x in []) = _");
+
+    // Templates are evaluated for each result of the goal.
+    qvar(
+        &mut p,
+        "total = sum(x.price, x in [{price: 2}, {price: 3}])",
+        "total",
+        values![5],
+    );
+    qvar(
+        &mut p,
+        "l = findall(x * 2, x in [1, 2])",
+        "l",
+        vec![value!([2, 4])],
+    );
+
+    // Variables bound in the goal stay unbound outside of it.
+    qvars(
+        &mut p,
+        "n = count(x in [1, 2]) and x = 3",
+        &["n", "x"],
+        vec![values![2, 3]],
+    );
+    // Variables bound before the aggregate are used in its goal.
+    qvar(
+        &mut p,
+        "y = 2 and n = count(x in [1, 2, 3] and x > y)",
+        "n",
+        values![1],
+    );
+
+    qruntime!(
+        &mut p,
+        "sum(x, x in [1, \"a\"]) = _",
+        RuntimeError::TypeError { .. }
+    );
+    qruntime!(
+        &mut p,
+        "max(x, x in [1, {}]) = _",
+        RuntimeError::Unsupported { .. }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_aggregate_keywords() -> TestResult {
+    // Aggregation keywords can still be used as method and attribute names.
+    let mut p = Polar::new();
+    p.load_str("f(x) if x.sum() = 1 and x.count(2) = 2 and x.max = 3;")?;
+
+    // They can also name variables and rules, which are called with a different
+    // number of arguments than the aggregate.
+    p.load_str(
+        r#"g(count) if count > 1;
+           h(max, min) if max > min;
+           sum(a, b, c) if c = a + b;
+           min(a) if a < 0;"#,
+    )?;
+    qeval(&mut p, "g(2)");
+    qnull(&mut p, "g(1)");
+    qeval(&mut p, "h(2, 1)");
+    qvar(&mut p, "sum(1, 2, c)", "c", values![3]);
+    qvar(&mut p, "x in [1, -1] and min(x)", "x", values![-1]);
+    qvar(&mut p, "n = min(x, x in [3, 2])", "n", values![2]);
+
+    qparse!("f(x) if x = count(1);", ParseError::WrongValueType { .. });
+    qparse!(
+        "f(x) if x = sum(a and b, a);",
+        ParseError::WrongValueType { .. }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_if_then_else() -> TestResult {
+    let mut p = Polar::new();
+    p.load_str(
+        r#"sign(x, s) if
+             if x < 0 then s = "negative"
+             else if x = 0 then s = "zero"
+             else s = "positive";
+           g(1);
+           g(2);
+           k(x) if if cut and g(x) then x > 0 else x = 0;
+           l(x) if if cut and 1 = 2 then x = 1 else x = 0;"#,
+    )?;
+
+    qvar(&mut p, "sign(-2, s)", "s", values!["negative"]);
+    qvar(&mut p, "sign(0, s)", "s", values!["zero"]);
+    qvar(&mut p, "sign(3, s)", "s", values!["positive"]);
+
+    // Every result of the condition is kept...
+    qvar(&mut p, "if g(y) then x = y else x = 0", "x", values![1, 2]);
+    qvar(&mut p, "if y in [] then x = y else x = 0", "x", values![0]);
+    // ...but the alternative is pruned once the condition succeeds.
+    qnull(&mut p, "if g(y) then y = 3 else 1 = 1");
+
+    // Bindings made by a failed condition are undone.
+    qvar(
+        &mut p,
+        "if y = 1 and 1 = 2 then x = 1 else x = y",
+        "x",
+        values![sym!("y")],
+    );
+
+    // Lookups are scoped to their branch.
+    qvar(
+        &mut p,
+        "if {a: 1}.a = 1 then x = {b: 2}.b else x = {c: 3}.c",
+        "x",
+        values![2],
+    );
+    qeval(&mut p, "not (if 1 = 1 then 1 = 2 else 1 = 1)");
+    qeval(&mut p, "(if 1 = 1 then x = 1 else x = 2) and x = 1");
+
+    // A `cut` in the condition commits to the rule, so the alternative is pruned
+    // but the choices the condition makes after it are kept.
+    qvar(&mut p, "k(x)", "x", values![1, 2]);
+    qnull(&mut p, "l(x)");
+    Ok(())
+}
+
+#[test]
+fn test_if_then_else_keywords() -> TestResult {
+    // `then` and `else` only separate the branches of a conditional, and can be
+    // used as names anywhere else.
+    let mut p = Polar::new();
+    p.load_str(
+        r#"f(then) if then = 1;
+           g(x) if x.else = 1;
+           then(else) if else = 2;
+           else(x) if if x.then = 1 then x.else = 2 else x.else = 3;"#,
+    )?;
+    qeval(&mut p, "f(1)");
+    qnull(&mut p, "f(2)");
+    qeval(&mut p, "g({else: 1})");
+    qvar(&mut p, "then(x)", "x", values![2]);
+    qeval(&mut p, "else({then: 1, else: 2})");
+    qeval(&mut p, "else({then: 2, else: 3})");
+    qnull(&mut p, "else({then: 1, else: 3})");
+    qvar(
+        &mut p,
+        "if then = 1 then else = then else else = 0",
+        "else",
+        values![1],
+    );
+    Ok(())
+}
+
+#[test]
+fn test_modules() -> TestResult {
+    let mut p = Polar::new();
+    p.load(
+        r#"module billing;
+           can_edit(user, invoice) if is_owner(user, invoice);
+           private is_owner(user, invoice) if invoice.owner = user;"#,
+        Some("billing.polar".to_owned()),
+    )?;
+    p.load(
+        r#"module docs;
+           can_edit(_user, doc) if doc.public = true;"#,
+        Some("docs.polar".to_owned()),
+    )?;
+    p.load(
+        r#"import billing;
+           import docs;
+           allow(user, "edit", resource) if
+             billing::can_edit(user, resource) or docs::can_edit(user, resource);
+           is_owner(_, _) if false;"#,
+        Some("main.polar".to_owned()),
+    )?;
+
+    // Rules with the same name in different modules don't merge.
+    qeval(&mut p, r#"allow("alice", "edit", {owner: "alice"})"#);
+    qeval(&mut p, r#"allow("bob", "edit", {public: true})"#);
+    qnull(&mut p, r#"allow("bob", "edit", {owner: "alice"})"#);
+    qeval(&mut p, r#"billing::can_edit("alice", {owner: "alice"})"#);
+    qnull(&mut p, r#"docs::can_edit("alice", {owner: "alice"})"#);
+    // The module's helper doesn't replace a rule outside of it.
+    qnull(&mut p, r#"is_owner("alice", {owner: "alice"})"#);
+
+    let private = p
+        .new_query(r#"billing::is_owner("alice", {owner: "alice"})"#, false)
+        .err()
+        .unwrap();
+    assert!(matches!(
+        private.kind,
+        ErrorKind::Validation(ValidationError::InvalidModule { .. })
+    ));
+
+    // Removing a module's file unloads it.
+    p.remove_file("billing.polar");
+    let unloaded = p
+        .new_query(r#"billing::can_edit("alice", {})"#, false)
+        .err()
+        .unwrap();
+    assert!(matches!(
+        unloaded.kind,
+        ErrorKind::Validation(ValidationError::UnresolvedImport { .. })
+    ));
+    p.load(
+        "module billing; can_edit(_, _);",
+        Some("billing2.polar".to_owned()),
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_module_keywords() -> TestResult {
+    // Module keywords only declare modules, imports and private rules at the start
+    // of a line, and can be used as names anywhere else.
+    let mut p = Polar::new();
+    p.load_str(
+        r#"g(x) if x.private = true;
+           h(module, import) if module = import;
+           private(x) if x = 1;
+           module(x) if x = 2;"#,
+    )?;
+    qeval(&mut p, "g({private: true})");
+    qnull(&mut p, "g({private: false})");
+    qeval(&mut p, "h(1, 1)");
+    qeval(&mut p, "private(1)");
+    qvar(&mut p, "module(x)", "x", values![2]);
+    qvar(&mut p, "private = 3", "private", values![3]);
+    Ok(())
+}
+
+#[test]
+fn test_module_errors() -> TestResult {
+    let p = Polar::new();
+    p.load(
+        r#"module billing;
+           can_edit(user, invoice) if is_owner(user, invoice);
+           private is_owner(user, invoice) if invoice.owner = user;"#,
+        Some("billing.polar".to_owned()),
+    )?;
+
+    let load_err = |src: &str| p.load(src, None).unwrap_err().kind;
+    assert!(matches!(
+        load_err("import accounts;"),
+        ErrorKind::Validation(ValidationError::UnresolvedImport { module }) if module == "accounts"
+    ));
+    assert!(matches!(
+        load_err("f(x) if billing::can_edit(x, x);"),
+        ErrorKind::Validation(ValidationError::UnresolvedImport { module }) if module == "billing"
+    ));
+    assert!(matches!(
+        load_err("import billing; f(x) if billing::is_owner(x, x);"),
+        ErrorKind::Validation(ValidationError::InvalidModule { msg, .. }) if msg.contains("private")
+    ));
+    assert!(matches!(
+        load_err("import billing; f(x) if billing::can_delete(x, x);"),
+        ErrorKind::Validation(ValidationError::InvalidModule { msg, .. }) if msg.contains("not defined")
+    ));
+    assert!(matches!(
+        load_err("billing::can_delete(_, _);"),
+        ErrorKind::Validation(ValidationError::InvalidModule { .. })
+    ));
+    assert!(matches!(
+        load_err("f(1); module accounts;"),
+        ErrorKind::Validation(ValidationError::InvalidModule { .. })
+    ));
+    assert!(matches!(
+        load_err("module billing;"),
+        ErrorKind::Validation(ValidationError::InvalidModule { .. })
+    ));
+    assert!(matches!(
+        load_err("private f(1);"),
+        ErrorKind::Validation(ValidationError::InvalidRule { .. })
+    ));
+    assert!(matches!(
+        load_err("module accounts; f(1); private f(2);"),
+        ErrorKind::Validation(ValidationError::InvalidModule { .. })
+    ));
+
+    // A file that fails to load doesn't leave its module behind.
+    assert!(matches!(
+        load_err("module accounts; f(x) if billing::can_edit(x, x);"),
+        ErrorKind::Validation(ValidationError::UnresolvedImport { .. })
+    ));
+    p.load("module accounts; f(1);", None)?;
+    Ok(())
+}
+
+#[test]
+fn test_has_rule() -> TestResult {
+    let p = Polar::new();
+    p.load_str(
+        r#"allow(_actor, _action, _resource);
+           deny(actor, _action) if actor = "intern";"#,
+    )?;
+    assert!(p.has_rule("allow", 3));
+    assert!(!p.has_rule("allow", 2));
+    assert!(!p.has_rule("deny", 3));
+    assert!(p.has_rule("deny", 2));
+    assert!(!p.has_rule("role_allows", 3));
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use wasm_bindgen::JsValue;
 
 use polar_core::error::{
-    ErrorKind, FormattedPolarError, OperationalError, ParameterError, ParseError, PolarError,
-    RolesValidationError, RuntimeError, ValidationError,
+    DataFilteringError, ErrorKind, FormattedPolarError, OperationalError, ParameterError,
+    ParseError, PolarError, RolesValidationError, RuntimeError, ValidationError,
 };
 
 pub struct Error {
@@ -53,6 +53,9 @@ fn kind(err: &PolarError) -> String {
         RolesValidation(RolesValidationError(..)) => "RolesValidationError::RolesValidationError",
         Validation(InvalidRule { .. }) => "ValidationError::InvalidRule",
         Validation(InvalidPrototype { .. }) => "ValidationError::InvalidPrototype",
+        Validation(UnresolvedImport { .. }) => "ValidationError::UnresolvedImport",
+        Validation(InvalidModule { .. }) => "ValidationError::InvalidModule",
+        DataFiltering(DataFilteringError { .. }) => "DataFilteringError::DataFilteringError",
     }
     .to_owned()
 }
@@ -1,3 +1,5 @@
+use polar_core::data_filtering::{ExplainFormat, FilterPlan};
+use polar_core::roles_model::RolesModelFormat;
 use polar_core::{polar, terms::Symbol};
 use wasm_bindgen::prelude::*;
 
@@ -44,6 +46,15 @@ impl Polar {
             })
     }
 
+    #[wasm_bindgen(js_class = Polar, js_name = rolesModel)]
+    pub fn wasm_roles_model(&self, format: &str) -> JsResult<String> {
+        format
+            .parse::<RolesModelFormat>()
+            .and_then(|format| self.0.roles_model().map(|model| model.render(format)))
+            .map_err(Error::from)
+            .map_err(Error::into)
+    }
+
     #[wasm_bindgen(js_class = Polar, js_name = clearRules)]
     pub fn wasm_clear_rules(&self) {
         self.0.clear_rules()
@@ -84,6 +95,34 @@ impl Polar {
         self.0.get_external_id() as f64
     }
 
+    #[wasm_bindgen(js_class = Polar, js_name = buildFilterPlan)]
+    pub fn wasm_build_filter_plan(
+        &self,
+        types: &str,
+        results: &str,
+        variable: &str,
+        class_tag: &str,
+    ) -> JsResult<String> {
+        let types = serde_json::from_str(types).map_err(serde_serialization_error)?;
+        let partial_results = serde_json::from_str(results).map_err(serde_serialization_error)?;
+        self.0
+            .build_filter_plan(types, partial_results, variable, class_tag)
+            .map_err(Error::from)
+            .map_err(Error::into)
+            .and_then(|plan| serde_json::to_string(&plan).map_err(serde_serialization_error))
+    }
+
+    #[wasm_bindgen(js_class = Polar, js_name = explainFilterPlan)]
+    pub fn wasm_explain_filter_plan(&self, filter_plan: &str, format: &str) -> JsResult<String> {
+        let filter_plan: FilterPlan =
+            serde_json::from_str(filter_plan).map_err(serde_serialization_error)?;
+        format
+            .parse::<ExplainFormat>()
+            .map(|format| filter_plan.explain().render(format))
+            .map_err(Error::from)
+            .map_err(Error::into)
+    }
+
     #[wasm_bindgen(js_class = Polar, js_name = nextMessage)]
     pub fn wasm_next_message(&self) -> JsResult<JsValue> {
         let message = self.0.next_message();
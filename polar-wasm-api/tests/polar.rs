@@ -131,3 +131,36 @@ fn get_external_id_succeeds() {
     assert_eq!(polar.wasm_get_external_id(), 1.0);
     assert_eq!(polar.wasm_get_external_id(), 2.0);
 }
+
+#[wasm_bindgen_test]
+fn explain_filter_plan_succeeds() {
+    let polar = polar_wasm_api::Polar::wasm_new();
+    let plan = r#"{"result_sets":[{"requests":{"0":{"class_tag":"Foo","constraints":[
+        {"kind":"Eq","field":"id","value":{"Term":{"value":{"String":"a"}}}}]}},
+        "resolve_order":["0"],"result_id":"0"}]}"#;
+    let text = polar.wasm_explain_filter_plan(plan, "text").unwrap();
+    assert_eq!(
+        text,
+        "UNION\n  Result Set 0 (result 0)\n    0: Fetch Foo\n      id = \"a\"\n"
+    );
+    let dot = polar.wasm_explain_filter_plan(plan, "dot").unwrap();
+    assert!(dot.starts_with("digraph filter_plan {"));
+}
+
+#[wasm_bindgen_test]
+fn explain_filter_plan_errors() {
+    let polar = polar_wasm_api::Polar::wasm_new();
+    let err = polar
+        .wasm_explain_filter_plan(r#"{"result_sets":[]}"#, "yaml")
+        .unwrap_err();
+    let err: Error = err.dyn_into().unwrap();
+    assert_eq!(err.name(), "ParameterError::ParameterError");
+}
+
+#[wasm_bindgen_test]
+fn roles_model_errors_before_enable_roles() {
+    let polar = polar_wasm_api::Polar::wasm_new();
+    let err = polar.wasm_roles_model("json").unwrap_err();
+    let err: Error = err.dyn_into().unwrap();
+    assert_eq!(err.name(), "RolesValidationError::RolesValidationError");
+}